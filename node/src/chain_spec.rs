@@ -219,7 +219,8 @@ fn testnet_genesis(
         asset_registry: Default::default(),
         assets: spectre_runtime::AssetsConfig { balances: vec![] },
         spectre: spectre_runtime::SpectreConfig {
-            relayer: Some(root_key),
+            relayers: vec![root_key],
+            relayer_threshold: 1,
             initial_capital: 0,
             supported_assets: vec![],
             fee: 10, // percentage
@@ -0,0 +1,31 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use {parity_scale_codec::Codec, sp_arithmetic::Permill, sp_std::vec::Vec};
+
+sp_api::decl_runtime_apis! {
+    /// Queries over the spectre capital pools
+    pub trait SpectreApi<AccountId, CurrencyId, Balance>
+    where
+        AccountId: Codec,
+        CurrencyId: Codec,
+        Balance: Codec,
+    {
+        /// Balance of the insurance fund of the asset
+        fn insurance_fund_balance(asset_id: CurrencyId) -> Balance;
+
+        /// Insurance fund balance relative to the pool capital allocated to traders
+        fn insurance_coverage_ratio(asset_id: CurrencyId) -> Permill;
+
+        /// Profit the investor can claim from the pool of the asset
+        fn claimable_profit(investor_id: AccountId, asset_id: CurrencyId) -> Balance;
+
+        /// Performance fees the trader can claim
+        fn claimable_performance_fees(trader_id: AccountId) -> Balance;
+
+        /// Pending redemption requests of the pool in order: request id, investor and locked shares
+        fn redemption_queue(asset_id: CurrencyId) -> Vec<(u32, AccountId, Balance)>;
+
+        /// Deposited capital of every pool position of the investor
+        fn investor_positions(investor_id: AccountId) -> Vec<(CurrencyId, Balance)>;
+    }
+}
@@ -31,30 +31,40 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
 pub mod util;
+pub mod weights;
+
+#[cfg(test)]
+mod mock;
+#[cfg(test)]
+mod tests;
 
 use {
-    frame_support::{
-        pallet_prelude::*, sp_runtime::traits::StaticLookup, traits::fungible, Blake2_128Concat,
-    },
-    orml_asset_registry, orml_tokens, orml_xtokens,
+    frame_support::{pallet_prelude::*, traits::fungible, Blake2_128Concat},
+    orml_asset_registry, orml_tokens,
+    orml_traits::{MultiCurrency, MultiReservableCurrency},
+    orml_xtokens,
     sp_arithmetic::Permill,
     sp_core::H256,
     sp_std::{vec, vec::Vec},
     sp_trie::{read_trie_value, verify_trie_proof, LayoutV1, MemoryDB, StorageProof, TrieDB},
+    staging_xcm::latest::{MaybeErrorCode, MultiLocation, QueryId, Response},
 };
 
 use util::*;
+pub use weights::WeightInfo;
 
 pub use pallet::*;
 
 #[frame_support::pallet]
 pub mod pallet {
     use {
-        frame_support::sp_runtime::{traits::BlakeTwo256, MultiAddress},
+        frame_support::sp_runtime::{
+            traits::{BlakeTwo256, CheckedAdd, Hash, Zero},
+            ArithmeticError, MultiAddress,
+        },
         frame_system::{
             ensure_none, ensure_signed,
             pallet_prelude::{BlockNumberFor, OriginFor},
-            RawOrigin,
         },
         sp_io::hashing::blake2_128,
     };
@@ -70,7 +80,9 @@ pub mod pallet {
 
     #[pallet::config]
     pub trait Config:
-        frame_system::Config + orml_asset_registry::module::Config + orml_tokens::Config
+        frame_system::Config
+        + orml_asset_registry::module::Config<AssetId = <Self as orml_tokens::Config>::CurrencyId>
+        + orml_tokens::Config
     {
         type RuntimeEvent: From<Event<Self>> + IsType<<Self as frame_system::Config>::RuntimeEvent>;
 
@@ -96,45 +108,343 @@ pub mod pallet {
         type TraderPoolOwnership: Get<u8>;
         /// Constant: Withdraw period that should pass for investor to withdraw capital + returns
         type WithdrawPeriod: Get<BlockNumberFor<Self>>;
+        /// Origin allowed to create, configure, pause and close capital pools
+        type PoolAdminOrigin: EnsureOrigin<Self::RuntimeOrigin>;
+        /// Origin allowed to manage the relayer set and the attestation threshold
+        type RelayerAdminOrigin: EnsureOrigin<Self::RuntimeOrigin>;
+        /// Constant: Maximum number of registered relayers
+        #[pallet::constant]
+        type MaxRelayers: Get<u32>;
+        /// Origin allowed to add, rotate and revoke the attestor keys of the Phala workers generating trading accounts
+        type AttestorAdminOrigin: EnsureOrigin<Self::RuntimeOrigin>;
+        /// Origin registering trading accounts without relayer attestations, e.g. the Phala contract
+        /// dispatching `Transact` over XCM. It may also add up to `MaxRegistrarAttestors` attestors,
+        /// and rotate or revoke the attestors it added
+        type TraderRegistrarOrigin: EnsureOrigin<Self::RuntimeOrigin>;
+        /// Constant: Maximum number of trusted attestors added by `TraderRegistrarOrigin`
+        #[pallet::constant]
+        type MaxRegistrarAttestors: Get<u32>;
+        /// Origin allowed to approve, suspend and reinstate traders
+        type TraderAdminOrigin: EnsureOrigin<Self::RuntimeOrigin>;
+        /// If true traders are approved as soon as relayers confirm their trading accounts
+        type AutoApproveTraders: Get<bool>;
+        /// Origin allowed to manage the target networks and dex venues registry
+        type RegistryAdminOrigin: EnsureOrigin<Self::RuntimeOrigin>;
+        /// Decoder of the swaps executed by verified trade transactions
+        type TradeDecoder: TradeDecoder<Self>;
+        /// Constant: Risk policy violations after which the trader is suspended
+        #[pallet::constant]
+        type MaxRiskViolations: Get<u8>;
+        /// Constant: Share of the trader bond slashed to the pool per risk policy violation
+        #[pallet::constant]
+        type RiskViolationSlash: Get<Permill>;
+        /// Constant: Blocks after which an unverified trader balance is stale and the allocation is recalled
+        #[pallet::constant]
+        type MaxReportAge: Get<BlockNumberFor<Self>>;
+        /// Constant: Maximum traders checked for stale balance reports per block
+        #[pallet::constant]
+        type MaxDrawdownChecks: Get<u32>;
+        /// Constant: Blocks after which the pool queries itself the unverified balance of a trader on an `XcmQuery` network
+        #[pallet::constant]
+        type BalanceQueryInterval: Get<BlockNumberFor<Self>>;
+        /// Constant: Maximum traders checked for due balance queries per block
+        #[pallet::constant]
+        type MaxBalanceQueryChecks: Get<u32>;
+        /// Account receiving the protocol fees not kept by the insurance fund
+        type TreasuryAccount: Get<AccountIdFor<Self>>;
+        /// Constant: Share of the protocol fee sent to the insurance fund
+        #[pallet::constant]
+        type InsuranceFeeCut: Get<Permill>;
+        /// Constant: Share of the realized profit net of fees sent to the insurance fund
+        #[pallet::constant]
+        type InsuranceProfitCut: Get<Permill>;
+        /// Origin allowed to approve payouts from the insurance funds
+        type InsuranceClaimOrigin: EnsureOrigin<Self::RuntimeOrigin>;
+        /// Constant: Number of blocks per settlement epoch
+        #[pallet::constant]
+        type EpochLength: Get<BlockNumberFor<Self>>;
+        /// Constant: Maximum traders or pools processed per block during the epoch settlement
+        #[pallet::constant]
+        type MaxEpochSteps: Get<u32>;
+        /// Constant: Maximum pending redemption requests per pool
+        #[pallet::constant]
+        type MaxRedemptionRequests: Get<u32>;
+        /// Allowlist or blocklist applied to investor deposits
+        type DepositFilter: DepositFilter<Self>;
+        /// Constant: Maximum number of pools an investor can hold a position in
+        #[pallet::constant]
+        type MaxInvestorPools: Get<u32>;
+        /// Share tokens of the capital pools
+        type PoolShares: PoolShareAsset<Self>;
+        /// Executes the swaps requested by traders on remote venues from the pool account
+        type RemoteSwapExecutor: RemoteSwapExecutor<Self>;
+        /// Checks the balance of the accounts the pools control for traders on networks verified by XCM queries
+        type RemoteBalanceQuerier: RemoteBalanceQuerier<Self>;
+        /// Origin of the XCM query responses reporting the remote swap results and balances
+        type XcmResponseOrigin: EnsureOrigin<Self::RuntimeOrigin, Success = MultiLocation>;
+        /// Constant: Blocks after which a remote swap without result expires
+        #[pallet::constant]
+        type RemoteSwapTimeout: Get<BlockNumberFor<Self>>;
+        /// Weight information for the extrinsics of this pallet
+        type WeightInfo: WeightInfo;
     }
 
+    /// Version 1 drops the investor profiles, replaced by `InvestorPositions`
+    const STORAGE_VERSION: StorageVersion = StorageVersion::new(1);
+
     #[pallet::pallet]
+    #[pallet::storage_version(STORAGE_VERSION)]
     pub struct Pallet<T>(_);
 
+    /// Pool asset of each pool share token
     #[pallet::storage]
-    #[pallet::unbounded]
-    pub type InvestorProfiles<T: Config> =
-        CountedStorageMap<_, Blake2_128Concat, AccountIdFor<T>, InvestorProfile<T>>;
+    pub type PoolShareAssets<T: Config> = StorageMap<_, Twox64Concat, T::CurrencyId, T::CurrencyId>;
 
+    /// ERC-20 allowances of the `orml_tokens` assets exposed as XC20s: asset, owner and spender to allowed amount
     #[pallet::storage]
-    pub type TraderProfiles<T: Config> = StorageDoubleMap<
+    pub type Erc20Approvals<T: Config> = StorageNMap<
+        _,
+        (
+            NMapKey<Twox64Concat, T::CurrencyId>,
+            NMapKey<Blake2_128Concat, AccountIdFor<T>>,
+            NMapKey<Blake2_128Concat, AccountIdFor<T>>,
+        ),
+        AssetBalance<T>,
+        ValueQuery,
+    >;
+
+    /// Investor position and deposit history in each pool
+    #[pallet::storage]
+    pub type InvestorPositions<T: Config> = StorageDoubleMap<
         _,
         Blake2_128Concat,
         AccountIdFor<T>,
-        Blake2_128Concat,
-        AccountIdFor<T>,
-        TraderProfile<T>,
+        Twox64Concat,
+        T::CurrencyId,
+        InvestorPosition<T>,
     >;
 
+    /// A mapping of Trader Soverign Account to its lifecycle state, bond and allocation
+    #[pallet::storage]
+    pub type TraderProfiles<T: Config> =
+        StorageMap<_, Blake2_128Concat, AccountIdFor<T>, TraderProfile<T>>;
+
     /// A mapping of Trader Soverign Account to the Onchain Trading Account
     #[pallet::storage]
     pub type OnChainTradingAccounts<T: Config> =
-        CountedStorageMap<_, Blake2_128Concat, AccountIdFor<T>, TradingAccounts<AccountIdFor<T>>>;
+        CountedStorageMap<_, Blake2_128Concat, AccountIdFor<T>, TradingAccounts>;
 
     /// A mapping of asset id to capital pool
     #[pallet::storage]
     pub type CapitalPool<T: Config> =
         StorageMap<_, Twox64Concat, T::CurrencyId, InvestorCapitalPool<T>, ValueQuery>;
 
-    /// Relayer account that is responsible for submitting txn for registering trader account and onchain trading account
+    /// Relayer accounts responsible for submitting txn for registering trader account and onchain trading account
     /// relating to the trader generated onchain from the contract
     #[pallet::storage]
-    pub type Relayer<T: Config> = StorageValue<_, AccountIdFor<T>, OptionQuery>;
+    pub type Relayers<T: Config> =
+        StorageValue<_, BoundedVec<AccountIdFor<T>, T::MaxRelayers>, ValueQuery>;
+
+    /// Number of relayer attestations required before a trader registration is accepted
+    #[pallet::storage]
+    pub type RelayerThreshold<T: Config> = StorageValue<_, u32, ValueQuery>;
+
+    /// Relayer attestations collected for a trader, keyed by the hash of the attested onchain trading accounts
+    #[pallet::storage]
+    pub type TraderRegistrationAttestations<T: Config> = StorageDoubleMap<
+        _,
+        Blake2_128Concat,
+        AccountIdFor<T>,
+        Identity,
+        T::Hash,
+        BoundedVec<AccountIdFor<T>, T::MaxRelayers>,
+        ValueQuery,
+    >;
+
+    /// Relayers that reported a relayer for misbehavior
+    #[pallet::storage]
+    pub type RelayerReports<T: Config> = StorageMap<
+        _,
+        Blake2_128Concat,
+        AccountIdFor<T>,
+        BoundedVec<AccountIdFor<T>, T::MaxRelayers>,
+        ValueQuery,
+    >;
+
+    /// Registry of the Phala worker / contract keys trusted to attest generated trading accounts
+    #[pallet::storage]
+    pub type Attestors<T: Config> =
+        StorageMap<_, Blake2_128Concat, AttestorKey, AttestorInfo<BlockNumberFor<T>>>;
+
+    /// Trusted attestor keys added by `TraderRegistrarOrigin`, the only ones it may rotate or revoke
+    #[pallet::storage]
+    pub type RegistrarAttestors<T: Config> =
+        StorageValue<_, BoundedVec<AttestorKey, T::MaxRegistrarAttestors>, ValueQuery>;
+
+    /// Nonce of the last attestation the trading accounts of a trader were registered with
+    #[pallet::storage]
+    pub type TraderAttestationNonces<T: Config> =
+        StorageMap<_, Blake2_128Concat, AccountIdFor<T>, u64, ValueQuery>;
+
+    /// Location of the sibling parachain contract allowed to register traders and manage its own attestors over XCM
+    #[pallet::storage]
+    pub type XcmTraderRegistrar<T: Config> = StorageValue<_, MultiLocation>;
+
+    /// Registry of the target networks capital can be deployed on
+    #[pallet::storage]
+    pub type TargetNetworks<T: Config> = StorageMap<_, Twox64Concat, NetworkId, NetworkInfo>;
+
+    /// Registry of the dex venues traders are allowed to trade on
+    #[pallet::storage]
+    pub type DexVenues<T: Config> = StorageMap<_, Twox64Concat, VenueId, DexVenue>;
+
+    /// Balances map of the pool assets on the target networks, the trade proofs of a trader prove
+    /// the entry of its trading account in the map of its pool asset
+    #[pallet::storage]
+    pub type NetworkAssets<T: Config> = StorageDoubleMap<
+        _,
+        Twox64Concat,
+        NetworkId,
+        Blake2_128Concat,
+        T::CurrencyId,
+        RemoteBalanceStorage,
+    >;
+
+    /// Raw storage key of the last trader checked for stale balance reports
+    #[pallet::storage]
+    pub type DrawdownCursor<T: Config> = StorageValue<_, StorageCursor>;
+
+    /// Raw storage key of the last trader checked for due balance queries
+    #[pallet::storage]
+    pub type BalanceQueryCursor<T: Config> = StorageValue<_, StorageCursor>;
+
+    /// Block the last balance query of each trader was sent at
+    #[pallet::storage]
+    pub type LastBalanceQuery<T: Config> =
+        StorageMap<_, Blake2_128Concat, AccountIdFor<T>, BlockNumberFor<T>>;
+
+    /// Index of the epoch in progress
+    #[pallet::storage]
+    pub type CurrentEpoch<T: Config> = StorageValue<_, EpochIndex, ValueQuery>;
+
+    /// Block the epoch in progress started at
+    #[pallet::storage]
+    pub type EpochStart<T: Config> = StorageValue<_, BlockNumberFor<T>, ValueQuery>;
+
+    /// Epoch settlement in progress, spread over several blocks
+    #[pallet::storage]
+    pub type EpochSettlement<T: Config> = StorageValue<_, EpochPhase>;
+
+    /// Pool snapshots taken at the end of each epoch
+    #[pallet::storage]
+    pub type PoolEpochs<T: Config> = StorageDoubleMap<
+        _,
+        Twox64Concat,
+        T::CurrencyId,
+        Twox64Concat,
+        EpochIndex,
+        EpochRecord<AssetBalance<T>, BlockNumberFor<T>>,
+    >;
+
+    /// Redemption requests of each pool, served first in first out
+    #[pallet::storage]
+    pub type RedemptionQueues<T: Config> = StorageMap<
+        _,
+        Twox64Concat,
+        T::CurrencyId,
+        BoundedVec<
+            RedemptionRequest<AccountIdFor<T>, AssetBalance<T>, BlockNumberFor<T>>,
+            T::MaxRedemptionRequests,
+        >,
+        ValueQuery,
+    >;
+
+    #[pallet::storage]
+    pub type NextRedemptionId<T: Config> = StorageValue<_, RedemptionId, ValueQuery>;
+
+    /// Profit credited to each investor deposit
+    #[pallet::storage]
+    pub type InvestorRewards<T: Config> = StorageDoubleMap<
+        _,
+        Blake2_128Concat,
+        AccountIdFor<T>,
+        Twox64Concat,
+        T::CurrencyId,
+        InvestorReward<AssetBalance<T>>,
+        ValueQuery,
+    >;
+
+    /// Remote swaps waiting for the XCM query response with their result
+    #[pallet::storage]
+    pub type RemoteSwaps<T: Config> = StorageMap<_, Twox64Concat, QueryId, PendingRemoteSwap<T>>;
+
+    /// Balance queries waiting for their XCM query response
+    #[pallet::storage]
+    pub type BalanceQueries<T: Config> =
+        StorageMap<_, Twox64Concat, QueryId, PendingBalanceQuery<T>>;
+
+    /// Relayer attestations of the roots of a target network block, keyed by the hash of the attested roots
+    #[pallet::storage]
+    pub type NetworkRootAttestations<T: Config> = StorageDoubleMap<
+        _,
+        Blake2_128Concat,
+        (NetworkId, BlockNumberFor<T>),
+        Identity,
+        T::Hash,
+        BoundedVec<AccountIdFor<T>, T::MaxRelayers>,
+        ValueQuery,
+    >;
+
+    /// Relayer attestations of the capital returned by a trader, keyed by the hash of the allocated and returned amounts
+    #[pallet::storage]
+    pub type SettlementAttestations<T: Config> = StorageDoubleMap<
+        _,
+        Blake2_128Concat,
+        AccountIdFor<T>,
+        Identity,
+        T::Hash,
+        BoundedVec<AccountIdFor<T>, T::MaxRelayers>,
+        ValueQuery,
+    >;
+
+    /// Relayer claims of the balance of a trader on an `XcmQuery` network, keyed by the hash of the allocated and claimed amounts
+    #[pallet::storage]
+    pub type BalanceClaims<T: Config> = StorageDoubleMap<
+        _,
+        Blake2_128Concat,
+        AccountIdFor<T>,
+        Identity,
+        T::Hash,
+        BoundedVec<AccountIdFor<T>, T::MaxRelayers>,
+        ValueQuery,
+    >;
+
+    /// Target network block of the last trade proof verified for each trader, proofs of older blocks are rejected
+    #[pallet::storage]
+    pub type LastVerifiedBlock<T: Config> =
+        StorageMap<_, Blake2_128Concat, AccountIdFor<T>, BlockNumberFor<T>>;
+
+    /// Trade proofs verified in the `LastVerifiedBlock` of each trader, keyed by `trade_proof_hash`
+    #[pallet::storage]
+    pub type ProcessedTradeProofs<T: Config> =
+        StorageDoubleMap<_, Blake2_128Concat, AccountIdFor<T>, Identity, T::Hash, ()>;
+
+    /// Roots of target network blocks attested by `RelayerThreshold` relayers, trade proofs are checked against them
+    #[pallet::storage]
+    pub type FinalizedNetworkRoots<T: Config> = StorageDoubleMap<
+        _,
+        Twox64Concat,
+        NetworkId,
+        Twox64Concat,
+        BlockNumberFor<T>,
+        FinalizedRoots,
+    >;
 
-    // Genesis Config for `Relayer` storage
+    // Genesis Config for `Relayers` storage
     #[pallet::genesis_config]
     pub struct GenesisConfig<T: Config> {
-        pub relayer: Option<AccountIdFor<T>>,
+        pub relayers: Vec<AccountIdFor<T>>,
+        pub relayer_threshold: u32,
         pub supported_assets: Vec<T::CurrencyId>,
         pub initial_capital: AssetBalance<T>,
         pub fee: u8,
@@ -143,7 +453,8 @@ pub mod pallet {
     impl<T: Config> Default for GenesisConfig<T> {
         fn default() -> Self {
             Self {
-                relayer: None,
+                relayers: vec![],
+                relayer_threshold: 1,
                 supported_assets: vec![],
                 initial_capital: AssetBalance::<T>::default(),
                 fee: 0,
@@ -154,22 +465,31 @@ pub mod pallet {
     #[pallet::genesis_build]
     impl<T: Config> BuildGenesisConfig for GenesisConfig<T> {
         fn build(&self) {
-            Relayer::<T>::put(
-                self.relayer
-                    .clone()
-                    .expect(" Setup spectre relayer account"),
+            let relayers: BoundedVec<AccountIdFor<T>, T::MaxRelayers> = self
+                .relayers
+                .clone()
+                .try_into()
+                .expect(" Too many spectre relayer accounts");
+            assert!(
+                relayers.is_empty() || self.relayer_threshold as usize <= relayers.len(),
+                " Spectre relayer threshold above the number of relayers"
             );
+            Relayers::<T>::put(relayers);
+            RelayerThreshold::<T>::put(self.relayer_threshold.max(1));
             self.supported_assets.iter().for_each(|asset| {
-                let account_id = Pallet::<T>::generate_pool_account(Some(asset.clone()));
+                let params = PoolParams {
+                    fee: Permill::from_percent(self.fee.into()),
+                    ..Default::default()
+                };
                 let investor_pool = InvestorCapitalPool {
-                    asset_name: Some(asset.clone()),
                     total_capital: self.initial_capital,
                     remaining_capital: self.initial_capital,
                     total_allocated_capital: self.initial_capital,
                     unrealized_balance: self.initial_capital,
-                    fee: self.fee,
-                    account_id,
+                    ..InvestorCapitalPool::<T>::new(asset.clone(), params)
                 };
+                Pallet::<T>::register_share_asset(asset)
+                    .expect(" Failed to register the spectre pool share token");
 
                 CapitalPool::<T>::insert(asset, investor_pool);
             });
@@ -206,7 +526,7 @@ pub mod pallet {
         InvalidBalanceStateProof,
 
         AccountUnavailable,
-        /// This error should not occur as the relayer is set in the pallet genesis storage
+        /// Returned when there is no registered relayer
         RelayerUnavailable,
         /// If the relayer submitting the transaction from the contract is not recognized in the chain
         RelayerNotRegistered,
@@ -218,25 +538,492 @@ pub mod pallet {
         AssetPoolNotSupported,
         /// Returned when failed to transfer funds from investor to pool account
         FailedToTransferCapitalToPool,
+        /// Returned when creating a pool for an asset that already has one
+        PoolAlreadyExists,
+        /// Returned when the pool is paused and does not accept deposits or allocations
+        PoolPaused,
+        /// Returned when resuming a pool that is not paused
+        PoolNotPaused,
+        /// Returned when the pool is closing or closed
+        PoolClosed,
+        /// Returned when a deposit would take the pool over its capital cap
+        PoolCapExceeded,
+        /// Returned when an allocation would take the trader over the pool allocation cap
+        TraderAllocationExceeded,
+        /// Returned when the pool does not hold enough unallocated capital
+        InsufficientPoolLiquidity,
+        /// Returned when the investor has no capital deposited in the pool
+        InvestorNotRegistered,
+        /// Returned when withdrawing more capital than the investor deposited
+        InsufficientInvestorCapital,
+        /// Returned when withdrawing from an active pool before the withdraw period elapsed
+        WithdrawPeriodNotElapsed,
+        /// Returned when adding an account that is already a relayer
+        RelayerAlreadyRegistered,
+        /// Returned when the relayer set is full
+        TooManyRelayers,
+        /// Returned when the threshold is zero or above the number of relayers
+        InvalidRelayerThreshold,
+        /// Returned when the relayer already attested the trader registration
+        AlreadyAttested,
+        /// Returned when the relayer attested different trading accounts for the same trader
+        ConflictingAttestation,
+        /// Returned when a relayer reports itself
+        CannotReportSelf,
+        /// Returned when the relayer already reported the offender
+        AlreadyReported,
+        /// Returned when the trader already applied
+        TraderAlreadyApplied,
+        /// Returned when the trader state does not allow the operation
+        InvalidTraderStatus,
+        /// Returned when the trader is not approved for trading
+        TraderNotActive,
+        /// Returned when the trader requests capital from a pool it did not apply to
+        TraderPoolMismatch,
+        /// Returned when the pool account does not hold the capital reported as returned
+        CapitalNotReturned,
+        /// Returned when a trading account is not in the address format of its network
+        InvalidTradingAccount,
+        /// Returned when allocating on a network other than the one holding the open allocation
+        AllocationNetworkMismatch,
+        /// Returned when the target network is not in the registry
+        NetworkNotSupported,
+        /// Returned when the target network is disabled
+        NetworkDisabled,
+        /// Returned when removing a network still referenced by venues or open allocations
+        NetworkInUse,
+        /// Returned when the dex venue is not in the registry
+        VenueNotFound,
+        /// Returned when the network has no verifier for its proof kind and hasher
+        UnsupportedProofKind,
+        /// Returned when the proof targets another network than the submitted one
+        ProofNetworkMismatch,
+        /// Returned when the insurance fund cannot cover the claim
+        InsufficientInsuranceFund,
+        /// Returned when there is no profit or fee to claim
+        NothingToClaim,
+        /// Returned when redemption requests are waiting for pool liquidity
+        RedemptionsPending,
+        /// Returned when the redemption queue of the pool is full
+        RedemptionQueueFull,
+        /// Returned when the redemption request is not in the queue
+        RedemptionNotFound,
+        /// Returned when a deposit would take the investor over the pool per investor cap
+        InvestorCapExceeded,
+        /// Returned when the investor is not allowed to deposit in the pool
+        InvestorNotAllowlisted,
+        /// Returned when the investor is blocked from depositing
+        InvestorBlocked,
+        /// Returned when the investor already holds positions in `MaxInvestorPools` pools
+        TooManyInvestorPools,
+        /// Returned when the pool asset has no asset registry metadata to derive the share token from
+        AssetMetadataNotFound,
+        /// Returned when the venue or its network does not support swaps executed by the pool
+        RemoteSwapsNotSupported,
+        /// Returned when the remote swap could not be sent to the venue network
+        FailedToSendRemoteSwap,
+        /// Returned when the query response does not belong to a pending remote swap
+        RemoteSwapNotFound,
+        /// Returned when the trader risk policy does not allow the venue
+        VenueNotAllowed,
+        /// Returned when the trader risk policy or the venue does not allow the asset
+        AssetNotAllowed,
+        /// Returned when the swap is larger than the trader risk policy position limit
+        PositionTooLarge,
+        /// Returned when the trader reached the trades allowed per period by its risk policy
+        TradeLimitExceeded,
+        /// Returned when the balances of the network are not verified by XCM queries
+        BalanceQueriesNotSupported,
+        /// Returned when the balance query could not be sent to the network
+        FailedToSendBalanceQuery,
+        /// Returned when the query response does not belong to a pending balance query
+        BalanceQueryNotFound,
+        /// Returned when the attestor key is not registered or no longer trusted
+        AttestorNotTrusted,
+        /// Returned when adding an attestor key that is or was already registered
+        AttestorAlreadyRegistered,
+        /// Returned when the trader registrar adds more than `MaxRegistrarAttestors` trusted attestors
+        TooManyRegistrarAttestors,
+        /// Returned when the trader registrar rotates or revokes an attestor it did not add
+        NotRegistrarAttestor,
+        /// Returned when the attestation signature does not match the trader and trading accounts
+        InvalidAttestation,
+        /// Returned when the attestation nonce is not above the nonce of the last registration of the trader
+        StaleAttestation,
+        /// Returned when depositing in a pool whose shares lost all their value
+        PoolValueDepleted,
+        /// Returned when a trade proof is not built against the finalized roots of the target network block
+        UntrustedStateRoot,
+        /// Returned when the roots of the target network block are already finalized
+        RootsAlreadyFinalized,
+        /// Returned when the proven transaction is not signed by the trader onchain trading account or can not be decoded
+        InvalidTradeTransaction,
+        /// Returned when the state key of the pool asset balances on the target network is not registered
+        NetworkAssetNotRegistered,
+        /// Returned when the trade proof is of a block before the last verified block of the trader
+        StaleTradeProof,
+        /// Returned when the trade proof was verified already
+        TradeProofAlreadyProcessed,
+        /// Returned when a query response does not come from the queried network
+        UnexpectedResponder,
+        /// Returned when the recall of the capital could not be sent to the trading account
+        FailedToRecallFunds,
+        /// Returned when expiring a remote swap before `RemoteSwapTimeout`
+        RemoteSwapNotExpired,
     }
 
     #[pallet::event]
     #[pallet::generate_deposit(pub(super) fn deposit_event)]
     pub enum Event<T: Config> {
         InvestorRegistered,
+        /// Investor deposited capital in a pool and received `shares` pool shares for it
+        CapitalDeposited {
+            investor_id: AccountIdFor<T>,
+            asset_id: T::CurrencyId,
+            amount: AssetBalance<T>,
+            shares: AssetBalance<T>,
+        },
+        /// Capital arriving over XCM was deposited in the pool for the investor
+        XcmDepositAccepted {
+            investor_id: AccountIdFor<T>,
+            asset_id: T::CurrencyId,
+            amount: AssetBalance<T>,
+        },
+        /// The pool refused capital arriving over XCM, the assets stay with the investor
+        XcmDepositFailed {
+            investor_id: AccountIdFor<T>,
+            asset_id: T::CurrencyId,
+            amount: AssetBalance<T>,
+            error: DispatchError,
+        },
         TraderRegistered {
             id: AccountIdFor<T>,
         },
         TradeVerifiedSuccesfully {
             trader_id: AccountIdFor<T>,
-            onchain_trading_account: AccountIdFor<T>,
-            network: Networks,
+            onchain_trading_account: RemoteAccount,
+            network: NetworkId,
         },
         FundsAllocated {
             trader_id: AccountIdFor<T>,
-            onchain_trading_account: AccountIdFor<T>,
-            network: Networks,
+            onchain_trading_account: RemoteAccount,
+            network: NetworkId,
+        },
+        /// A new capital pool was created
+        PoolCreated {
+            asset_id: T::CurrencyId,
+            account_id: AccountIdFor<T>,
+            share_asset_id: T::CurrencyId,
+        },
+        /// Pool fee and limits were updated
+        PoolParamsUpdated {
+            asset_id: T::CurrencyId,
+            params: PoolParams<AssetBalance<T>>,
+        },
+        PoolPaused {
+            asset_id: T::CurrencyId,
+        },
+        PoolResumed {
+            asset_id: T::CurrencyId,
+        },
+        /// Pool started winding down, open allocations are being recalled
+        PoolClosing {
+            asset_id: T::CurrencyId,
+        },
+        /// Every allocation of the pool has been recalled
+        PoolClosed {
+            asset_id: T::CurrencyId,
+        },
+        /// Return of the capital allocated to the trader was requested
+        CapitalRecalled {
+            asset_id: T::CurrencyId,
+            trader_id: AccountIdFor<T>,
+        },
+        CapitalWithdrawn {
+            investor_id: AccountIdFor<T>,
+            asset_id: T::CurrencyId,
+            amount: AssetBalance<T>,
+        },
+        RelayerAdded {
+            relayer: AccountIdFor<T>,
+        },
+        RelayerRemoved {
+            relayer: AccountIdFor<T>,
+        },
+        RelayerRotated {
+            old: AccountIdFor<T>,
+            new: AccountIdFor<T>,
+        },
+        RelayerThresholdSet {
+            threshold: u32,
+        },
+        AttestorAdded {
+            attestor: AttestorKey,
+        },
+        AttestorRotated {
+            old: AttestorKey,
+            new: AttestorKey,
+        },
+        AttestorRevoked {
+            attestor: AttestorKey,
+        },
+        XcmTraderRegistrarSet {
+            location: Option<MultiLocation>,
+        },
+        /// A relayer attested the trading accounts of a trader, `attestations` is the current count
+        TraderRegistrationAttested {
+            trader_id: AccountIdFor<T>,
+            relayer: AccountIdFor<T>,
+            attestations: u32,
+        },
+        /// A relayer reported another relayer for misbehavior, `reports` is the current count
+        RelayerReported {
+            reporter: AccountIdFor<T>,
+            offender: AccountIdFor<T>,
+            reports: u32,
+        },
+        /// Trader applied to trade the pool capital and placed its bond on hold
+        TraderApplied {
+            trader_id: AccountIdFor<T>,
+            asset_id: T::CurrencyId,
+            bond: AssetBalance<T>,
+        },
+        /// Trader is approved and can request capital allocation
+        TraderApproved {
+            trader_id: AccountIdFor<T>,
+        },
+        TraderSuspended {
+            trader_id: AccountIdFor<T>,
+            reason: SuspensionReason,
+        },
+        TraderReinstated {
+            trader_id: AccountIdFor<T>,
+        },
+        /// Trader requested to exit, its open allocation is being recalled
+        TraderExiting {
+            trader_id: AccountIdFor<T>,
+        },
+        /// Trader left and its bond was released
+        TraderDeregistered {
+            trader_id: AccountIdFor<T>,
+            bond_released: AssetBalance<T>,
+        },
+        /// A relayer claimed the balance of a trader on an `XcmQuery` network, `claims` is the current count
+        TraderBalanceClaimed {
+            trader_id: AccountIdFor<T>,
+            balance: AssetBalance<T>,
+            relayer: AccountIdFor<T>,
+            claims: u32,
+        },
+        /// A relayer attested the capital returned by the trader, `attestations` is the current count
+        SettlementAttested {
+            trader_id: AccountIdFor<T>,
+            returned: AssetBalance<T>,
+            relayer: AccountIdFor<T>,
+            attestations: u32,
+        },
+        /// Capital allocated to the trader returned to the pool account
+        AllocationSettled {
+            trader_id: AccountIdFor<T>,
+            asset_id: T::CurrencyId,
+            allocated: AssetBalance<T>,
+            returned: AssetBalance<T>,
+        },
+        /// A target network was registered or updated
+        TargetNetworkSet {
+            network_id: NetworkId,
+            info: NetworkInfo,
+        },
+        TargetNetworkRemoved {
+            network_id: NetworkId,
+        },
+        /// A dex venue was registered or updated
+        DexVenueSet {
+            venue_id: VenueId,
+            venue: DexVenue,
+        },
+        DexVenueRemoved {
+            venue_id: VenueId,
+        },
+        /// The balances map of a pool asset on a target network was registered, updated or removed
+        NetworkAssetSet {
+            network_id: NetworkId,
+            asset_id: T::CurrencyId,
+            balance_storage: Option<RemoteBalanceStorage>,
+        },
+        /// Part of a realized loss was covered by slashing the trader bond
+        LossCoveredByBond {
+            trader_id: AccountIdFor<T>,
+            asset_id: T::CurrencyId,
+            amount: AssetBalance<T>,
+        },
+        /// Part of a realized loss was covered by the insurance fund
+        LossCoveredByInsurance {
+            asset_id: T::CurrencyId,
+            amount: AssetBalance<T>,
+        },
+        /// Remaining realized loss deducted from the pool unrealized balance, shared by investors pro rata
+        LossAbsorbedByInvestors {
+            asset_id: T::CurrencyId,
+            amount: AssetBalance<T>,
+        },
+        /// Protocol fee taken from a realized profit was sent to the treasury
+        ProtocolFeeCollected {
+            asset_id: T::CurrencyId,
+            amount: AssetBalance<T>,
+        },
+        /// Insurance fund received its cut of the protocol fee and realized profit
+        InsuranceFunded {
+            asset_id: T::CurrencyId,
+            amount: AssetBalance<T>,
+        },
+        /// Governance approved claim paid out of the insurance fund
+        InsuranceClaimPaid {
+            asset_id: T::CurrencyId,
+            beneficiary: AccountIdFor<T>,
+            amount: AssetBalance<T>,
+        },
+        /// Trader performance fee charged on a new high at the end of the epoch
+        PerformanceFeeCrystallized {
+            trader_id: AccountIdFor<T>,
+            asset_id: T::CurrencyId,
+            fee: AssetBalance<T>,
+            high_water_mark: AssetBalance<T>,
+        },
+        /// Pool snapshot taken and realized profit distributed for the epoch
+        PoolEpochClosed {
+            asset_id: T::CurrencyId,
+            epoch: EpochIndex,
+            nav: AssetBalance<T>,
+            distributed_profit: AssetBalance<T>,
+        },
+        /// Every pool was settled for the epoch, the next epoch started
+        EpochClosed {
+            epoch: EpochIndex,
+        },
+        /// Investor claimed its share of the distributed profit
+        ProfitClaimed {
+            investor_id: AccountIdFor<T>,
+            asset_id: T::CurrencyId,
+            amount: AssetBalance<T>,
+        },
+        /// Trader claimed its performance fees
+        PerformanceFeesClaimed {
+            trader_id: AccountIdFor<T>,
+            asset_id: T::CurrencyId,
+            amount: AssetBalance<T>,
+        },
+        /// Investor queued a redemption, its shares are locked until filled or cancelled
+        RedemptionRequested {
+            request_id: RedemptionId,
+            investor_id: AccountIdFor<T>,
+            asset_id: T::CurrencyId,
+            shares: AssetBalance<T>,
+        },
+        /// Redemption request filled, partially when `remaining_shares` is not zero
+        RedemptionFilled {
+            request_id: RedemptionId,
+            investor_id: AccountIdFor<T>,
+            asset_id: T::CurrencyId,
+            shares: AssetBalance<T>,
+            payout: AssetBalance<T>,
+            remaining_shares: AssetBalance<T>,
+        },
+        RedemptionCancelled {
+            request_id: RedemptionId,
+            investor_id: AccountIdFor<T>,
+            asset_id: T::CurrencyId,
+            shares: AssetBalance<T>,
+        },
+        /// Redemption request dropped from the queue after failing to be filled, its `shares` are unlocked
+        RedemptionFailed {
+            request_id: RedemptionId,
+            investor_id: AccountIdFor<T>,
+            asset_id: T::CurrencyId,
+            shares: AssetBalance<T>,
+            error: DispatchError,
+        },
+        /// Stop loss triggered, the trader capital is being recalled
+        TraderLiquidating {
+            trader_id: AccountIdFor<T>,
+            reason: LiquidationReason,
+        },
+        /// Risk policy of the trader was updated
+        RiskPolicySet {
+            trader_id: AccountIdFor<T>,
+        },
+        /// A verified trade broke the trader risk policy
+        RiskPolicyViolated {
+            trader_id: AccountIdFor<T>,
+            violation: RiskViolation,
+            slashed: AssetBalance<T>,
+        },
+        /// The pool sent the swap requested by the trader to the remote venue
+        RemoteSwapRequested {
+            trader_id: AccountIdFor<T>,
+            query_id: QueryId,
+            swap: RemoteSwap,
+        },
+        /// The remote venue executed the swap
+        RemoteSwapExecuted {
+            trader_id: AccountIdFor<T>,
+            query_id: QueryId,
+        },
+        /// The remote swap failed or its result could not be reported
+        RemoteSwapFailed {
+            trader_id: AccountIdFor<T>,
+            query_id: QueryId,
+            response: Response,
+        },
+        /// The result of a remote swap did not come back within `RemoteSwapTimeout` blocks
+        RemoteSwapExpired {
+            trader_id: AccountIdFor<T>,
+            query_id: QueryId,
+        },
+        /// The balance of the account the pool controls for the trader was queried, by a relayer or by the pool itself
+        TraderBalanceQueried {
+            trader_id: AccountIdFor<T>,
+            query_id: QueryId,
+            balance: AssetBalance<T>,
+        },
+        /// The query response confirmed the balance and the reported balance was recorded for the trader
+        TraderBalanceVerified {
+            trader_id: AccountIdFor<T>,
+            query_id: QueryId,
+            balance: AssetBalance<T>,
+        },
+        /// The query response did not confirm the balance
+        TraderBalanceNotVerified {
+            trader_id: AccountIdFor<T>,
+            query_id: QueryId,
+            reported: AssetBalance<T>,
+        },
+        /// A relayer attested the roots of a target network block, `attestations` is the current count
+        NetworkRootsAttested {
+            network: NetworkId,
+            block_number: BlockNumberFor<T>,
+            relayer: AccountIdFor<T>,
+            attestations: u32,
         },
+        /// The roots of a target network block reached the relayer threshold, trade proofs can be anchored to them
+        NetworkRootsFinalized {
+            network: NetworkId,
+            block_number: BlockNumberFor<T>,
+            roots: FinalizedRoots,
+        },
+    }
+
+    #[pallet::hooks]
+    impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+        fn on_initialize(n: BlockNumberFor<T>) -> Weight {
+            Self::liquidate_stale_traders(n)
+                .saturating_add(Self::query_trader_balances(n))
+                .saturating_add(Self::process_epoch(n))
+        }
+
+        fn on_runtime_upgrade() -> Weight {
+            Self::remove_investor_profiles()
+        }
     }
 
     // unsigned transaction for submitting trade execution proofs
@@ -267,11 +1054,20 @@ pub mod pallet {
                 _ => Err(TransactionValidityError::Invalid(InvalidTransaction::Call))?,
             };
 
+            // proofs not anchored to finalized roots can not change the trader or pool balances, keep them out of the pool
+            let network_info = TargetNetworks::<T>::get(network)
+                .ok_or(TransactionValidityError::Invalid(InvalidTransaction::Call))?;
+            Self::ensure_trusted_roots(*network, &network_info, trade_execution_proof)
+                .map_err(|_| TransactionValidityError::Invalid(InvalidTransaction::BadProof))?;
+            // replayed and stale proofs are kept out of the pool, they are rejected at dispatch too
+            Self::ensure_fresh_trade_proof(trader_id, *network, trade_execution_proof)
+                .map_err(|_| TransactionValidityError::Invalid(InvalidTransaction::Stale))?;
+
             // Modify this to be dynamic in terms of priority,
             // All polkadot related verification should have lesser priorioty than non polkadot trade verification
             // No duplicate tx
             let hash = (
-                trade_action,
+                trader_id,
                 asset_id,
                 network,
                 trade_execution_proof,
@@ -292,128 +1088,240 @@ pub mod pallet {
 
     #[pallet::call]
     impl<T: Config> Pallet<T> {
-        /// This extrinsic registers investor by depositing capital to the pool and registering the details in `InvestorProfile`
-        /// As the Investor can register in atmost 4 pools supporting assets
-        /// Calling this function by specifying the asset registers to the specific pool, if the investors did register, it will add the assets.
+        /// This extrinsic registers investor by depositing capital to the pool and registering the details in `InvestorPosition`
+        /// As the Investor can register in atmost `MaxInvestorPools` pools supporting assets
+        /// Calling this function by specifying the asset registers to the specific pool, if the investors did register, it adds to its `InvestorPosition`.
         #[pallet::call_index(0)]
-        #[pallet::weight(Weight::default())]
+        #[pallet::weight(T::WeightInfo::register_investor(T::MaxRedemptionRequests::get()))]
         pub fn register_investor(
             origin: OriginFor<T>,
             asset_id: T::CurrencyId,
             capital_amount: AssetBalance<T>,
         ) -> DispatchResult {
-            let investor = ensure_signed(origin.clone())?;
-
-            ensure!(
-                CapitalPool::<T>::contains_key(asset_id.clone()),
-                Error::<T>::AssetPoolNotSupported
-            );
-
-            let _ = CapitalPool::<T>::try_mutate(asset_id.clone(), |pool| {
-                // update the pool & investor profile with correct ownership
-                // transfer from investor to pool
-                let pool_id_source = T::Lookup::unlookup(pool.account_id.clone());
-                // update pool
-                pool.add_capital(capital_amount);
-                // update investor profile
-                let mut investor_profile = InvestorProfile::<T>::default();
-                investor_profile.register_capital(investor.clone(), asset_id, capital_amount);
-                // actual depositing of asset
-                <orml_tokens::Pallet<T>>::transfer_keep_alive(
-                    RawOrigin::Signed(investor).into(),
-                    pool_id_source,
-                    asset_id,
-                    capital_amount,
-                )
-                .map_err(|_| Error::<T>::FailedToTransferCapitalToPool)?;
-                Ok::<(), Error<T>>(())
-            });
+            let investor = ensure_signed(origin)?;
 
+            Self::deposit_capital(investor, asset_id, capital_amount)?;
             Ok(())
         }
 
         /// Registers trader after generating on chain trading accounts in the contract.
-        /// This extrinsic accept the trading acconts public key to registers them with trader account id
+        /// This extrinsic accept the trading acconts public key to registers them with trader account id.
+        /// The accounts must come with the attestation of a trusted attestor, the Phala worker or contract key
+        /// that generated them, signing the trader, the accounts and a nonce above the last registration of the trader.
+        /// Each call is one relayer attestation, the accounts are registered once `RelayerThreshold`
+        /// relayers attested the same trading accounts.
+        /// Called by `TraderRegistrarOrigin`, e.g. the Phala contract over XCM `Transact`, the accounts are
+        /// registered at once without relayer attestations
         #[pallet::call_index(1)]
-        #[pallet::weight(Weight::default())]
+        #[pallet::weight(T::WeightInfo::register_trader(T::MaxRelayers::get()))]
         pub fn register_trader(
             origin: OriginFor<T>,
             trader_id: AccountIdFor<T>,
-            onchain_trading_accounts: TradingAccounts<AccountIdFor<T>>,
+            onchain_trading_accounts: TradingAccounts,
+            attestation: TradingAccountsAttestation,
         ) -> DispatchResult {
-            let relayer_id = ensure_signed(origin)?;
+            // the registrar contract reaching us over XCM needs no relayer attestations
+            let relayer_id = match T::TraderRegistrarOrigin::try_origin(origin) {
+                Ok(_) => None,
+                Err(origin) => Some(ensure_signed(origin)?),
+            };
+            let trader_profile =
+                TraderProfiles::<T>::get(&trader_id).ok_or(Error::<T>::TraderNotRegistered)?;
+            ensure!(
+                trader_profile.status == TraderStatus::Applied,
+                Error::<T>::InvalidTraderStatus
+            );
+            ensure!(
+                onchain_trading_accounts.is_valid(),
+                Error::<T>::InvalidTradingAccount
+            );
+            Self::ensure_trading_accounts_attested(
+                &trader_id,
+                &onchain_trading_accounts,
+                &attestation,
+            )?;
+
+            let Some(relayer_id) = relayer_id else {
+                Self::finalize_trader_registration(
+                    trader_id,
+                    onchain_trading_accounts,
+                    attestation.nonce,
+                );
+                return Ok(());
+            };
             // check the signer relayer is registered on chain
-            let registered_relayer_id =
-                Relayer::<T>::get().ok_or(Error::<T>::RelayerUnavailable)?;
+            let relayers = Relayers::<T>::get();
+            ensure!(!relayers.is_empty(), Error::<T>::RelayerUnavailable);
             ensure!(
-                relayer_id == registered_relayer_id,
+                relayers.contains(&relayer_id),
                 Error::<T>::RelayerNotRegistered
             );
 
-            // register the accounts
-            OnChainTradingAccounts::<T>::insert(trader_id.clone(), &onchain_trading_accounts);
+            let attested_hash = T::Hashing::hash_of(&onchain_trading_accounts);
+            // a relayer vouching for two different account sets of the same trader is equivocating
+            ensure!(
+                !TraderRegistrationAttestations::<T>::iter_prefix(&trader_id)
+                    .any(|(hash, attestations)| hash != attested_hash
+                        && attestations.contains(&relayer_id)),
+                Error::<T>::ConflictingAttestation
+            );
+
+            let attestations = TraderRegistrationAttestations::<T>::try_mutate(
+                &trader_id,
+                attested_hash,
+                |attestations| -> Result<u32, DispatchError> {
+                    ensure!(
+                        !attestations.contains(&relayer_id),
+                        Error::<T>::AlreadyAttested
+                    );
+                    attestations
+                        .try_push(relayer_id.clone())
+                        .map_err(|_| Error::<T>::TooManyRelayers)?;
+                    // attestations of relayers removed in the meantime no longer count
+                    Ok(attestations
+                        .iter()
+                        .filter(|relayer| relayers.contains(relayer))
+                        .count() as u32)
+                },
+            )?;
 
-            Self::deposit_event(Event::TraderRegistered { id: trader_id });
+            Self::deposit_event(Event::TraderRegistrationAttested {
+                trader_id: trader_id.clone(),
+                relayer: relayer_id,
+                attestations,
+            });
+
+            if attestations >= RelayerThreshold::<T>::get() {
+                let _ = TraderRegistrationAttestations::<T>::clear_prefix(
+                    &trader_id,
+                    T::MaxRelayers::get(),
+                    None,
+                );
+                Self::finalize_trader_registration(
+                    trader_id,
+                    onchain_trading_accounts,
+                    attestation.nonce,
+                );
+            }
             Ok(())
         }
 
-        /// Allocate capital from the pool to the trader onchain trading account
+        /// Allocate capital from the pool the trader applied to, to the trader onchain trading account
+        /// The allocation is capped by the pool unallocated capital and the pool `max_trader_allocation`
         #[pallet::call_index(2)]
-        #[pallet::weight(Weight::default())]
-        pub fn allocate_capital(origin: OriginFor<T>, network: Networks) -> DispatchResult {
+        #[pallet::weight(T::WeightInfo::allocate_capital())]
+        pub fn allocate_capital(
+            origin: OriginFor<T>,
+            network: NetworkId,
+            amount: AssetBalance<T>,
+        ) -> DispatchResult {
             let trader_id = ensure_signed(origin)?;
 
-            match network {
-                Networks::Substrate => {
-                    let onchain_trading_account =
-                        OnChainTradingAccounts::<T>::get(trader_id.clone())
-                            .ok_or(Error::<T>::TraderNotRegistered)?
-                            .substrate
-                            .ok_or(Error::<T>::TraderNotRegistered)?;
-
-                    T::CapitalAllocator::allocate_capital(
-                        network.clone(),
-                        trader_id.clone(),
-                        onchain_trading_account.clone(),
-                    )?;
-                    Self::deposit_event(Event::FundsAllocated {
-                        trader_id,
-                        onchain_trading_account,
-                        network,
-                    });
+            let mut trader_profile =
+                TraderProfiles::<T>::get(&trader_id).ok_or(Error::<T>::TraderNotRegistered)?;
+            ensure!(
+                trader_profile.status == TraderStatus::Active,
+                Error::<T>::TraderNotActive
+            );
+            let asset_id = trader_profile.asset_id.clone();
+            // the open allocation is deployed on a single network at a time
+            ensure!(
+                trader_profile
+                    .network
+                    .as_ref()
+                    .map_or(true, |allocated_network| *allocated_network == network),
+                Error::<T>::AllocationNetworkMismatch
+            );
+
+            let (network_info, onchain_trading_account) =
+                Self::trading_account_on(&trader_id, network)?;
+            ensure!(network_info.enabled, Error::<T>::NetworkDisabled);
+
+            ensure!(
+                RedemptionQueues::<T>::get(asset_id.clone()).is_empty(),
+                Error::<T>::RedemptionsPending
+            );
+
+            CapitalPool::<T>::try_mutate(asset_id.clone(), |pool| -> DispatchResult {
+                pool.ensure_active()?;
+                ensure!(
+                    pool.remaining_capital >= amount,
+                    Error::<T>::InsufficientPoolLiquidity
+                );
+                if let Some(max_allocation) = pool.params.max_trader_allocation {
+                    let allocation = trader_profile
+                        .funds_allocated
+                        .checked_add(&amount)
+                        .ok_or(ArithmeticError::Overflow)?;
+                    ensure!(
+                        allocation <= max_allocation,
+                        Error::<T>::TraderAllocationExceeded
+                    );
                 }
-                _ => todo!(),
-            }
+
+                T::CapitalAllocator::allocate_capital(
+                    network,
+                    asset_id.clone(),
+                    amount,
+                    trader_id.clone(),
+                    onchain_trading_account.clone(),
+                )?;
+                pool.update_allocated_funds(amount)?;
+                trader_profile.deposit_allocated_funds(amount);
+                trader_profile.network = Some(network);
+                trader_profile.last_report = frame_system::Pallet::<T>::block_number();
+                Ok(())
+            })?;
+            TraderProfiles::<T>::insert(&trader_id, trader_profile);
+            Self::deposit_event(Event::FundsAllocated {
+                trader_id,
+                onchain_trading_account,
+                network,
+            });
 
             Ok(())
         }
 
         #[pallet::call_index(3)]
-        #[pallet::weight(Weight::default())]
+        #[pallet::weight(T::WeightInfo::verify_trade_execution())]
         pub fn verify_trade_execution(
             origin: OriginFor<T>,
             trader_id: AccountIdFor<T>,
             asset_id: T::CurrencyId,
-            network: Networks,
+            network: NetworkId,
             trade_execution_proof: TradeExecutionProof<BlockNumberFor<T>>,
             trade_action: TradeAction,
         ) -> DispatchResult {
             ensure_none(origin)?;
+            ensure!(
+                trade_execution_proof.target_network == network,
+                Error::<T>::ProofNetworkMismatch
+            );
 
-            let trading_account = OnChainTradingAccounts::<T>::get(trader_id.clone())
-                .ok_or(Error::<T>::TraderNotRegistered)?
-                .substrate
-                .ok_or(Error::<T>::TraderNotRegistered)?;
+            let (network_info, trading_account) = Self::trading_account_on(&trader_id, network)?;
+            ensure!(network_info.enabled, Error::<T>::NetworkDisabled);
+            // balances are recorded and bonds slashed only on proofs anchored to finalized roots
+            Self::ensure_trusted_roots(network, &network_info, &trade_execution_proof)?;
+            // and only once per proof, never rolled back to an older block
+            Self::ensure_fresh_trade_proof(&trader_id, network, &trade_execution_proof)?;
 
             // verify proofs submitted per the network
             T::TradeExecutionVerifier::verify_trade_execution(
                 trader_id.clone(),
                 trading_account.clone(),
                 asset_id.clone(),
-                network.clone(),
+                network,
                 trade_execution_proof.clone(),
                 trade_action.clone(),
             )?;
+            Self::enforce_risk_policy(
+                &trader_id,
+                network,
+                &network_info,
+                &trading_account,
+                trade_execution_proof.transaction_inclusion.transaction(),
+            )?;
+            Self::record_trade_proof(&trader_id, network, &trade_execution_proof);
 
             Self::deposit_event(Event::TradeVerifiedSuccesfully {
                 network,
@@ -422,5 +1330,1253 @@ pub mod pallet {
             });
             Ok(())
         }
+
+        /// Create a capital pool for `asset_id` with its own keyless pool account and share token
+        #[pallet::call_index(4)]
+        #[pallet::weight(T::WeightInfo::create_pool())]
+        pub fn create_pool(
+            origin: OriginFor<T>,
+            asset_id: T::CurrencyId,
+            params: PoolParams<AssetBalance<T>>,
+        ) -> DispatchResult {
+            T::PoolAdminOrigin::ensure_origin(origin)?;
+
+            ensure!(
+                !CapitalPool::<T>::contains_key(asset_id.clone()),
+                Error::<T>::PoolAlreadyExists
+            );
+
+            let share_asset_id = Self::register_share_asset(&asset_id)?;
+            let pool = InvestorCapitalPool::<T>::new(asset_id.clone(), params);
+            let account_id = pool.account_id.clone();
+            CapitalPool::<T>::insert(asset_id.clone(), pool);
+
+            Self::deposit_event(Event::PoolCreated {
+                asset_id,
+                account_id,
+                share_asset_id,
+            });
+            Ok(())
+        }
+
+        /// Update the fee, deposit caps, minimum deposit and per trader allocation cap of a pool
+        #[pallet::call_index(5)]
+        #[pallet::weight(T::WeightInfo::set_pool_params())]
+        pub fn set_pool_params(
+            origin: OriginFor<T>,
+            asset_id: T::CurrencyId,
+            params: PoolParams<AssetBalance<T>>,
+        ) -> DispatchResult {
+            T::PoolAdminOrigin::ensure_origin(origin)?;
+
+            ensure!(
+                CapitalPool::<T>::contains_key(asset_id.clone()),
+                Error::<T>::AssetPoolNotSupported
+            );
+
+            CapitalPool::<T>::try_mutate(asset_id.clone(), |pool| -> DispatchResult {
+                ensure!(
+                    matches!(pool.status, PoolStatus::Active | PoolStatus::Paused),
+                    Error::<T>::PoolClosed
+                );
+                pool.params = params.clone();
+                Ok(())
+            })?;
+
+            Self::deposit_event(Event::PoolParamsUpdated { asset_id, params });
+            Ok(())
+        }
+
+        /// Halt deposits and capital allocation of a pool, investors can still withdraw
+        #[pallet::call_index(6)]
+        #[pallet::weight(T::WeightInfo::pause_pool())]
+        pub fn pause_pool(origin: OriginFor<T>, asset_id: T::CurrencyId) -> DispatchResult {
+            T::PoolAdminOrigin::ensure_origin(origin)?;
+
+            ensure!(
+                CapitalPool::<T>::contains_key(asset_id.clone()),
+                Error::<T>::AssetPoolNotSupported
+            );
+
+            CapitalPool::<T>::try_mutate(asset_id.clone(), |pool| -> DispatchResult {
+                pool.ensure_active()?;
+                pool.status = PoolStatus::Paused;
+                Ok(())
+            })?;
+
+            Self::deposit_event(Event::PoolPaused { asset_id });
+            Ok(())
+        }
+
+        /// Resume a paused pool
+        #[pallet::call_index(7)]
+        #[pallet::weight(T::WeightInfo::resume_pool())]
+        pub fn resume_pool(origin: OriginFor<T>, asset_id: T::CurrencyId) -> DispatchResult {
+            T::PoolAdminOrigin::ensure_origin(origin)?;
+
+            ensure!(
+                CapitalPool::<T>::contains_key(asset_id.clone()),
+                Error::<T>::AssetPoolNotSupported
+            );
+
+            CapitalPool::<T>::try_mutate(asset_id.clone(), |pool| -> DispatchResult {
+                ensure!(pool.status == PoolStatus::Paused, Error::<T>::PoolNotPaused);
+                pool.status = PoolStatus::Active;
+                Ok(())
+            })?;
+
+            Self::deposit_event(Event::PoolResumed { asset_id });
+            Ok(())
+        }
+
+        /// Retire a pool. The pool stops accepting deposits and allocations, every open
+        /// allocation is recalled and investors can redeem their share without waiting for the withdraw period
+        #[pallet::call_index(8)]
+        #[pallet::weight(T::WeightInfo::close_pool())]
+        pub fn close_pool(origin: OriginFor<T>, asset_id: T::CurrencyId) -> DispatchResult {
+            T::PoolAdminOrigin::ensure_origin(origin)?;
+
+            ensure!(
+                CapitalPool::<T>::contains_key(asset_id.clone()),
+                Error::<T>::AssetPoolNotSupported
+            );
+
+            let fully_recalled = CapitalPool::<T>::try_mutate(
+                asset_id.clone(),
+                |pool| -> Result<bool, DispatchError> {
+                    ensure!(
+                        matches!(pool.status, PoolStatus::Active | PoolStatus::Paused),
+                        Error::<T>::PoolClosed
+                    );
+                    let fully_recalled = Self::recall_pool_allocations(asset_id.clone())?;
+                    pool.status = if fully_recalled {
+                        PoolStatus::Closed
+                    } else {
+                        PoolStatus::Closing
+                    };
+                    Ok(fully_recalled)
+                },
+            )?;
+
+            Self::deposit_event(Event::PoolClosing {
+                asset_id: asset_id.clone(),
+            });
+            if fully_recalled {
+                Self::deposit_event(Event::PoolClosed { asset_id });
+            }
+            Ok(())
+        }
+
+        /// Withdraw deposited capital together with its share of the pool gains or losses.
+        /// On active pools the investor `withdraw_period` must have elapsed, closing pools can be redeemed at any time
+        #[pallet::call_index(9)]
+        #[pallet::weight(T::WeightInfo::withdraw_capital())]
+        pub fn withdraw_capital(
+            origin: OriginFor<T>,
+            asset_id: T::CurrencyId,
+            amount: AssetBalance<T>,
+        ) -> DispatchResult {
+            let investor = ensure_signed(origin)?;
+
+            ensure!(
+                CapitalPool::<T>::contains_key(asset_id.clone()),
+                Error::<T>::AssetPoolNotSupported
+            );
+
+            // pending redemptions are served first
+            ensure!(
+                RedemptionQueues::<T>::get(asset_id.clone()).is_empty(),
+                Error::<T>::RedemptionsPending
+            );
+
+            let payout = CapitalPool::<T>::try_mutate(
+                asset_id.clone(),
+                |pool| -> Result<AssetBalance<T>, DispatchError> {
+                    let mut position = Self::sync_investor_position(&investor, &asset_id, pool);
+                    ensure!(
+                        !position.deposited.is_zero(),
+                        Error::<T>::InvestorNotRegistered
+                    );
+                    Self::ensure_withdraw_period_elapsed(pool, &position, amount)?;
+                    let payout =
+                        Self::redeem_deposit(&investor, &asset_id, pool, &mut position, amount)?;
+                    Self::store_investor_position(&investor, asset_id.clone(), position)?;
+                    Ok(payout)
+                },
+            )?;
+
+            Self::deposit_event(Event::CapitalWithdrawn {
+                investor_id: investor,
+                asset_id,
+                amount: payout,
+            });
+            Ok(())
+        }
+
+        /// Add a relayer to the relayer set
+        #[pallet::call_index(10)]
+        #[pallet::weight(T::WeightInfo::add_relayer(T::MaxRelayers::get()))]
+        pub fn add_relayer(origin: OriginFor<T>, relayer: AccountIdFor<T>) -> DispatchResult {
+            T::RelayerAdminOrigin::ensure_origin(origin)?;
+
+            Relayers::<T>::try_mutate(|relayers| -> DispatchResult {
+                ensure!(
+                    !relayers.contains(&relayer),
+                    Error::<T>::RelayerAlreadyRegistered
+                );
+                relayers
+                    .try_push(relayer.clone())
+                    .map_err(|_| Error::<T>::TooManyRelayers)?;
+                Ok(())
+            })?;
+
+            Self::deposit_event(Event::RelayerAdded { relayer });
+            Ok(())
+        }
+
+        /// Remove a relayer from the relayer set, the threshold is lowered if it exceeds the remaining relayers
+        #[pallet::call_index(11)]
+        #[pallet::weight(T::WeightInfo::remove_relayer(T::MaxRelayers::get()))]
+        pub fn remove_relayer(origin: OriginFor<T>, relayer: AccountIdFor<T>) -> DispatchResult {
+            T::RelayerAdminOrigin::ensure_origin(origin)?;
+
+            Self::do_remove_relayer(&relayer)
+        }
+
+        /// Replace a relayer key, callable by `RelayerAdminOrigin` or by the relayer being rotated
+        #[pallet::call_index(12)]
+        #[pallet::weight(T::WeightInfo::rotate_relayer(T::MaxRelayers::get()))]
+        pub fn rotate_relayer(
+            origin: OriginFor<T>,
+            old: AccountIdFor<T>,
+            new: AccountIdFor<T>,
+        ) -> DispatchResult {
+            if let Err(origin) = T::RelayerAdminOrigin::try_origin(origin) {
+                let who = ensure_signed(origin)?;
+                ensure!(who == old, DispatchError::BadOrigin);
+            }
+
+            Relayers::<T>::try_mutate(|relayers| -> DispatchResult {
+                ensure!(
+                    !relayers.contains(&new),
+                    Error::<T>::RelayerAlreadyRegistered
+                );
+                let relayer = relayers
+                    .iter_mut()
+                    .find(|relayer| **relayer == old)
+                    .ok_or(Error::<T>::RelayerNotRegistered)?;
+                *relayer = new.clone();
+                Ok(())
+            })?;
+            RelayerReports::<T>::remove(&old);
+
+            Self::deposit_event(Event::RelayerRotated { old, new });
+            Ok(())
+        }
+
+        /// Set the number of relayer attestations required to register a trader
+        #[pallet::call_index(13)]
+        #[pallet::weight(T::WeightInfo::set_relayer_threshold())]
+        pub fn set_relayer_threshold(origin: OriginFor<T>, threshold: u32) -> DispatchResult {
+            T::RelayerAdminOrigin::ensure_origin(origin)?;
+
+            ensure!(
+                threshold > 0 && threshold as usize <= Relayers::<T>::decode_len().unwrap_or(0),
+                Error::<T>::InvalidRelayerThreshold
+            );
+            RelayerThreshold::<T>::put(threshold);
+
+            Self::deposit_event(Event::RelayerThresholdSet { threshold });
+            Ok(())
+        }
+
+        /// Report a relayer for misbehavior. Once reported by `RelayerThreshold` other relayers
+        /// the offender is removed from the relayer set
+        #[pallet::call_index(14)]
+        #[pallet::weight(T::WeightInfo::report_relayer(T::MaxRelayers::get()))]
+        pub fn report_relayer(origin: OriginFor<T>, offender: AccountIdFor<T>) -> DispatchResult {
+            let reporter = ensure_signed(origin)?;
+
+            let relayers = Relayers::<T>::get();
+            ensure!(
+                relayers.contains(&reporter) && relayers.contains(&offender),
+                Error::<T>::RelayerNotRegistered
+            );
+            ensure!(reporter != offender, Error::<T>::CannotReportSelf);
+
+            let reports = RelayerReports::<T>::try_mutate(
+                &offender,
+                |reports| -> Result<u32, DispatchError> {
+                    ensure!(!reports.contains(&reporter), Error::<T>::AlreadyReported);
+                    reports
+                        .try_push(reporter.clone())
+                        .map_err(|_| Error::<T>::TooManyRelayers)?;
+                    Ok(reports
+                        .iter()
+                        .filter(|relayer| relayers.contains(relayer))
+                        .count() as u32)
+                },
+            )?;
+
+            Self::deposit_event(Event::RelayerReported {
+                reporter,
+                offender: offender.clone(),
+                reports,
+            });
+
+            // the offender cannot report itself, so at most every other relayer can report it
+            let required_reports = RelayerThreshold::<T>::get()
+                .min(relayers.len().saturating_sub(1) as u32)
+                .max(1);
+            if reports >= required_reports {
+                Self::do_remove_relayer(&offender)?;
+            }
+            Ok(())
+        }
+
+        /// Apply to trade the capital of the `asset_id` pool by placing `bond` of the pool asset on hold.
+        /// Relayers then confirm the trading accounts generated in the contract through `register_trader`
+        #[pallet::call_index(15)]
+        #[pallet::weight(T::WeightInfo::apply_as_trader())]
+        pub fn apply_as_trader(
+            origin: OriginFor<T>,
+            asset_id: T::CurrencyId,
+            bond: AssetBalance<T>,
+        ) -> DispatchResult {
+            let trader_id = ensure_signed(origin)?;
+
+            ensure!(
+                CapitalPool::<T>::contains_key(asset_id.clone()),
+                Error::<T>::AssetPoolNotSupported
+            );
+            ensure!(
+                !TraderProfiles::<T>::contains_key(&trader_id),
+                Error::<T>::TraderAlreadyApplied
+            );
+
+            let pool = CapitalPool::<T>::get(asset_id.clone());
+            pool.ensure_active()?;
+            ensure!(
+                bond >= pool.params.min_trader_bond,
+                Error::<T>::InsufficientBond
+            );
+
+            <orml_tokens::Pallet<T> as MultiReservableCurrency<AccountIdFor<T>>>::reserve(
+                asset_id.clone(),
+                &trader_id,
+                bond,
+            )
+            .map_err(|_| Error::<T>::InsufficientBond)?;
+
+            TraderProfiles::<T>::insert(
+                &trader_id,
+                TraderProfile::<T>::new(asset_id.clone(), bond),
+            );
+
+            Self::deposit_event(Event::TraderApplied {
+                trader_id,
+                asset_id,
+                bond,
+            });
+            Ok(())
+        }
+
+        /// Approve a trader whose trading accounts were confirmed by the relayers
+        #[pallet::call_index(16)]
+        #[pallet::weight(T::WeightInfo::approve_trader())]
+        pub fn approve_trader(origin: OriginFor<T>, trader_id: AccountIdFor<T>) -> DispatchResult {
+            T::TraderAdminOrigin::ensure_origin(origin)?;
+
+            TraderProfiles::<T>::try_mutate(&trader_id, |profile| -> DispatchResult {
+                let profile = profile.as_mut().ok_or(Error::<T>::TraderNotRegistered)?;
+                ensure!(
+                    profile.status == TraderStatus::AccountsConfirmed,
+                    Error::<T>::InvalidTraderStatus
+                );
+                profile.status = TraderStatus::Active;
+                Ok(())
+            })?;
+
+            Self::deposit_event(Event::TraderApproved { trader_id });
+            Ok(())
+        }
+
+        /// Suspend a trader, its open allocation is recalled and no new capital is allocated
+        #[pallet::call_index(17)]
+        #[pallet::weight(T::WeightInfo::suspend_trader())]
+        pub fn suspend_trader(origin: OriginFor<T>, trader_id: AccountIdFor<T>) -> DispatchResult {
+            T::TraderAdminOrigin::ensure_origin(origin)?;
+
+            Self::do_suspend_trader(&trader_id, SuspensionReason::Governance)
+        }
+
+        /// Reinstate a suspended trader
+        #[pallet::call_index(18)]
+        #[pallet::weight(T::WeightInfo::reinstate_trader())]
+        pub fn reinstate_trader(
+            origin: OriginFor<T>,
+            trader_id: AccountIdFor<T>,
+        ) -> DispatchResult {
+            T::TraderAdminOrigin::ensure_origin(origin)?;
+
+            TraderProfiles::<T>::try_mutate(&trader_id, |profile| -> DispatchResult {
+                let profile = profile.as_mut().ok_or(Error::<T>::TraderNotRegistered)?;
+                ensure!(
+                    profile.status == TraderStatus::Suspended,
+                    Error::<T>::InvalidTraderStatus
+                );
+                profile.status = TraderStatus::Active;
+                Ok(())
+            })?;
+
+            Self::deposit_event(Event::TraderReinstated { trader_id });
+            Ok(())
+        }
+
+        /// Leave the pool. Without open allocation the bond is released right away, otherwise the
+        /// allocation is recalled and the trader is deregistered once it is settled
+        #[pallet::call_index(19)]
+        #[pallet::weight(T::WeightInfo::deregister_trader())]
+        pub fn deregister_trader(origin: OriginFor<T>) -> DispatchResult {
+            let trader_id = ensure_signed(origin)?;
+
+            let mut trader_profile =
+                TraderProfiles::<T>::get(&trader_id).ok_or(Error::<T>::TraderNotRegistered)?;
+            ensure!(
+                trader_profile.status != TraderStatus::Exiting,
+                Error::<T>::InvalidTraderStatus
+            );
+
+            if trader_profile.funds_allocated.is_zero() {
+                return Self::do_deregister_trader(&trader_id, trader_profile);
+            }
+
+            Self::recall_trader_capital(&trader_id, &trader_profile)?;
+            trader_profile.status = TraderStatus::Exiting;
+            TraderProfiles::<T>::insert(&trader_id, trader_profile);
+
+            Self::deposit_event(Event::TraderExiting { trader_id });
+            Ok(())
+        }
+
+        /// Called by relayers once the capital allocated to the trader is back in the pool account.
+        /// Each call is one relayer attestation of the returned amount, the allocation is settled once
+        /// `RelayerThreshold` relayers attested the same amount for the same allocation.
+        /// The pool account balance must cover the amount on top of the pool unallocated capital and profit reserve
+        #[pallet::call_index(20)]
+        #[pallet::weight(T::WeightInfo::settle_allocation(
+            T::MaxRelayers::get(),
+            T::MaxRedemptionRequests::get()
+        ))]
+        pub fn settle_allocation(
+            origin: OriginFor<T>,
+            trader_id: AccountIdFor<T>,
+            returned: AssetBalance<T>,
+        ) -> DispatchResult {
+            let relayer_id = ensure_signed(origin)?;
+
+            let relayers = Relayers::<T>::get();
+            ensure!(
+                relayers.contains(&relayer_id),
+                Error::<T>::RelayerNotRegistered
+            );
+            let profile =
+                TraderProfiles::<T>::get(&trader_id).ok_or(Error::<T>::TraderNotRegistered)?;
+            ensure!(
+                !profile.funds_allocated.is_zero(),
+                Error::<T>::TraderNotFunded
+            );
+
+            // attestations of an earlier allocation of the trader do not count for this one
+            let attested_hash = T::Hashing::hash_of(&(profile.funds_allocated, returned));
+            // a relayer reporting two different amounts for the same allocation is equivocating
+            ensure!(
+                !SettlementAttestations::<T>::iter_prefix(&trader_id)
+                    .any(|(hash, attestations)| hash != attested_hash
+                        && attestations.contains(&relayer_id)),
+                Error::<T>::ConflictingAttestation
+            );
+
+            let attestations = SettlementAttestations::<T>::try_mutate(
+                &trader_id,
+                attested_hash,
+                |attestations| -> Result<u32, DispatchError> {
+                    ensure!(
+                        !attestations.contains(&relayer_id),
+                        Error::<T>::AlreadyAttested
+                    );
+                    attestations
+                        .try_push(relayer_id.clone())
+                        .map_err(|_| Error::<T>::TooManyRelayers)?;
+                    // attestations of relayers removed in the meantime no longer count
+                    Ok(attestations
+                        .iter()
+                        .filter(|relayer| relayers.contains(relayer))
+                        .count() as u32)
+                },
+            )?;
+
+            Self::deposit_event(Event::SettlementAttested {
+                trader_id: trader_id.clone(),
+                returned,
+                relayer: relayer_id,
+                attestations,
+            });
+
+            if attestations >= RelayerThreshold::<T>::get() {
+                let _ = SettlementAttestations::<T>::clear_prefix(
+                    &trader_id,
+                    T::MaxRelayers::get(),
+                    None,
+                );
+                Self::do_settle_allocation(&trader_id, returned)?;
+            }
+            Ok(())
+        }
+
+        /// Register a target network or update its chain id, hasher, proof kind and finality source.
+        /// Only networks whose trades can be verified are accepted, see `NetworkInfo::is_supported`.
+        /// Disabling a network blocks new allocations and trade proofs on it
+        #[pallet::call_index(21)]
+        #[pallet::weight(T::WeightInfo::set_target_network())]
+        pub fn set_target_network(
+            origin: OriginFor<T>,
+            network_id: NetworkId,
+            info: NetworkInfo,
+        ) -> DispatchResult {
+            T::RegistryAdminOrigin::ensure_origin(origin)?;
+            ensure!(info.is_supported(), Error::<T>::UnsupportedProofKind);
+
+            TargetNetworks::<T>::insert(network_id, info.clone());
+            Self::deposit_event(Event::TargetNetworkSet { network_id, info });
+            Ok(())
+        }
+
+        /// Remove a target network without venues and open allocations
+        #[pallet::call_index(22)]
+        #[pallet::weight(T::WeightInfo::remove_target_network())]
+        pub fn remove_target_network(
+            origin: OriginFor<T>,
+            network_id: NetworkId,
+        ) -> DispatchResult {
+            T::RegistryAdminOrigin::ensure_origin(origin)?;
+            ensure!(
+                TargetNetworks::<T>::contains_key(network_id),
+                Error::<T>::NetworkNotSupported
+            );
+            ensure!(
+                !DexVenues::<T>::iter_values().any(|venue| venue.network == network_id),
+                Error::<T>::NetworkInUse
+            );
+            ensure!(
+                !TraderProfiles::<T>::iter_values()
+                    .any(|profile| profile.network == Some(network_id)),
+                Error::<T>::NetworkInUse
+            );
+
+            TargetNetworks::<T>::remove(network_id);
+            let _ = NetworkAssets::<T>::clear_prefix(network_id, u32::MAX, None);
+            Self::deposit_event(Event::TargetNetworkRemoved { network_id });
+            Ok(())
+        }
+
+        /// Register a dex venue on a registered network or update its addresses and allowed assets
+        #[pallet::call_index(23)]
+        #[pallet::weight(T::WeightInfo::set_dex_venue())]
+        pub fn set_dex_venue(
+            origin: OriginFor<T>,
+            venue_id: VenueId,
+            venue: DexVenue,
+        ) -> DispatchResult {
+            T::RegistryAdminOrigin::ensure_origin(origin)?;
+            ensure!(
+                TargetNetworks::<T>::contains_key(venue.network),
+                Error::<T>::NetworkNotSupported
+            );
+
+            DexVenues::<T>::insert(venue_id, venue.clone());
+            Self::deposit_event(Event::DexVenueSet { venue_id, venue });
+            Ok(())
+        }
+
+        #[pallet::call_index(24)]
+        #[pallet::weight(T::WeightInfo::remove_dex_venue())]
+        pub fn remove_dex_venue(origin: OriginFor<T>, venue_id: VenueId) -> DispatchResult {
+            T::RegistryAdminOrigin::ensure_origin(origin)?;
+            ensure!(
+                DexVenues::<T>::contains_key(venue_id),
+                Error::<T>::VenueNotFound
+            );
+
+            DexVenues::<T>::remove(venue_id);
+            Self::deposit_event(Event::DexVenueRemoved { venue_id });
+            Ok(())
+        }
+
+        /// Register the balances map of the `asset_id` pool asset on a substrate target network, the trade proofs
+        /// of the network must prove the entry of the trading account in it. `None` removes it
+        #[pallet::call_index(40)]
+        #[pallet::weight(T::WeightInfo::set_network_asset())]
+        pub fn set_network_asset(
+            origin: OriginFor<T>,
+            network_id: NetworkId,
+            asset_id: T::CurrencyId,
+            balance_storage: Option<RemoteBalanceStorage>,
+        ) -> DispatchResult {
+            T::RegistryAdminOrigin::ensure_origin(origin)?;
+            ensure!(
+                TargetNetworks::<T>::contains_key(network_id),
+                Error::<T>::NetworkNotSupported
+            );
+            ensure!(
+                CapitalPool::<T>::contains_key(asset_id.clone()),
+                Error::<T>::AssetPoolNotSupported
+            );
+
+            NetworkAssets::<T>::set(network_id, asset_id.clone(), balance_storage.clone());
+            Self::deposit_event(Event::NetworkAssetSet {
+                network_id,
+                asset_id,
+                balance_storage,
+            });
+            Ok(())
+        }
+
+        /// Set the risk policy verified trades of the trader are evaluated against
+        #[pallet::call_index(25)]
+        #[pallet::weight(T::WeightInfo::set_risk_policy())]
+        pub fn set_risk_policy(
+            origin: OriginFor<T>,
+            trader_id: AccountIdFor<T>,
+            policy: RiskPolicy<BlockNumberFor<T>>,
+        ) -> DispatchResult {
+            T::TraderAdminOrigin::ensure_origin(origin)?;
+            ensure!(
+                policy
+                    .allowed_venues
+                    .iter()
+                    .all(|venue_id| DexVenues::<T>::contains_key(venue_id)),
+                Error::<T>::VenueNotFound
+            );
+
+            TraderProfiles::<T>::try_mutate(&trader_id, |profile| -> DispatchResult {
+                let profile = profile.as_mut().ok_or(Error::<T>::TraderNotRegistered)?;
+                profile.risk_policy = policy;
+                Ok(())
+            })?;
+            Self::deposit_event(Event::RiskPolicySet { trader_id });
+            Ok(())
+        }
+
+        /// Pay a claim approved by `InsuranceClaimOrigin` out of the insurance fund of `asset_id`
+        #[pallet::call_index(26)]
+        #[pallet::weight(T::WeightInfo::pay_insurance_claim())]
+        pub fn pay_insurance_claim(
+            origin: OriginFor<T>,
+            asset_id: T::CurrencyId,
+            beneficiary: AccountIdFor<T>,
+            amount: AssetBalance<T>,
+        ) -> DispatchResult {
+            T::InsuranceClaimOrigin::ensure_origin(origin)?;
+            ensure!(
+                Self::insurance_fund_balance(asset_id.clone()) >= amount,
+                Error::<T>::InsufficientInsuranceFund
+            );
+
+            Self::transfer_from_pool(
+                Self::generate_insurance_account(asset_id.clone()),
+                beneficiary.clone(),
+                asset_id.clone(),
+                amount,
+            )?;
+            Self::deposit_event(Event::InsuranceClaimPaid {
+                asset_id,
+                beneficiary,
+                amount,
+            });
+            Ok(())
+        }
+
+        /// Claim the profit distributed to the investor deposit in `asset_id` pool, the principal stays deposited
+        #[pallet::call_index(27)]
+        #[pallet::weight(T::WeightInfo::claim_profit())]
+        pub fn claim_profit(origin: OriginFor<T>, asset_id: T::CurrencyId) -> DispatchResult {
+            let investor = ensure_signed(origin)?;
+
+            CapitalPool::<T>::try_mutate(asset_id.clone(), |pool| -> DispatchResult {
+                // profit credited before a full withdrawal stays claimable
+                let position = Self::sync_investor_position(&investor, &asset_id, pool);
+                Self::store_investor_position(&investor, asset_id.clone(), position)?;
+                let amount = InvestorRewards::<T>::mutate(&investor, asset_id.clone(), |reward| {
+                    let amount = core::mem::take(&mut reward.pending);
+                    reward.claimed = reward.claimed.saturating_add(amount);
+                    amount
+                });
+                ensure!(!amount.is_zero(), Error::<T>::NothingToClaim);
+
+                pool.profit_reserve = pool.profit_reserve.saturating_sub(amount);
+                Self::transfer_from_pool(
+                    pool.account_id.clone(),
+                    investor.clone(),
+                    asset_id.clone(),
+                    amount,
+                )?;
+                Self::deposit_event(Event::ProfitClaimed {
+                    investor_id: investor.clone(),
+                    asset_id: asset_id.clone(),
+                    amount,
+                });
+                Ok(())
+            })
+        }
+
+        /// Claim the performance fees owed to the trader, paid from the pool unallocated capital
+        #[pallet::call_index(28)]
+        #[pallet::weight(T::WeightInfo::claim_performance_fees())]
+        pub fn claim_performance_fees(origin: OriginFor<T>) -> DispatchResult {
+            let trader_id = ensure_signed(origin)?;
+            let mut trader_profile =
+                TraderProfiles::<T>::get(&trader_id).ok_or(Error::<T>::TraderNotRegistered)?;
+            let amount = trader_profile.accrued_fees;
+            ensure!(!amount.is_zero(), Error::<T>::NothingToClaim);
+            let asset_id = trader_profile.asset_id.clone();
+
+            CapitalPool::<T>::try_mutate(asset_id.clone(), |pool| -> DispatchResult {
+                ensure!(
+                    pool.remaining_capital >= amount,
+                    Error::<T>::InsufficientPoolLiquidity
+                );
+                pool.remaining_capital = pool.remaining_capital.saturating_sub(amount);
+                pool.trader_fees_owed = pool.trader_fees_owed.saturating_sub(amount);
+                Self::transfer_from_pool(
+                    pool.account_id.clone(),
+                    trader_id.clone(),
+                    asset_id.clone(),
+                    amount,
+                )
+            })?;
+            trader_profile.accrued_fees = AssetBalance::<T>::default();
+            TraderProfiles::<T>::insert(&trader_id, trader_profile);
+
+            Self::deposit_event(Event::PerformanceFeesClaimed {
+                trader_id,
+                asset_id,
+                amount,
+            });
+            Ok(())
+        }
+
+        /// Queue the redemption of `shares` deposited capital when the pool unallocated capital is short.
+        /// The shares are locked and the request is filled in order, possibly partially, as capital returns to the pool
+        #[pallet::call_index(29)]
+        #[pallet::weight(T::WeightInfo::request_redemption(T::MaxRedemptionRequests::get()))]
+        pub fn request_redemption(
+            origin: OriginFor<T>,
+            asset_id: T::CurrencyId,
+            shares: AssetBalance<T>,
+        ) -> DispatchResult {
+            let investor = ensure_signed(origin)?;
+            let pool = CapitalPool::<T>::get(asset_id.clone());
+            let position = Self::sync_investor_position(&investor, &asset_id, &pool);
+            ensure!(
+                !position.deposited.is_zero(),
+                Error::<T>::InvestorNotRegistered
+            );
+            let queued = Self::queued_redemptions(&investor, asset_id.clone());
+            ensure!(
+                position.deposited.saturating_sub(queued) >= shares,
+                Error::<T>::InsufficientInvestorCapital
+            );
+            Self::ensure_withdraw_period_elapsed(&pool, &position, queued.saturating_add(shares))?;
+            Self::store_investor_position(&investor, asset_id.clone(), position)?;
+            // locked shares cannot be transferred
+            if let Some(share_asset_id) = pool.share_asset_id.clone() {
+                <orml_tokens::Pallet<T> as MultiReservableCurrency<AccountIdFor<T>>>::reserve(
+                    share_asset_id,
+                    &investor,
+                    shares,
+                )
+                .map_err(|_| Error::<T>::InsufficientInvestorCapital)?;
+            }
+
+            let request_id = NextRedemptionId::<T>::mutate(|id| {
+                let request_id = *id;
+                *id = id.wrapping_add(1);
+                request_id
+            });
+            RedemptionQueues::<T>::try_mutate(asset_id.clone(), |queue| {
+                queue
+                    .try_push(RedemptionRequest {
+                        id: request_id,
+                        investor_id: investor.clone(),
+                        shares,
+                        requested_at: <frame_system::Pallet<T>>::block_number(),
+                    })
+                    .map_err(|_| Error::<T>::RedemptionQueueFull)
+            })?;
+            Self::deposit_event(Event::RedemptionRequested {
+                request_id,
+                investor_id: investor,
+                asset_id: asset_id.clone(),
+                shares,
+            });
+
+            Self::process_redemption_queue(asset_id);
+            Ok(())
+        }
+
+        /// Cancel a queued redemption and unlock its remaining shares
+        #[pallet::call_index(30)]
+        #[pallet::weight(T::WeightInfo::cancel_redemption(T::MaxRedemptionRequests::get()))]
+        pub fn cancel_redemption(
+            origin: OriginFor<T>,
+            asset_id: T::CurrencyId,
+            request_id: RedemptionId,
+        ) -> DispatchResult {
+            let investor = ensure_signed(origin)?;
+
+            let request = RedemptionQueues::<T>::try_mutate(
+                asset_id.clone(),
+                |queue| -> Result<_, DispatchError> {
+                    let index = queue
+                        .iter()
+                        .position(|request| {
+                            request.id == request_id && request.investor_id == investor
+                        })
+                        .ok_or(Error::<T>::RedemptionNotFound)?;
+                    Ok(queue.remove(index))
+                },
+            )?;
+            if let Some(share_asset_id) = CapitalPool::<T>::get(asset_id.clone()).share_asset_id {
+                <orml_tokens::Pallet<T> as MultiReservableCurrency<AccountIdFor<T>>>::unreserve(
+                    share_asset_id,
+                    &investor,
+                    request.shares,
+                );
+            }
+            Self::deposit_event(Event::RedemptionCancelled {
+                request_id,
+                investor_id: investor,
+                asset_id: asset_id.clone(),
+                shares: request.shares,
+            });
+
+            // the next request may now be served
+            Self::process_redemption_queue(asset_id);
+            Ok(())
+        }
+
+        /// Swap the capital allocated to the trader on a remote venue, executed by the pool account itself
+        /// through `RemoteSwapExecutor` instead of the trader onchain trading account. No trade proof is needed,
+        /// the swap is checked against the trader risk policy up front and its result comes back as an XCM query response.
+        /// Only on `XcmQuery` networks, where `CapitalAllocator` funds the account the pool keeps for the trader
+        #[pallet::call_index(31)]
+        #[pallet::weight(T::WeightInfo::request_remote_swap())]
+        pub fn request_remote_swap(origin: OriginFor<T>, swap: RemoteSwap) -> DispatchResult {
+            let trader_id = ensure_signed(origin)?;
+
+            let mut profile =
+                TraderProfiles::<T>::get(&trader_id).ok_or(Error::<T>::TraderNotRegistered)?;
+            ensure!(
+                profile.status == TraderStatus::Active,
+                Error::<T>::TraderNotActive
+            );
+            // the pool trades the open allocation of the trader on its network
+            let network = profile.network.ok_or(Error::<T>::TraderNotFunded)?;
+            let network_info =
+                TargetNetworks::<T>::get(network).ok_or(Error::<T>::NetworkNotSupported)?;
+            ensure!(network_info.enabled, Error::<T>::NetworkDisabled);
+            // elsewhere the capital sits in the trader onchain trading account, out of reach of the pool
+            ensure!(
+                network_info.proof_kind == ProofKind::XcmQuery,
+                Error::<T>::RemoteSwapsNotSupported
+            );
+            let venue = DexVenues::<T>::get(swap.venue).ok_or(Error::<T>::VenueNotFound)?;
+            Self::ensure_remote_swap_allowed(&mut profile, network, &venue, &swap)?;
+
+            let pool = CapitalPool::<T>::get(profile.asset_id.clone());
+            pool.ensure_active()?;
+            let (query_id, responder) = T::RemoteSwapExecutor::execute_swap(
+                &pool.account_id,
+                &trader_id,
+                &profile.asset_id,
+                &network_info,
+                &venue,
+                &swap,
+            )?;
+            RemoteSwaps::<T>::insert(
+                query_id,
+                PendingRemoteSwap {
+                    trader_id: trader_id.clone(),
+                    network,
+                    swap: swap.clone(),
+                    requested_at: <frame_system::Pallet<T>>::block_number(),
+                    responder,
+                },
+            );
+            TraderProfiles::<T>::insert(&trader_id, profile);
+            Self::deposit_event(Event::RemoteSwapRequested {
+                trader_id,
+                query_id,
+                swap,
+            });
+
+            Ok(())
+        }
+
+        /// Notified with the query response reporting the result of a remote swap
+        #[pallet::call_index(32)]
+        #[pallet::weight(T::WeightInfo::remote_swap_executed())]
+        pub fn remote_swap_executed(
+            origin: OriginFor<T>,
+            query_id: QueryId,
+            response: Response,
+        ) -> DispatchResult {
+            let responder = T::XcmResponseOrigin::ensure_origin(origin)?;
+
+            let pending = RemoteSwaps::<T>::take(query_id).ok_or(Error::<T>::RemoteSwapNotFound)?;
+            ensure!(
+                responder == pending.responder,
+                Error::<T>::UnexpectedResponder
+            );
+            match response {
+                Response::DispatchResult(MaybeErrorCode::Success) => {
+                    TraderProfiles::<T>::mutate(&pending.trader_id, |profile| {
+                        if let Some(profile) = profile {
+                            profile.trades_executed = profile.trades_executed.saturating_add(1);
+                        }
+                    });
+                    Self::deposit_event(Event::RemoteSwapExecuted {
+                        trader_id: pending.trader_id,
+                        query_id,
+                    });
+                }
+                response => Self::deposit_event(Event::RemoteSwapFailed {
+                    trader_id: pending.trader_id,
+                    query_id,
+                    response,
+                }),
+            }
+
+            Ok(())
+        }
+
+        /// Drop a remote swap whose result did not come back within `RemoteSwapTimeout` blocks, callable by anyone.
+        /// A result reported later is rejected
+        #[pallet::call_index(41)]
+        #[pallet::weight(T::WeightInfo::expire_remote_swap())]
+        pub fn expire_remote_swap(origin: OriginFor<T>, query_id: QueryId) -> DispatchResult {
+            ensure_signed(origin)?;
+
+            let pending = RemoteSwaps::<T>::get(query_id).ok_or(Error::<T>::RemoteSwapNotFound)?;
+            let now = <frame_system::Pallet<T>>::block_number();
+            ensure!(
+                now.saturating_sub(pending.requested_at) >= T::RemoteSwapTimeout::get(),
+                Error::<T>::RemoteSwapNotExpired
+            );
+            RemoteSwaps::<T>::remove(query_id);
+
+            Self::deposit_event(Event::RemoteSwapExpired {
+                trader_id: pending.trader_id,
+                query_id,
+            });
+            Ok(())
+        }
+
+        /// Called by relayers to verify the balance of a trader on a network with `XcmQuery` proofs, the alternative
+        /// to a `StateProof`. Each call is one relayer claim of the trader `balance`, once `RelayerThreshold` relayers
+        /// claimed the same balance the pool queries the account it controls for the trader on the network, and the
+        /// reported balance is recorded like a verified trading account balance once it covers the claim.
+        /// A relayer claiming another balance replaces its previous claim.
+        /// The pool also queries the last recorded balance itself every `BalanceQueryInterval` blocks
+        #[pallet::call_index(33)]
+        #[pallet::weight(T::WeightInfo::request_balance_query(T::MaxRelayers::get()))]
+        pub fn request_balance_query(
+            origin: OriginFor<T>,
+            trader_id: AccountIdFor<T>,
+            balance: AssetBalance<T>,
+        ) -> DispatchResult {
+            let relayer_id = ensure_signed(origin)?;
+
+            let relayers = Relayers::<T>::get();
+            ensure!(
+                relayers.contains(&relayer_id),
+                Error::<T>::RelayerNotRegistered
+            );
+            let profile =
+                TraderProfiles::<T>::get(&trader_id).ok_or(Error::<T>::TraderNotRegistered)?;
+            ensure!(
+                !profile.funds_allocated.is_zero(),
+                Error::<T>::TraderNotFunded
+            );
+
+            // the balance moves with the trades, an earlier claim of the relayer is outdated
+            let claimed_hash = T::Hashing::hash_of(&(profile.funds_allocated, balance));
+            let outdated: Vec<_> = BalanceClaims::<T>::iter_prefix(&trader_id)
+                .filter(|(hash, claims)| *hash != claimed_hash && claims.contains(&relayer_id))
+                .map(|(hash, _)| hash)
+                .collect();
+            for hash in outdated {
+                BalanceClaims::<T>::mutate_exists(&trader_id, hash, |claims| {
+                    if let Some(relayers) = claims {
+                        relayers.retain(|relayer| *relayer != relayer_id);
+                        if relayers.is_empty() {
+                            *claims = None;
+                        }
+                    }
+                });
+            }
+
+            let claims = BalanceClaims::<T>::try_mutate(
+                &trader_id,
+                claimed_hash,
+                |claims| -> Result<u32, DispatchError> {
+                    ensure!(!claims.contains(&relayer_id), Error::<T>::AlreadyAttested);
+                    claims
+                        .try_push(relayer_id.clone())
+                        .map_err(|_| Error::<T>::TooManyRelayers)?;
+                    // claims of relayers removed in the meantime no longer count
+                    Ok(claims
+                        .iter()
+                        .filter(|relayer| relayers.contains(relayer))
+                        .count() as u32)
+                },
+            )?;
+
+            Self::deposit_event(Event::TraderBalanceClaimed {
+                trader_id: trader_id.clone(),
+                balance,
+                relayer: relayer_id,
+                claims,
+            });
+
+            if claims >= RelayerThreshold::<T>::get() {
+                let _ = BalanceClaims::<T>::clear_prefix(&trader_id, T::MaxRelayers::get(), None);
+                Self::send_balance_query(&trader_id, &profile, balance)?;
+            }
+            Ok(())
+        }
+
+        /// Notified with the query response reporting the balance of the account the pool controls for the trader
+        #[pallet::call_index(34)]
+        #[pallet::weight(T::WeightInfo::balance_query_response())]
+        pub fn balance_query_response(
+            origin: OriginFor<T>,
+            query_id: QueryId,
+            response: Response,
+        ) -> DispatchResult {
+            let responder = T::XcmResponseOrigin::ensure_origin(origin)?;
+
+            let query =
+                BalanceQueries::<T>::take(query_id).ok_or(Error::<T>::BalanceQueryNotFound)?;
+            ensure!(
+                responder == query.responder,
+                Error::<T>::UnexpectedResponder
+            );
+            let profile = TraderProfiles::<T>::get(&query.trader_id)
+                .ok_or(Error::<T>::TraderNotRegistered)?;
+            let reported = T::RemoteBalanceQuerier::reported_balance(&profile.asset_id, &response);
+            // the allocation may have been settled or moved since the query was sent
+            if reported < query.balance
+                || profile.funds_allocated.is_zero()
+                || profile.network != Some(query.network)
+            {
+                Self::deposit_event(Event::TraderBalanceNotVerified {
+                    trader_id: query.trader_id,
+                    query_id,
+                    reported,
+                });
+                return Ok(());
+            }
+
+            Self::record_trader_balance(&query.trader_id, profile, reported)?;
+            Self::deposit_event(Event::TraderBalanceVerified {
+                trader_id: query.trader_id,
+                query_id,
+                balance: reported,
+            });
+
+            Ok(())
+        }
+
+        /// Trust a Phala worker or contract key to attest generated trading accounts.
+        /// `TraderRegistrarOrigin` may add up to `MaxRegistrarAttestors` trusted attestors
+        #[pallet::call_index(35)]
+        #[pallet::weight(T::WeightInfo::add_attestor())]
+        pub fn add_attestor(origin: OriginFor<T>, attestor: AttestorKey) -> DispatchResult {
+            let is_admin = Self::ensure_attestor_manager(origin)?;
+
+            ensure!(
+                !Attestors::<T>::contains_key(attestor),
+                Error::<T>::AttestorAlreadyRegistered
+            );
+            if !is_admin {
+                RegistrarAttestors::<T>::try_append(attestor)
+                    .map_err(|_| Error::<T>::TooManyRegistrarAttestors)?;
+            }
+            Attestors::<T>::insert(
+                attestor,
+                AttestorInfo::new(<frame_system::Pallet<T>>::block_number()),
+            );
+
+            Self::deposit_event(Event::AttestorAdded { attestor });
+            Ok(())
+        }
+
+        /// Replace an attestor key, e.g. after the worker or contract rotated its key.
+        /// Attestations of the old key are no longer accepted, the new key stays with whoever added the old one.
+        /// `TraderRegistrarOrigin` may only rotate the attestors it added
+        #[pallet::call_index(36)]
+        #[pallet::weight(T::WeightInfo::rotate_attestor())]
+        pub fn rotate_attestor(
+            origin: OriginFor<T>,
+            old: AttestorKey,
+            new: AttestorKey,
+        ) -> DispatchResult {
+            let is_admin = Self::ensure_attestor_manager(origin)?;
+
+            ensure!(
+                !Attestors::<T>::contains_key(new),
+                Error::<T>::AttestorAlreadyRegistered
+            );
+            RegistrarAttestors::<T>::try_mutate(|keys| -> DispatchResult {
+                match keys.iter_mut().find(|key| **key == old) {
+                    Some(key) => *key = new,
+                    None => ensure!(is_admin, Error::<T>::NotRegistrarAttestor),
+                }
+                Ok(())
+            })?;
+            let now = <frame_system::Pallet<T>>::block_number();
+            Attestors::<T>::try_mutate(old, |info| -> DispatchResult {
+                let info = info
+                    .as_mut()
+                    .filter(|info| info.is_trusted())
+                    .ok_or(Error::<T>::AttestorNotTrusted)?;
+                info.status = AttestorStatus::Rotated { new, at: now };
+                Ok(())
+            })?;
+            Attestors::<T>::insert(new, AttestorInfo::new(now));
+
+            Self::deposit_event(Event::AttestorRotated { old, new });
+            Ok(())
+        }
+
+        /// Stop trusting a compromised or retired attestor key. The key is kept as revoked and cannot be added again.
+        /// `TraderRegistrarOrigin` may only revoke the attestors it added
+        #[pallet::call_index(37)]
+        #[pallet::weight(T::WeightInfo::revoke_attestor())]
+        pub fn revoke_attestor(origin: OriginFor<T>, attestor: AttestorKey) -> DispatchResult {
+            let is_admin = Self::ensure_attestor_manager(origin)?;
+
+            RegistrarAttestors::<T>::try_mutate(|keys| -> DispatchResult {
+                let added = keys.len();
+                keys.retain(|key| *key != attestor);
+                ensure!(
+                    is_admin || keys.len() < added,
+                    Error::<T>::NotRegistrarAttestor
+                );
+                Ok(())
+            })?;
+
+            let now = <frame_system::Pallet<T>>::block_number();
+            Attestors::<T>::try_mutate(attestor, |info| -> DispatchResult {
+                let info = info.as_mut().ok_or(Error::<T>::AttestorNotTrusted)?;
+                info.status = AttestorStatus::Revoked { at: now };
+                Ok(())
+            })?;
+            Self::deposit_event(Event::AttestorRevoked { attestor });
+            Ok(())
+        }
+
+        /// Set the location of the sibling parachain contract, e.g. the Phala trader registration contract,
+        /// allowed to register traders through XCM `Transact`, `None` disables it
+        #[pallet::call_index(38)]
+        #[pallet::weight(T::WeightInfo::set_xcm_trader_registrar())]
+        pub fn set_xcm_trader_registrar(
+            origin: OriginFor<T>,
+            location: Option<MultiLocation>,
+        ) -> DispatchResult {
+            T::RelayerAdminOrigin::ensure_origin(origin)?;
+
+            XcmTraderRegistrar::<T>::set(location);
+            Self::deposit_event(Event::XcmTraderRegistrarSet { location });
+            Ok(())
+        }
+
+        /// Called by relayers with the roots of a finalized block of a target network.
+        /// Each call is one relayer attestation, the roots are trusted for trade proofs once
+        /// `RelayerThreshold` relayers attested the same roots for the block
+        #[pallet::call_index(39)]
+        #[pallet::weight(T::WeightInfo::attest_network_roots(T::MaxRelayers::get()))]
+        pub fn attest_network_roots(
+            origin: OriginFor<T>,
+            network: NetworkId,
+            block_number: BlockNumberFor<T>,
+            roots: FinalizedRoots,
+        ) -> DispatchResult {
+            let relayer_id = ensure_signed(origin)?;
+
+            let relayers = Relayers::<T>::get();
+            ensure!(
+                relayers.contains(&relayer_id),
+                Error::<T>::RelayerNotRegistered
+            );
+            ensure!(
+                TargetNetworks::<T>::contains_key(network),
+                Error::<T>::NetworkNotSupported
+            );
+            ensure!(
+                !FinalizedNetworkRoots::<T>::contains_key(network, block_number),
+                Error::<T>::RootsAlreadyFinalized
+            );
+
+            let block = (network, block_number);
+            let attested_hash = T::Hashing::hash_of(&roots);
+            // a relayer vouching for two different roots of the same block is equivocating
+            ensure!(
+                !NetworkRootAttestations::<T>::iter_prefix(block)
+                    .any(|(hash, attestations)| hash != attested_hash
+                        && attestations.contains(&relayer_id)),
+                Error::<T>::ConflictingAttestation
+            );
+
+            let attestations = NetworkRootAttestations::<T>::try_mutate(
+                block,
+                attested_hash,
+                |attestations| -> Result<u32, DispatchError> {
+                    ensure!(
+                        !attestations.contains(&relayer_id),
+                        Error::<T>::AlreadyAttested
+                    );
+                    attestations
+                        .try_push(relayer_id.clone())
+                        .map_err(|_| Error::<T>::TooManyRelayers)?;
+                    // attestations of relayers removed in the meantime no longer count
+                    Ok(attestations
+                        .iter()
+                        .filter(|relayer| relayers.contains(relayer))
+                        .count() as u32)
+                },
+            )?;
+
+            Self::deposit_event(Event::NetworkRootsAttested {
+                network,
+                block_number,
+                relayer: relayer_id,
+                attestations,
+            });
+
+            if attestations >= RelayerThreshold::<T>::get() {
+                let _ =
+                    NetworkRootAttestations::<T>::clear_prefix(block, T::MaxRelayers::get(), None);
+                FinalizedNetworkRoots::<T>::insert(network, block_number, roots.clone());
+                Self::deposit_event(Event::NetworkRootsFinalized {
+                    network,
+                    block_number,
+                    roots,
+                });
+            }
+            Ok(())
+        }
     }
 }
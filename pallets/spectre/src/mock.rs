@@ -0,0 +1,444 @@
+use crate as pallet_spectre;
+use crate::util::*;
+
+use {
+    frame_support::{
+        assert_ok, construct_runtime, ord_parameter_types, parameter_types,
+        traits::{
+            AsEnsureOriginWithArg, ConstBool, ConstU128, ConstU32, ConstU64, ConstU8, Everything,
+            Hooks, Nothing,
+        },
+        BoundedBTreeMap, BoundedVec,
+    },
+    frame_system::{EnsureRoot, EnsureRootWithSuccess, EnsureSignedBy, RawOrigin},
+    orml_traits::{asset_registry::AssetMetadata, parameter_type_with_key, MultiCurrency},
+    sp_arithmetic::Permill,
+    sp_core::{sr25519, Pair, H256},
+    sp_runtime::{
+        traits::{BlakeTwo256, IdentityLookup},
+        BuildStorage, DispatchError, DispatchResult,
+    },
+    staging_xcm::latest::{Fungibility, MultiAsset, MultiLocation, QueryId, Response},
+};
+
+pub type AccountId = u64;
+pub type Balance = u128;
+pub type AssetId = u32;
+pub type BlockNumber = u64;
+
+type Block = frame_system::mocking::MockBlock<Test>;
+
+pub const ALICE: AccountId = 1;
+pub const BOB: AccountId = 2;
+pub const TRADER: AccountId = 10;
+pub const RELAYER_1: AccountId = 20;
+pub const RELAYER_2: AccountId = 21;
+pub const RELAYER_3: AccountId = 22;
+pub const TREASURY: AccountId = 99;
+pub const REGISTRAR: AccountId = 30;
+
+pub const USDT: AssetId = 1;
+pub const SHARE_ASSET_OFFSET: AssetId = 1000;
+pub const USDT_SHARES: AssetId = USDT + SHARE_ASSET_OFFSET;
+pub const INITIAL_BALANCE: Balance = 10_000;
+
+pub const NETWORK: NetworkId = 1;
+pub const WITHDRAW_PERIOD: BlockNumber = 10;
+pub const EPOCH_LENGTH: BlockNumber = 100;
+pub const BALANCE_QUERY_INTERVAL: BlockNumber = 100;
+pub const REMOTE_SWAP_TIMEOUT: BlockNumber = 20;
+
+construct_runtime!(
+    pub enum Test {
+        System: frame_system,
+        Balances: pallet_balances,
+        Tokens: orml_tokens,
+        AssetRegistry: orml_asset_registry,
+        Spectre: pallet_spectre,
+    }
+);
+
+impl frame_system::Config for Test {
+    type BaseCallFilter = Everything;
+    type BlockWeights = ();
+    type BlockLength = ();
+    type DbWeight = ();
+    type RuntimeOrigin = RuntimeOrigin;
+    type RuntimeCall = RuntimeCall;
+    type Nonce = u64;
+    type Hash = H256;
+    type Hashing = BlakeTwo256;
+    type AccountId = AccountId;
+    type Lookup = IdentityLookup<Self::AccountId>;
+    type Block = Block;
+    type RuntimeEvent = RuntimeEvent;
+    type BlockHashCount = ConstU64<250>;
+    type Version = ();
+    type PalletInfo = PalletInfo;
+    type AccountData = pallet_balances::AccountData<Balance>;
+    type OnNewAccount = ();
+    type OnKilledAccount = ();
+    type SystemWeightInfo = ();
+    type SS58Prefix = ();
+    type OnSetCode = ();
+    type MaxConsumers = ConstU32<16>;
+    type RuntimeTask = RuntimeTask;
+}
+
+impl pallet_balances::Config for Test {
+    type MaxLocks = ConstU32<50>;
+    type Balance = Balance;
+    type RuntimeEvent = RuntimeEvent;
+    type DustRemoval = ();
+    type ExistentialDeposit = ConstU128<1>;
+    type AccountStore = System;
+    type MaxReserves = ConstU32<50>;
+    type ReserveIdentifier = [u8; 8];
+    type FreezeIdentifier = [u8; 8];
+    type MaxFreezes = ConstU32<0>;
+    type RuntimeHoldReason = RuntimeHoldReason;
+    type RuntimeFreezeReason = RuntimeFreezeReason;
+    type MaxHolds = ConstU32<0>;
+    type WeightInfo = ();
+}
+
+parameter_type_with_key! {
+    pub ExistentialDeposits: |_currency_id: AssetId| -> Balance {
+        0
+    };
+}
+
+impl orml_tokens::Config for Test {
+    type RuntimeEvent = RuntimeEvent;
+    type Balance = Balance;
+    type Amount = i128;
+    type CurrencyHooks = PoolShareHooks<Test>;
+    type CurrencyId = AssetId;
+    type DustRemovalWhitelist = Nothing;
+    type ExistentialDeposits = ExistentialDeposits;
+    type MaxLocks = ConstU32<0>;
+    type MaxReserves = ConstU32<3>;
+    type ReserveIdentifier = [u8; 8];
+    type WeightInfo = ();
+}
+
+impl orml_asset_registry::Config for Test {
+    type RuntimeEvent = RuntimeEvent;
+    type CustomMetadata = ();
+    type AssetId = AssetId;
+    type AuthorityOrigin = AsEnsureOriginWithArg<EnsureRoot<AccountId>>;
+    type AssetProcessor = orml_asset_registry::SequentialId<Test>;
+    type Balance = Balance;
+    type StringLimit = ConstU32<32>;
+    type WeightInfo = ();
+}
+
+/// Share tokens of the pools live `SHARE_ASSET_OFFSET` above the pool asset
+pub struct MockPoolShares;
+
+impl PoolShareAsset<Test> for MockPoolShares {
+    fn share_asset_id(asset_id: &AssetId) -> AssetId {
+        asset_id + SHARE_ASSET_OFFSET
+    }
+
+    fn share_metadata(_asset_id: &AssetId) -> Option<AssetMetadataOf<Test>> {
+        Some(AssetMetadata {
+            decimals: 6,
+            name: BoundedVec::truncate_from(b"Spectre Finance Tether".to_vec()),
+            symbol: BoundedVec::truncate_from(b"sfUSDT".to_vec()),
+            existential_deposit: 0,
+            location: None,
+            additional: (),
+        })
+    }
+}
+
+/// Moves the allocated capital from the pool account to the trader account, the trader sends it
+/// back to the pool account before the allocation is settled
+pub struct MockCapitalAllocator;
+
+impl CapitalAllocator<Test> for MockCapitalAllocator {
+    fn allocate_capital(
+        _network: NetworkId,
+        asset_id: AssetId,
+        amount: Balance,
+        trader_id: AccountId,
+        _onchain_trading_account: RemoteAccount,
+    ) -> DispatchResult {
+        Spectre::transfer_from_pool(
+            Spectre::generate_pool_account(Some(asset_id)),
+            trader_id,
+            asset_id,
+            amount,
+        )
+    }
+
+    fn recall_capital(
+        _network: NetworkId,
+        _asset_id: AssetId,
+        _amount: Balance,
+        _trader_id: AccountId,
+        _onchain_trading_account: RemoteAccount,
+    ) -> DispatchResult {
+        Ok(())
+    }
+}
+
+parameter_types! {
+    pub static NextQueryId: QueryId = 0;
+    pub static QueryResponder: MultiLocation = MultiLocation::parent();
+}
+
+/// Sends every swap, handing out the query ids of `MockBalanceQuerier` answered by `QueryResponder`
+pub struct MockSwapExecutor;
+
+impl RemoteSwapExecutor<Test> for MockSwapExecutor {
+    fn execute_swap(
+        _pool_account: &AccountId,
+        _trader_id: &AccountId,
+        _asset_id: &AssetId,
+        _network: &NetworkInfo,
+        _venue: &DexVenue,
+        _swap: &RemoteSwap,
+    ) -> Result<(QueryId, MultiLocation), DispatchError> {
+        let query_id = NextQueryId::get();
+        NextQueryId::set(query_id + 1);
+        Ok((query_id, QueryResponder::get()))
+    }
+}
+
+/// Hands out sequential query ids answered by `QueryResponder`, the reported balance is the sum of the fungible assets in the response
+pub struct MockBalanceQuerier;
+
+impl RemoteBalanceQuerier<Test> for MockBalanceQuerier {
+    fn query_balance(
+        _pool_account: &AccountId,
+        _trader_id: &AccountId,
+        _network: &NetworkInfo,
+        _asset_id: &AssetId,
+        _balance: Balance,
+    ) -> Result<(QueryId, MultiLocation), DispatchError> {
+        let query_id = NextQueryId::get();
+        NextQueryId::set(query_id + 1);
+        Ok((query_id, QueryResponder::get()))
+    }
+
+    fn reported_balance(_asset_id: &AssetId, response: &Response) -> Balance {
+        let Response::Assets(assets) = response else {
+            return 0;
+        };
+        assets
+            .inner()
+            .iter()
+            .map(|asset| match asset.fun {
+                Fungibility::Fungible(amount) => amount,
+                _ => 0,
+            })
+            .sum()
+    }
+}
+
+parameter_types! {
+    pub RiskViolationSlash: Permill = Permill::from_percent(10);
+    pub InsuranceFeeCut: Permill = Permill::from_percent(20);
+    pub InsuranceProfitCut: Permill = Permill::from_percent(2);
+    pub ResponderLocation: MultiLocation = MultiLocation::parent();
+}
+
+ord_parameter_types! {
+    pub const Registrar: AccountId = REGISTRAR;
+}
+
+impl pallet_spectre::Config for Test {
+    type RuntimeEvent = RuntimeEvent;
+    type NativeBalance = Balances;
+    type CapitalAllocator = MockCapitalAllocator;
+    type TradeExecutionVerifier = TradeExecutionVerifyV1;
+    type InvestorPoolOwnership = ConstU8<30>;
+    type TraderPoolOwnership = ConstU8<60>;
+    type WithdrawPeriod = ConstU64<WITHDRAW_PERIOD>;
+    type PoolAdminOrigin = EnsureRoot<AccountId>;
+    type RelayerAdminOrigin = EnsureRoot<AccountId>;
+    type MaxRelayers = ConstU32<4>;
+    type AttestorAdminOrigin = EnsureRoot<AccountId>;
+    type TraderRegistrarOrigin = EnsureSignedBy<Registrar, AccountId>;
+    type MaxRegistrarAttestors = ConstU32<2>;
+    type TraderAdminOrigin = EnsureRoot<AccountId>;
+    type AutoApproveTraders = ConstBool<false>;
+    type RegistryAdminOrigin = EnsureRoot<AccountId>;
+    type TradeDecoder = ();
+    type MaxRiskViolations = ConstU8<3>;
+    type RiskViolationSlash = RiskViolationSlash;
+    type MaxReportAge = ConstU64<1_000>;
+    type MaxDrawdownChecks = ConstU32<10>;
+    type BalanceQueryInterval = ConstU64<BALANCE_QUERY_INTERVAL>;
+    type MaxBalanceQueryChecks = ConstU32<10>;
+    type TreasuryAccount = ConstU64<TREASURY>;
+    type InsuranceFeeCut = InsuranceFeeCut;
+    type InsuranceProfitCut = InsuranceProfitCut;
+    type InsuranceClaimOrigin = EnsureRoot<AccountId>;
+    type EpochLength = ConstU64<EPOCH_LENGTH>;
+    type MaxEpochSteps = ConstU32<10>;
+    type MaxRedemptionRequests = ConstU32<8>;
+    type DepositFilter = ();
+    type MaxInvestorPools = ConstU32<2>;
+    type PoolShares = MockPoolShares;
+    type RemoteSwapExecutor = MockSwapExecutor;
+    type RemoteBalanceQuerier = MockBalanceQuerier;
+    type XcmResponseOrigin = EnsureRootWithSuccess<AccountId, ResponderLocation>;
+    type RemoteSwapTimeout = ConstU64<REMOTE_SWAP_TIMEOUT>;
+    type WeightInfo = ();
+}
+
+pub struct ExtBuilder {
+    balances: Vec<(AccountId, AssetId, Balance)>,
+}
+
+impl Default for ExtBuilder {
+    fn default() -> Self {
+        Self {
+            balances: [ALICE, BOB, TRADER]
+                .into_iter()
+                .map(|account| (account, USDT, INITIAL_BALANCE))
+                .collect(),
+        }
+    }
+}
+
+impl ExtBuilder {
+    pub fn build(self) -> sp_io::TestExternalities {
+        let mut storage = frame_system::GenesisConfig::<Test>::default()
+            .build_storage()
+            .unwrap();
+
+        orml_tokens::GenesisConfig::<Test> {
+            balances: self.balances,
+        }
+        .assimilate_storage(&mut storage)
+        .unwrap();
+
+        pallet_spectre::GenesisConfig::<Test> {
+            relayers: vec![RELAYER_1, RELAYER_2, RELAYER_3],
+            relayer_threshold: 2,
+            supported_assets: vec![USDT],
+            initial_capital: 0,
+            fee: 0,
+        }
+        .assimilate_storage(&mut storage)
+        .unwrap();
+
+        let mut ext = sp_io::TestExternalities::new(storage);
+        ext.execute_with(|| System::set_block_number(1));
+        ext
+    }
+}
+
+pub fn run_to_block(n: BlockNumber) {
+    while System::block_number() < n {
+        System::set_block_number(System::block_number() + 1);
+        Spectre::on_initialize(System::block_number());
+    }
+}
+
+pub fn free_balance(asset_id: AssetId, account: &AccountId) -> Balance {
+    <Tokens as MultiCurrency<AccountId>>::free_balance(asset_id, account)
+}
+
+pub fn pool_account(asset_id: AssetId) -> AccountId {
+    Spectre::generate_pool_account(Some(asset_id))
+}
+
+pub fn attestor() -> sr25519::Pair {
+    sr25519::Pair::from_seed(&[7u8; 32])
+}
+
+pub fn trading_accounts() -> TradingAccounts {
+    TradingAccounts(
+        BoundedBTreeMap::try_from(
+            [(Networks::Substrate, RemoteAccount::AccountId32([5u8; 32]))]
+                .into_iter()
+                .collect::<std::collections::BTreeMap<_, _>>(),
+        )
+        .unwrap(),
+    )
+}
+
+pub fn attest(
+    trader_id: AccountId,
+    accounts: &TradingAccounts,
+    nonce: u64,
+) -> TradingAccountsAttestation {
+    let pair = attestor();
+    TradingAccountsAttestation {
+        attestor: pair.public(),
+        nonce,
+        signature: pair.sign(&attestation_payload(&trader_id, accounts, nonce)),
+    }
+}
+
+// Reported holding of a balance query response
+pub fn balance_report(amount: Balance) -> Response {
+    Response::Assets(MultiAsset::from((MultiLocation::parent(), amount)).into())
+}
+
+pub fn substrate_network() -> NetworkInfo {
+    NetworkInfo {
+        kind: Networks::Substrate,
+        chain_id: 2034,
+        hasher: HashAlgorithm::Blake2,
+        proof_kind: ProofKind::SubstrateTrie,
+        finality: FinalitySource::RelayChain,
+        enabled: true,
+    }
+}
+
+// Simulate the pool gaining `gain` or losing `loss` on the capital held by the pool account
+pub fn mark_pool_value(asset_id: AssetId, gain: Balance, loss: Balance) {
+    let pool_account = pool_account(asset_id);
+    assert_ok!(<Tokens as MultiCurrency<AccountId>>::deposit(
+        asset_id,
+        &pool_account,
+        gain
+    ));
+    assert_ok!(<Tokens as MultiCurrency<AccountId>>::withdraw(
+        asset_id,
+        &pool_account,
+        loss
+    ));
+    pallet_spectre::CapitalPool::<Test>::mutate(asset_id, |pool| {
+        pool.remaining_capital = pool.remaining_capital + gain - loss;
+        pool.unrealized_balance = pool.unrealized_balance + gain - loss;
+    });
+}
+
+// Apply to the USDT pool with `bond`, register the attested trading accounts through two relayers
+// and approve the trader on the substrate target network
+pub fn register_active_trader(trader_id: AccountId, bond: Balance) {
+    assert_ok!(Spectre::set_target_network(
+        RawOrigin::Root.into(),
+        NETWORK,
+        substrate_network()
+    ));
+    if pallet_spectre::Attestors::<Test>::get(attestor().public()).is_none() {
+        assert_ok!(Spectre::add_attestor(
+            RawOrigin::Root.into(),
+            attestor().public()
+        ));
+    }
+    assert_ok!(Spectre::apply_as_trader(
+        RuntimeOrigin::signed(trader_id),
+        USDT,
+        bond
+    ));
+    let accounts = trading_accounts();
+    let attestation = attest(trader_id, &accounts, 1);
+    for relayer in [RELAYER_1, RELAYER_2] {
+        assert_ok!(Spectre::register_trader(
+            RuntimeOrigin::signed(relayer),
+            trader_id,
+            accounts.clone(),
+            attestation.clone(),
+        ));
+    }
+    assert_ok!(Spectre::approve_trader(RawOrigin::Root.into(), trader_id));
+}
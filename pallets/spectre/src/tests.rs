@@ -1 +1,1929 @@
-// Test for
\ No newline at end of file
+use crate::{mock::*, util::*, *};
+
+use {
+    frame_support::{
+        assert_noop, assert_ok,
+        storage::migration,
+        traits::{GetStorageVersion, Hooks, PalletInfoAccess, StorageVersion},
+    },
+    frame_system::RawOrigin,
+    orml_traits::{MultiCurrency, MultiReservableCurrency},
+    parity_scale_codec::{Compact, Encode},
+    sp_arithmetic::{Permill, Rounding},
+    sp_runtime::{traits::BlakeTwo256, BoundedVec, DispatchError, DispatchResult},
+    sp_trie::{generate_trie_proof, trie_types::TrieDBMutBuilderV1, LayoutV1, MemoryDB, TrieMut},
+    staging_xcm::latest::{MaybeErrorCode, MultiLocation, Response},
+};
+
+fn deposit(investor: AccountId, amount: Balance) {
+    assert_ok!(Spectre::register_investor(
+        RuntimeOrigin::signed(investor),
+        USDT,
+        amount
+    ));
+}
+
+fn shares_of(investor: &AccountId) -> Balance {
+    free_balance(USDT_SHARES, investor)
+}
+
+fn deposited(investor: AccountId) -> Balance {
+    InvestorPositions::<Test>::get(investor, USDT)
+        .map(|position| position.deposited)
+        .unwrap_or_default()
+}
+
+fn set_fee(fee: Permill) {
+    assert_ok!(Spectre::set_pool_params(
+        RawOrigin::Root.into(),
+        USDT,
+        PoolParams {
+            fee,
+            ..Default::default()
+        }
+    ));
+}
+
+// Trader returns `returned` of its allocation to the pool account and the relayers settle it
+fn return_and_settle(trader_id: AccountId, returned: Balance) {
+    let pool_account = pool_account(USDT);
+    let held = free_balance(USDT, &trader_id);
+    if returned > held {
+        assert_ok!(<Tokens as MultiCurrency<AccountId>>::deposit(
+            USDT,
+            &trader_id,
+            returned - held
+        ));
+    }
+    assert_ok!(Tokens::transfer(
+        RuntimeOrigin::signed(trader_id),
+        pool_account,
+        USDT,
+        returned
+    ));
+    for relayer in [RELAYER_1, RELAYER_2] {
+        assert_ok!(Spectre::settle_allocation(
+            RuntimeOrigin::signed(relayer),
+            trader_id,
+            returned
+        ));
+    }
+}
+
+#[test]
+fn deposit_mints_shares_one_to_one_in_new_pool() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 1_000);
+
+        assert_eq!(shares_of(&ALICE), 1_000);
+        assert_eq!(deposited(ALICE), 1_000);
+        assert_eq!(free_balance(USDT, &pool_account(USDT)), 1_000);
+        let pool = CapitalPool::<Test>::get(USDT);
+        assert_eq!(pool.total_capital, 1_000);
+        assert_eq!(pool.unrealized_balance, 1_000);
+        System::assert_last_event(
+            Event::CapitalDeposited {
+                investor_id: ALICE,
+                asset_id: USDT,
+                amount: 1_000,
+                shares: 1_000,
+            }
+            .into(),
+        );
+    });
+}
+
+#[test]
+fn deposit_after_pool_gain_redeems_for_amount_paid_in() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 1_000);
+        mark_pool_value(USDT, 500, 0);
+
+        // 300 at 1.5 per share
+        deposit(BOB, 300);
+        assert_eq!(shares_of(&BOB), 200);
+        assert_eq!(deposited(BOB), 200);
+        let pool = CapitalPool::<Test>::get(USDT);
+        assert_eq!(pool.total_capital, 1_200);
+        assert_eq!(pool.unrealized_balance, 1_800);
+
+        run_to_block(1 + WITHDRAW_PERIOD);
+        assert_ok!(Spectre::withdraw_capital(
+            RuntimeOrigin::signed(BOB),
+            USDT,
+            200
+        ));
+        assert_eq!(free_balance(USDT, &BOB), INITIAL_BALANCE);
+        assert_eq!(shares_of(&BOB), 0);
+
+        // the gain stays with the investor who was in the pool when it happened
+        assert_ok!(Spectre::withdraw_capital(
+            RuntimeOrigin::signed(ALICE),
+            USDT,
+            1_000
+        ));
+        assert_eq!(free_balance(USDT, &ALICE), INITIAL_BALANCE + 500);
+    });
+}
+
+#[test]
+fn deposit_after_pool_loss_redeems_for_amount_paid_in() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 1_000);
+        mark_pool_value(USDT, 0, 500);
+
+        // 300 at 0.5 per share
+        deposit(BOB, 300);
+        assert_eq!(shares_of(&BOB), 600);
+        assert_eq!(CapitalPool::<Test>::get(USDT).total_capital, 1_600);
+
+        run_to_block(1 + WITHDRAW_PERIOD);
+        assert_ok!(Spectre::withdraw_capital(
+            RuntimeOrigin::signed(BOB),
+            USDT,
+            600
+        ));
+        assert_eq!(free_balance(USDT, &BOB), INITIAL_BALANCE);
+
+        assert_ok!(Spectre::withdraw_capital(
+            RuntimeOrigin::signed(ALICE),
+            USDT,
+            1_000
+        ));
+        assert_eq!(free_balance(USDT, &ALICE), INITIAL_BALANCE - 500);
+    });
+}
+
+#[test]
+fn deposit_rounds_shares_down() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 100);
+        mark_pool_value(USDT, 200, 0);
+
+        // a share is worth 3, 2 cannot buy one
+        assert_noop!(
+            Spectre::register_investor(RuntimeOrigin::signed(BOB), USDT, 2),
+            Error::<Test>::InsufficientDeposit
+        );
+
+        deposit(BOB, 10);
+        assert_eq!(shares_of(&BOB), 3);
+        // rounding favours the investors already in the pool
+        assert!(CapitalPool::<Test>::get(USDT).redemption_value(3) <= 10);
+    });
+}
+
+#[test]
+fn deposit_in_depleted_pool_fails() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 1_000);
+        mark_pool_value(USDT, 0, 1_000);
+
+        assert_noop!(
+            Spectre::register_investor(RuntimeOrigin::signed(BOB), USDT, 100),
+            Error::<Test>::PoolValueDepleted
+        );
+    });
+}
+
+#[test]
+fn xcm_deposit_emits_its_outcome() {
+    ExtBuilder::default().build().execute_with(|| {
+        Spectre::deposit_xcm_capital(ALICE, USDT, 1_000);
+        assert_eq!(shares_of(&ALICE), 1_000);
+        System::assert_last_event(
+            Event::XcmDepositAccepted {
+                investor_id: ALICE,
+                asset_id: USDT,
+                amount: 1_000,
+            }
+            .into(),
+        );
+
+        // the refused capital stays with the investor
+        Spectre::deposit_xcm_capital(BOB, USDT, 0);
+        assert_eq!(free_balance(USDT, &BOB), INITIAL_BALANCE);
+        assert_eq!(shares_of(&BOB), 0);
+        System::assert_last_event(
+            Event::XcmDepositFailed {
+                investor_id: BOB,
+                asset_id: USDT,
+                amount: 0,
+                error: Error::<Test>::InsufficientDeposit.into(),
+            }
+            .into(),
+        );
+    });
+}
+
+#[test]
+fn deposit_caps_are_enforced() {
+    ExtBuilder::default().build().execute_with(|| {
+        assert_ok!(Spectre::set_pool_params(
+            RawOrigin::Root.into(),
+            USDT,
+            PoolParams {
+                max_capital: Some(1_500),
+                min_deposit: 50,
+                max_investor_capital: Some(1_000),
+                ..Default::default()
+            }
+        ));
+
+        assert_noop!(
+            Spectre::register_investor(RuntimeOrigin::signed(ALICE), USDT, 49),
+            Error::<Test>::InsufficientDeposit
+        );
+        assert_noop!(
+            Spectre::register_investor(RuntimeOrigin::signed(ALICE), USDT, 1_001),
+            Error::<Test>::InvestorCapExceeded
+        );
+        deposit(ALICE, 1_000);
+        assert_noop!(
+            Spectre::register_investor(RuntimeOrigin::signed(BOB), USDT, 501),
+            Error::<Test>::PoolCapExceeded
+        );
+        deposit(BOB, 500);
+    });
+}
+
+#[test]
+fn deposit_caps_follow_the_pool_value() {
+    ExtBuilder::default().build().execute_with(|| {
+        assert_ok!(Spectre::set_pool_params(
+            RawOrigin::Root.into(),
+            USDT,
+            PoolParams {
+                max_capital: Some(2_500),
+                max_investor_capital: Some(1_500),
+                ..Default::default()
+            }
+        ));
+        deposit(ALICE, 1_000);
+        // the 1_000 shares of alice are now worth 2_000
+        mark_pool_value(USDT, 1_000, 0);
+
+        assert_noop!(
+            Spectre::register_investor(RuntimeOrigin::signed(ALICE), USDT, 100),
+            Error::<Test>::InvestorCapExceeded
+        );
+        assert_noop!(
+            Spectre::register_investor(RuntimeOrigin::signed(BOB), USDT, 501),
+            Error::<Test>::PoolCapExceeded
+        );
+        deposit(BOB, 500);
+        assert_eq!(shares_of(&BOB), 250);
+    });
+}
+
+#[test]
+fn withdraw_requires_withdraw_period() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 1_000);
+
+        assert_noop!(
+            Spectre::withdraw_capital(RuntimeOrigin::signed(ALICE), USDT, 1_000),
+            Error::<Test>::WithdrawPeriodNotElapsed
+        );
+        run_to_block(1 + WITHDRAW_PERIOD);
+        assert_ok!(Spectre::withdraw_capital(
+            RuntimeOrigin::signed(ALICE),
+            USDT,
+            1_000
+        ));
+        assert!(InvestorPositions::<Test>::get(ALICE, USDT).is_none());
+    });
+}
+
+#[test]
+fn top_up_is_locked_for_the_withdraw_period() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 1_000);
+        run_to_block(WITHDRAW_PERIOD);
+        deposit(ALICE, 500);
+
+        run_to_block(1 + WITHDRAW_PERIOD);
+        assert_noop!(
+            Spectre::withdraw_capital(RuntimeOrigin::signed(ALICE), USDT, 1_500),
+            Error::<Test>::WithdrawPeriodNotElapsed
+        );
+        assert_noop!(
+            Spectre::request_redemption(RuntimeOrigin::signed(ALICE), USDT, 1_001),
+            Error::<Test>::WithdrawPeriodNotElapsed
+        );
+        assert_ok!(Spectre::withdraw_capital(
+            RuntimeOrigin::signed(ALICE),
+            USDT,
+            1_000
+        ));
+        run_to_block(2 * WITHDRAW_PERIOD);
+        assert_ok!(Spectre::withdraw_capital(
+            RuntimeOrigin::signed(ALICE),
+            USDT,
+            500
+        ));
+    });
+}
+
+#[test]
+fn pool_share_transfer_keeps_the_deposit_locks() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 1_000);
+        run_to_block(1 + WITHDRAW_PERIOD);
+        deposit(ALICE, 500);
+
+        // the unlocked shares move first
+        assert_ok!(Tokens::transfer(
+            RuntimeOrigin::signed(ALICE),
+            BOB,
+            USDT_SHARES,
+            1_200
+        ));
+        assert_noop!(
+            Spectre::withdraw_capital(RuntimeOrigin::signed(BOB), USDT, 1_001),
+            Error::<Test>::WithdrawPeriodNotElapsed
+        );
+        assert_noop!(
+            Spectre::withdraw_capital(RuntimeOrigin::signed(ALICE), USDT, 1),
+            Error::<Test>::WithdrawPeriodNotElapsed
+        );
+        assert_ok!(Spectre::withdraw_capital(
+            RuntimeOrigin::signed(BOB),
+            USDT,
+            1_000
+        ));
+
+        run_to_block(1 + 2 * WITHDRAW_PERIOD);
+        assert_ok!(Spectre::withdraw_capital(
+            RuntimeOrigin::signed(BOB),
+            USDT,
+            200
+        ));
+        assert_ok!(Spectre::withdraw_capital(
+            RuntimeOrigin::signed(ALICE),
+            USDT,
+            300
+        ));
+    });
+}
+
+#[test]
+fn runtime_upgrade_removes_investor_profiles() {
+    ExtBuilder::default().build().execute_with(|| {
+        StorageVersion::new(0).put::<Spectre>();
+        let pallet = <Spectre as PalletInfoAccess>::name().as_bytes();
+        migration::put_storage_value(pallet, b"InvestorProfiles", &ALICE.encode(), 1u32);
+        migration::put_storage_value(pallet, b"CounterForInvestorProfiles", b"", 1u32);
+
+        Spectre::on_runtime_upgrade();
+        assert!(!migration::have_storage_value(
+            pallet,
+            b"InvestorProfiles",
+            &ALICE.encode()
+        ));
+        assert!(!migration::have_storage_value(
+            pallet,
+            b"CounterForInvestorProfiles",
+            b""
+        ));
+        assert_eq!(Spectre::on_chain_storage_version(), 1);
+    });
+}
+
+#[test]
+fn pool_share_transfer_moves_position() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 1_000);
+
+        assert_ok!(Tokens::transfer(
+            RuntimeOrigin::signed(ALICE),
+            BOB,
+            USDT_SHARES,
+            400
+        ));
+        assert_eq!(deposited(ALICE), 600);
+        assert_eq!(deposited(BOB), 400);
+    });
+}
+
+#[test]
+fn pool_lifecycle_works() {
+    ExtBuilder::default().build().execute_with(|| {
+        assert_noop!(
+            Spectre::create_pool(RawOrigin::Root.into(), USDT, PoolParams::default()),
+            Error::<Test>::PoolAlreadyExists
+        );
+        assert_noop!(
+            Spectre::pause_pool(RuntimeOrigin::signed(ALICE), USDT),
+            DispatchError::BadOrigin
+        );
+
+        assert_ok!(Spectre::pause_pool(RawOrigin::Root.into(), USDT));
+        assert_noop!(
+            Spectre::register_investor(RuntimeOrigin::signed(ALICE), USDT, 100),
+            Error::<Test>::PoolPaused
+        );
+        assert_ok!(Spectre::resume_pool(RawOrigin::Root.into(), USDT));
+        deposit(ALICE, 100);
+
+        // without allocations the pool closes at once and can be redeemed right away
+        assert_ok!(Spectre::close_pool(RawOrigin::Root.into(), USDT));
+        assert_eq!(CapitalPool::<Test>::get(USDT).status, PoolStatus::Closed);
+        assert_noop!(
+            Spectre::register_investor(RuntimeOrigin::signed(BOB), USDT, 100),
+            Error::<Test>::PoolClosed
+        );
+        assert_ok!(Spectre::withdraw_capital(
+            RuntimeOrigin::signed(ALICE),
+            USDT,
+            100
+        ));
+    });
+}
+
+#[test]
+fn relayer_set_management_works() {
+    ExtBuilder::default().build().execute_with(|| {
+        assert_ok!(Spectre::add_relayer(RawOrigin::Root.into(), 23));
+        assert_noop!(
+            Spectre::add_relayer(RawOrigin::Root.into(), 23),
+            Error::<Test>::RelayerAlreadyRegistered
+        );
+        assert_noop!(
+            Spectre::add_relayer(RawOrigin::Root.into(), 24),
+            Error::<Test>::TooManyRelayers
+        );
+        assert_noop!(
+            Spectre::set_relayer_threshold(RawOrigin::Root.into(), 5),
+            Error::<Test>::InvalidRelayerThreshold
+        );
+        assert_ok!(Spectre::set_relayer_threshold(RawOrigin::Root.into(), 4));
+
+        // a relayer rotates its own key
+        assert_ok!(Spectre::rotate_relayer(RuntimeOrigin::signed(23), 23, 30));
+        assert!(Relayers::<Test>::get().contains(&30));
+
+        // the threshold follows the shrinking relayer set
+        assert_ok!(Spectre::remove_relayer(RawOrigin::Root.into(), 30));
+        assert_eq!(RelayerThreshold::<Test>::get(), 3);
+    });
+}
+
+#[test]
+fn reported_relayer_is_removed() {
+    ExtBuilder::default().build().execute_with(|| {
+        assert_noop!(
+            Spectre::report_relayer(RuntimeOrigin::signed(RELAYER_1), RELAYER_1),
+            Error::<Test>::CannotReportSelf
+        );
+        assert_ok!(Spectre::report_relayer(
+            RuntimeOrigin::signed(RELAYER_1),
+            RELAYER_3
+        ));
+        assert_noop!(
+            Spectre::report_relayer(RuntimeOrigin::signed(RELAYER_1), RELAYER_3),
+            Error::<Test>::AlreadyReported
+        );
+        assert!(Relayers::<Test>::get().contains(&RELAYER_3));
+
+        assert_ok!(Spectre::report_relayer(
+            RuntimeOrigin::signed(RELAYER_2),
+            RELAYER_3
+        ));
+        assert!(!Relayers::<Test>::get().contains(&RELAYER_3));
+    });
+}
+
+#[test]
+fn trader_registration_needs_threshold_attestations() {
+    ExtBuilder::default().build().execute_with(|| {
+        assert_ok!(Spectre::add_attestor(
+            RawOrigin::Root.into(),
+            attestor().public()
+        ));
+        assert_ok!(Spectre::apply_as_trader(
+            RuntimeOrigin::signed(TRADER),
+            USDT,
+            100
+        ));
+        assert_eq!(
+            <Tokens as MultiReservableCurrency<AccountId>>::reserved_balance(USDT, &TRADER),
+            100
+        );
+
+        let accounts = trading_accounts();
+        let attestation = attest(TRADER, &accounts, 1);
+        assert_noop!(
+            Spectre::register_trader(
+                RuntimeOrigin::signed(ALICE),
+                TRADER,
+                accounts.clone(),
+                attestation.clone()
+            ),
+            Error::<Test>::RelayerNotRegistered
+        );
+
+        assert_ok!(Spectre::register_trader(
+            RuntimeOrigin::signed(RELAYER_1),
+            TRADER,
+            accounts.clone(),
+            attestation.clone()
+        ));
+        assert_noop!(
+            Spectre::register_trader(
+                RuntimeOrigin::signed(RELAYER_1),
+                TRADER,
+                accounts.clone(),
+                attestation.clone()
+            ),
+            Error::<Test>::AlreadyAttested
+        );
+        assert!(OnChainTradingAccounts::<Test>::get(TRADER).is_none());
+
+        assert_ok!(Spectre::register_trader(
+            RuntimeOrigin::signed(RELAYER_2),
+            TRADER,
+            accounts.clone(),
+            attestation
+        ));
+        assert_eq!(OnChainTradingAccounts::<Test>::get(TRADER), Some(accounts));
+        assert_eq!(
+            TraderProfiles::<Test>::get(TRADER).unwrap().status,
+            TraderStatus::AccountsConfirmed
+        );
+        assert_eq!(TraderAttestationNonces::<Test>::get(TRADER), 1);
+    });
+}
+
+#[test]
+fn trader_registration_checks_attestation() {
+    ExtBuilder::default().build().execute_with(|| {
+        assert_ok!(Spectre::apply_as_trader(
+            RuntimeOrigin::signed(TRADER),
+            USDT,
+            100
+        ));
+        let accounts = trading_accounts();
+        let attestation = attest(TRADER, &accounts, 1);
+        assert_noop!(
+            Spectre::register_trader(
+                RuntimeOrigin::signed(RELAYER_1),
+                TRADER,
+                accounts.clone(),
+                attestation.clone()
+            ),
+            Error::<Test>::AttestorNotTrusted
+        );
+
+        assert_ok!(Spectre::add_attestor(
+            RawOrigin::Root.into(),
+            attestor().public()
+        ));
+        // signed for another trader
+        assert_noop!(
+            Spectre::register_trader(
+                RuntimeOrigin::signed(RELAYER_1),
+                TRADER,
+                accounts.clone(),
+                attest(BOB, &accounts, 1)
+            ),
+            Error::<Test>::InvalidAttestation
+        );
+
+        assert_ok!(Spectre::revoke_attestor(
+            RawOrigin::Root.into(),
+            attestor().public()
+        ));
+        assert_noop!(
+            Spectre::register_trader(
+                RuntimeOrigin::signed(RELAYER_1),
+                TRADER,
+                accounts,
+                attestation
+            ),
+            Error::<Test>::AttestorNotTrusted
+        );
+        assert_noop!(
+            Spectre::add_attestor(RawOrigin::Root.into(), attestor().public()),
+            Error::<Test>::AttestorAlreadyRegistered
+        );
+    });
+}
+
+#[test]
+fn registrar_manages_only_its_own_attestors() {
+    ExtBuilder::default().build().execute_with(|| {
+        let key = |seed: u8| AttestorKey::from_raw([seed; 32]);
+        assert_ok!(Spectre::add_attestor(RawOrigin::Root.into(), key(1)));
+        assert_noop!(
+            Spectre::add_attestor(RuntimeOrigin::signed(RELAYER_1), key(2)),
+            DispatchError::BadOrigin
+        );
+
+        assert_ok!(Spectre::add_attestor(
+            RuntimeOrigin::signed(REGISTRAR),
+            key(2)
+        ));
+        assert_ok!(Spectre::add_attestor(
+            RuntimeOrigin::signed(REGISTRAR),
+            key(3)
+        ));
+        assert_noop!(
+            Spectre::add_attestor(RuntimeOrigin::signed(REGISTRAR), key(4)),
+            Error::<Test>::TooManyRegistrarAttestors
+        );
+
+        // the attestor added by root is out of reach of the registrar
+        assert_noop!(
+            Spectre::revoke_attestor(RuntimeOrigin::signed(REGISTRAR), key(1)),
+            Error::<Test>::NotRegistrarAttestor
+        );
+        assert_noop!(
+            Spectre::rotate_attestor(RuntimeOrigin::signed(REGISTRAR), key(1), key(4)),
+            Error::<Test>::NotRegistrarAttestor
+        );
+
+        assert_ok!(Spectre::rotate_attestor(
+            RuntimeOrigin::signed(REGISTRAR),
+            key(2),
+            key(4)
+        ));
+        assert_ok!(Spectre::revoke_attestor(
+            RuntimeOrigin::signed(REGISTRAR),
+            key(4)
+        ));
+        assert!(!Attestors::<Test>::get(key(4)).unwrap().is_trusted());
+        // root keeps control over every attestor, a revoked key frees a registrar slot
+        assert_ok!(Spectre::revoke_attestor(RawOrigin::Root.into(), key(3)));
+        assert!(RegistrarAttestors::<Test>::get().is_empty());
+        assert_ok!(Spectre::add_attestor(
+            RuntimeOrigin::signed(REGISTRAR),
+            key(5)
+        ));
+        assert_eq!(RegistrarAttestors::<Test>::get().into_inner(), vec![key(5)]);
+    });
+}
+
+#[test]
+fn trader_lifecycle_works() {
+    ExtBuilder::default().build().execute_with(|| {
+        register_active_trader(TRADER, 100);
+        assert_eq!(
+            TraderProfiles::<Test>::get(TRADER).unwrap().status,
+            TraderStatus::Active
+        );
+
+        assert_ok!(Spectre::suspend_trader(RawOrigin::Root.into(), TRADER));
+        assert_noop!(
+            Spectre::allocate_capital(RuntimeOrigin::signed(TRADER), NETWORK, 100),
+            Error::<Test>::TraderNotActive
+        );
+        assert_ok!(Spectre::reinstate_trader(RawOrigin::Root.into(), TRADER));
+
+        // without open allocation the bond is released right away
+        assert_ok!(Spectre::deregister_trader(RuntimeOrigin::signed(TRADER)));
+        assert!(TraderProfiles::<Test>::get(TRADER).is_none());
+        assert!(OnChainTradingAccounts::<Test>::get(TRADER).is_none());
+        assert_eq!(free_balance(USDT, &TRADER), INITIAL_BALANCE);
+    });
+}
+
+#[test]
+fn allocate_capital_respects_pool_limits() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 1_000);
+        register_active_trader(TRADER, 100);
+        assert_ok!(Spectre::set_pool_params(
+            RawOrigin::Root.into(),
+            USDT,
+            PoolParams {
+                max_trader_allocation: Some(600),
+                ..Default::default()
+            }
+        ));
+
+        assert_noop!(
+            Spectre::allocate_capital(RuntimeOrigin::signed(TRADER), NETWORK, 601),
+            Error::<Test>::TraderAllocationExceeded
+        );
+        assert_ok!(Spectre::allocate_capital(
+            RuntimeOrigin::signed(TRADER),
+            NETWORK,
+            500
+        ));
+
+        let pool = CapitalPool::<Test>::get(USDT);
+        assert_eq!(pool.remaining_capital, 500);
+        assert_eq!(pool.total_allocated_capital, 500);
+        assert_eq!(pool.unrealized_balance, 1_000);
+        assert_eq!(free_balance(USDT, &pool_account(USDT)), 500);
+        let profile = TraderProfiles::<Test>::get(TRADER).unwrap();
+        assert_eq!(profile.funds_allocated, 500);
+        assert_eq!(profile.network, Some(NETWORK));
+    });
+}
+
+#[test]
+fn settlement_with_profit_collects_fees() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 1_000);
+        set_fee(Permill::from_percent(10));
+        register_active_trader(TRADER, 100);
+        assert_ok!(Spectre::allocate_capital(
+            RuntimeOrigin::signed(TRADER),
+            NETWORK,
+            500
+        ));
+
+        return_and_settle(TRADER, 600);
+
+        // protocol fee 10, 2 of it and 1 of the remaining profit to the insurance fund
+        assert_eq!(free_balance(USDT, &TREASURY), 8);
+        assert_eq!(Spectre::insurance_fund_balance(USDT), 3);
+        let pool = CapitalPool::<Test>::get(USDT);
+        assert_eq!(pool.remaining_capital, 1_089);
+        assert_eq!(pool.unrealized_balance, 1_089);
+        assert_eq!(pool.total_allocated_capital, 0);
+        assert_eq!(pool.epoch_profit, 89);
+        let profile = TraderProfiles::<Test>::get(TRADER).unwrap();
+        assert_eq!(profile.funds_allocated, 0);
+        assert_eq!(profile.network, None);
+    });
+}
+
+#[test]
+fn settlement_with_loss_runs_loss_waterfall() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 1_000);
+        register_active_trader(TRADER, 100);
+        assert_ok!(<Tokens as MultiCurrency<AccountId>>::deposit(
+            USDT,
+            &Spectre::generate_insurance_account(USDT),
+            50
+        ));
+        assert_ok!(Spectre::allocate_capital(
+            RuntimeOrigin::signed(TRADER),
+            NETWORK,
+            500
+        ));
+
+        return_and_settle(TRADER, 300);
+
+        System::assert_has_event(
+            Event::LossCoveredByBond {
+                trader_id: TRADER,
+                asset_id: USDT,
+                amount: 100,
+            }
+            .into(),
+        );
+        System::assert_has_event(
+            Event::LossCoveredByInsurance {
+                asset_id: USDT,
+                amount: 50,
+            }
+            .into(),
+        );
+        System::assert_has_event(
+            Event::LossAbsorbedByInvestors {
+                asset_id: USDT,
+                amount: 50,
+            }
+            .into(),
+        );
+        let pool = CapitalPool::<Test>::get(USDT);
+        assert_eq!(pool.unrealized_balance, 950);
+        assert_eq!(pool.remaining_capital, 950);
+        assert_eq!(free_balance(USDT, &pool_account(USDT)), 950);
+        assert_eq!(
+            TraderProfiles::<Test>::get(TRADER)
+                .unwrap()
+                .bonded_amount
+                .amount,
+            0
+        );
+        assert_eq!(Spectre::insurance_fund_balance(USDT), 0);
+    });
+}
+
+#[test]
+fn settlement_requires_returned_capital() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 1_000);
+        register_active_trader(TRADER, 100);
+        assert_ok!(Spectre::allocate_capital(
+            RuntimeOrigin::signed(TRADER),
+            NETWORK,
+            500
+        ));
+
+        assert_ok!(Spectre::settle_allocation(
+            RuntimeOrigin::signed(RELAYER_1),
+            TRADER,
+            500
+        ));
+        assert_noop!(
+            Spectre::settle_allocation(RuntimeOrigin::signed(RELAYER_2), TRADER, 500),
+            Error::<Test>::CapitalNotReturned
+        );
+        assert_noop!(
+            Spectre::settle_allocation(RuntimeOrigin::signed(ALICE), TRADER, 0),
+            Error::<Test>::RelayerNotRegistered
+        );
+    });
+}
+
+#[test]
+fn settlement_needs_threshold_attestations() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 1_000);
+        register_active_trader(TRADER, 100);
+        assert_ok!(Spectre::allocate_capital(
+            RuntimeOrigin::signed(TRADER),
+            NETWORK,
+            500
+        ));
+        assert_ok!(Tokens::transfer(
+            RuntimeOrigin::signed(TRADER),
+            pool_account(USDT),
+            USDT,
+            500
+        ));
+
+        // a single relayer can not book the returned capital
+        assert_ok!(Spectre::settle_allocation(
+            RuntimeOrigin::signed(RELAYER_1),
+            TRADER,
+            500
+        ));
+        assert_eq!(
+            TraderProfiles::<Test>::get(TRADER).unwrap().funds_allocated,
+            500
+        );
+        assert_noop!(
+            Spectre::settle_allocation(RuntimeOrigin::signed(RELAYER_1), TRADER, 500),
+            Error::<Test>::AlreadyAttested
+        );
+        assert_noop!(
+            Spectre::settle_allocation(RuntimeOrigin::signed(RELAYER_1), TRADER, 400),
+            Error::<Test>::ConflictingAttestation
+        );
+
+        // a relayer attesting another amount does not count for it
+        assert_ok!(Spectre::settle_allocation(
+            RuntimeOrigin::signed(RELAYER_2),
+            TRADER,
+            400
+        ));
+        assert_eq!(
+            TraderProfiles::<Test>::get(TRADER).unwrap().funds_allocated,
+            500
+        );
+
+        assert_ok!(Spectre::settle_allocation(
+            RuntimeOrigin::signed(RELAYER_3),
+            TRADER,
+            500
+        ));
+        assert_eq!(
+            TraderProfiles::<Test>::get(TRADER).unwrap().funds_allocated,
+            0
+        );
+        assert_eq!(CapitalPool::<Test>::get(USDT).remaining_capital, 1_000);
+    });
+}
+
+#[test]
+fn settlement_does_not_count_the_profit_reserve_as_returned() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 1_000);
+        register_active_trader(TRADER, 100);
+        assert_ok!(Spectre::allocate_capital(
+            RuntimeOrigin::signed(TRADER),
+            NETWORK,
+            500
+        ));
+        // profit distributed to the investors and not claimed yet
+        assert_ok!(<Tokens as MultiCurrency<AccountId>>::deposit(
+            USDT,
+            &pool_account(USDT),
+            100
+        ));
+        CapitalPool::<Test>::mutate(USDT, |pool| pool.profit_reserve = 100);
+
+        assert_ok!(Spectre::settle_allocation(
+            RuntimeOrigin::signed(RELAYER_1),
+            TRADER,
+            100
+        ));
+        assert_noop!(
+            Spectre::settle_allocation(RuntimeOrigin::signed(RELAYER_2), TRADER, 100),
+            Error::<Test>::CapitalNotReturned
+        );
+    });
+}
+
+#[test]
+fn epoch_distributes_realized_profit() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 1_000);
+        register_active_trader(TRADER, 100);
+        assert_ok!(Spectre::allocate_capital(
+            RuntimeOrigin::signed(TRADER),
+            NETWORK,
+            500
+        ));
+        return_and_settle(TRADER, 600);
+
+        // traders are settled on the first block of the epoch settlement, pools on the next one
+        run_to_block(EPOCH_LENGTH + 2);
+        assert_eq!(CurrentEpoch::<Test>::get(), 1);
+        let profile = TraderProfiles::<Test>::get(TRADER).unwrap();
+        // 60% of the settled gain
+        assert_eq!(profile.accrued_fees, 60);
+
+        // 2 of the 100 profit went to the insurance fund and 60 to the trader
+        assert_eq!(Spectre::claimable_profit(&ALICE, USDT), 38);
+        assert_ok!(Spectre::claim_profit(RuntimeOrigin::signed(ALICE), USDT));
+        assert_eq!(free_balance(USDT, &ALICE), INITIAL_BALANCE - 1_000 + 38);
+        assert_noop!(
+            Spectre::claim_profit(RuntimeOrigin::signed(ALICE), USDT),
+            Error::<Test>::NothingToClaim
+        );
+
+        assert_ok!(Spectre::claim_performance_fees(RuntimeOrigin::signed(
+            TRADER
+        )));
+        assert_eq!(Spectre::claimable_performance_fees(&TRADER), 0);
+    });
+}
+
+#[test]
+fn redemption_queue_is_filled_as_capital_returns() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 1_000);
+        register_active_trader(TRADER, 100);
+        assert_ok!(Spectre::allocate_capital(
+            RuntimeOrigin::signed(TRADER),
+            NETWORK,
+            800
+        ));
+        run_to_block(1 + WITHDRAW_PERIOD);
+
+        // only 200 is left in the pool, the request is partially filled
+        assert_ok!(Spectre::request_redemption(
+            RuntimeOrigin::signed(ALICE),
+            USDT,
+            500
+        ));
+        assert_eq!(free_balance(USDT, &ALICE), INITIAL_BALANCE - 800);
+        assert_eq!(Spectre::redemption_queue(USDT), vec![(0, ALICE, 300)]);
+        assert_noop!(
+            Spectre::withdraw_capital(RuntimeOrigin::signed(ALICE), USDT, 100),
+            Error::<Test>::RedemptionsPending
+        );
+        // locked shares cannot be transferred
+        assert!(Tokens::transfer(RuntimeOrigin::signed(ALICE), BOB, USDT_SHARES, 600).is_err());
+
+        return_and_settle(TRADER, 800);
+        assert!(Spectre::redemption_queue(USDT).is_empty());
+        assert_eq!(free_balance(USDT, &ALICE), INITIAL_BALANCE - 500);
+        assert_eq!(deposited(ALICE), 500);
+    });
+}
+
+#[test]
+fn cancel_redemption_unlocks_shares() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 1_000);
+        register_active_trader(TRADER, 100);
+        assert_ok!(Spectre::allocate_capital(
+            RuntimeOrigin::signed(TRADER),
+            NETWORK,
+            1_000
+        ));
+        run_to_block(1 + WITHDRAW_PERIOD);
+
+        assert_ok!(Spectre::request_redemption(
+            RuntimeOrigin::signed(ALICE),
+            USDT,
+            400
+        ));
+        assert_eq!(
+            <Tokens as MultiReservableCurrency<AccountId>>::reserved_balance(USDT_SHARES, &ALICE),
+            400
+        );
+        assert_noop!(
+            Spectre::cancel_redemption(RuntimeOrigin::signed(BOB), USDT, 0),
+            Error::<Test>::RedemptionNotFound
+        );
+
+        assert_ok!(Spectre::cancel_redemption(
+            RuntimeOrigin::signed(ALICE),
+            USDT,
+            0
+        ));
+        assert_eq!(
+            <Tokens as MultiReservableCurrency<AccountId>>::reserved_balance(USDT_SHARES, &ALICE),
+            0
+        );
+        assert!(Spectre::redemption_queue(USDT).is_empty());
+    });
+}
+
+#[test]
+fn registry_and_risk_policy_management_works() {
+    ExtBuilder::default().build().execute_with(|| {
+        register_active_trader(TRADER, 100);
+        let venue = DexVenue {
+            network: NETWORK,
+            dex: SupportedDexs::HydraDx,
+            router: None,
+            factory: None,
+            pallet_index: Some(75),
+            swap_calls: Some(SwapCalls { sell: 4, buy: 5 }),
+            allowed_assets: Default::default(),
+        };
+        assert_noop!(
+            Spectre::set_dex_venue(
+                RawOrigin::Root.into(),
+                1,
+                DexVenue {
+                    network: 2,
+                    ..venue.clone()
+                }
+            ),
+            Error::<Test>::NetworkNotSupported
+        );
+        assert_ok!(Spectre::set_dex_venue(RawOrigin::Root.into(), 1, venue));
+
+        let policy = RiskPolicy {
+            allowed_venues: BoundedVec::truncate_from(vec![2]),
+            ..Default::default()
+        };
+        assert_noop!(
+            Spectre::set_risk_policy(RawOrigin::Root.into(), TRADER, policy),
+            Error::<Test>::VenueNotFound
+        );
+        let policy = RiskPolicy {
+            allowed_venues: BoundedVec::truncate_from(vec![1]),
+            max_drawdown: Permill::from_percent(20),
+            ..Default::default()
+        };
+        assert_ok!(Spectre::set_risk_policy(
+            RawOrigin::Root.into(),
+            TRADER,
+            policy.clone()
+        ));
+        assert_eq!(
+            TraderProfiles::<Test>::get(TRADER).unwrap().risk_policy,
+            policy
+        );
+
+        assert_noop!(
+            Spectre::remove_target_network(RawOrigin::Root.into(), NETWORK),
+            Error::<Test>::NetworkInUse
+        );
+        assert_ok!(Spectre::remove_dex_venue(RawOrigin::Root.into(), 1));
+        assert_ok!(Spectre::remove_target_network(
+            RawOrigin::Root.into(),
+            NETWORK
+        ));
+    });
+}
+
+#[test]
+fn insurance_claims_are_paid_from_the_fund() {
+    ExtBuilder::default().build().execute_with(|| {
+        assert_ok!(<Tokens as MultiCurrency<AccountId>>::deposit(
+            USDT,
+            &Spectre::generate_insurance_account(USDT),
+            100
+        ));
+
+        assert_noop!(
+            Spectre::pay_insurance_claim(RawOrigin::Root.into(), USDT, BOB, 101),
+            Error::<Test>::InsufficientInsuranceFund
+        );
+        assert_noop!(
+            Spectre::pay_insurance_claim(RuntimeOrigin::signed(BOB), USDT, BOB, 10),
+            DispatchError::BadOrigin
+        );
+        assert_ok!(Spectre::pay_insurance_claim(
+            RawOrigin::Root.into(),
+            USDT,
+            BOB,
+            60
+        ));
+        assert_eq!(free_balance(USDT, &BOB), INITIAL_BALANCE + 60);
+        assert_eq!(Spectre::insurance_fund_balance(USDT), 40);
+    });
+}
+
+#[test]
+fn stale_traders_are_liquidated() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 1_000);
+        register_active_trader(TRADER, 100);
+        assert_ok!(Spectre::allocate_capital(
+            RuntimeOrigin::signed(TRADER),
+            NETWORK,
+            500
+        ));
+
+        run_to_block(1_002);
+        assert_eq!(
+            TraderProfiles::<Test>::get(TRADER).unwrap().status,
+            TraderStatus::Liquidating
+        );
+        System::assert_has_event(
+            Event::TraderLiquidating {
+                trader_id: TRADER,
+                reason: LiquidationReason::StaleReport,
+            }
+            .into(),
+        );
+
+        // a liquidated trader is suspended once its allocation is settled
+        return_and_settle(TRADER, 500);
+        assert_eq!(
+            TraderProfiles::<Test>::get(TRADER).unwrap().status,
+            TraderStatus::Suspended
+        );
+    });
+}
+
+fn roots(seed: u8) -> FinalizedRoots {
+    FinalizedRoots {
+        state_root: H256::repeat_byte(seed),
+        extrinsics_root: H256::repeat_byte(seed + 1),
+    }
+}
+
+// Proof carrying the roots of `roots`, the trie proofs themselves are not reached in these tests
+fn trade_proof(block_number: u64, roots: &FinalizedRoots) -> TradeExecutionProof<u64> {
+    TradeExecutionProof {
+        target_network: NETWORK,
+        target_network_blocknumber: block_number,
+        transaction_inclusion: TransactionInclusionProof::new(
+            vec![1, 2, 3],
+            vec![],
+            vec![0],
+            roots.extrinsics_root.as_bytes().to_vec(),
+        ),
+        state_proof: StateProof {
+            state_root: roots.state_root.as_bytes().to_vec(),
+            state_proofs: vec![],
+            state_key: vec![],
+        },
+        consensus_proof: None,
+    }
+}
+
+// Balances map of USDT on the target network, an `orml_tokens` `Accounts` map
+fn usdt_balance_storage() -> RemoteBalanceStorage {
+    RemoteBalanceStorage {
+        prefix: [9u8; 32],
+        asset: BoundedVec::truncate_from(10u32.encode()),
+    }
+}
+
+// Blake2 trie of `entries`
+fn build_trie(entries: &[(Vec<u8>, Vec<u8>)]) -> (MemoryDB<BlakeTwo256>, H256) {
+    let mut db = MemoryDB::<BlakeTwo256>::default();
+    let mut root = H256::default();
+    {
+        let mut trie = TrieDBMutBuilderV1::<BlakeTwo256>::new(&mut db, &mut root).build();
+        for (key, value) in entries {
+            trie.insert(key, value).unwrap();
+        }
+    }
+    (db, root)
+}
+
+// Trade proof of `transaction` in block `block_number` proving `state_key` of a state holding the USDT and
+// the DOT balances of the trading account. The roots of the block are attested by the relayers
+fn attested_trade_proof(
+    block_number: u64,
+    transaction: Vec<u8>,
+    state_key: Vec<u8>,
+) -> TradeExecutionProof<u64> {
+    let trading_account = RemoteAccount::AccountId32([5u8; 32]);
+    let dot_balance_storage = RemoteBalanceStorage {
+        asset: BoundedVec::truncate_from(5u32.encode()),
+        ..usdt_balance_storage()
+    };
+    let account_data = |free: u128| {
+        RemoteAccountData {
+            free,
+            reserved: 0,
+            frozen: 0,
+        }
+        .encode()
+    };
+    let (mut state_db, state_root) = build_trie(&[
+        (
+            usdt_balance_storage().account_key(&trading_account),
+            account_data(600),
+        ),
+        (
+            dot_balance_storage.account_key(&trading_account),
+            account_data(5_000),
+        ),
+    ]);
+    let tx_key = Compact(0u32).encode();
+    let (tx_db, tx_root) = build_trie(&[(tx_key.clone(), transaction.clone())]);
+    let tx_proof =
+        generate_trie_proof::<LayoutV1<BlakeTwo256>, _, _, _>(&tx_db, tx_root, &[tx_key.clone()])
+            .unwrap();
+
+    let roots = FinalizedRoots {
+        state_root,
+        extrinsics_root: tx_root,
+    };
+    if FinalizedNetworkRoots::<Test>::get(NETWORK, block_number).is_none() {
+        for relayer in [RELAYER_1, RELAYER_2] {
+            assert_ok!(Spectre::attest_network_roots(
+                RuntimeOrigin::signed(relayer),
+                NETWORK,
+                block_number,
+                roots.clone()
+            ));
+        }
+    }
+    TradeExecutionProof {
+        target_network: NETWORK,
+        target_network_blocknumber: block_number,
+        transaction_inclusion: TransactionInclusionProof::new(
+            transaction,
+            tx_proof,
+            tx_key,
+            tx_root.as_bytes().to_vec(),
+        ),
+        state_proof: StateProof {
+            state_root: state_root.as_bytes().to_vec(),
+            state_proofs: state_db
+                .drain()
+                .into_values()
+                .filter(|(_, rc)| *rc > 0)
+                .map(|(node, _)| node)
+                .collect(),
+            state_key,
+        },
+        consensus_proof: None,
+    }
+}
+
+// Allocate 500 USDT to an active trader on the network and register the USDT balances map of the network
+fn fund_trader_on_network() {
+    deposit(ALICE, 1_000);
+    register_active_trader(TRADER, 100);
+    assert_ok!(Spectre::allocate_capital(
+        RuntimeOrigin::signed(TRADER),
+        NETWORK,
+        500
+    ));
+    assert_ok!(Spectre::set_network_asset(
+        RawOrigin::Root.into(),
+        NETWORK,
+        USDT,
+        Some(usdt_balance_storage())
+    ));
+}
+
+fn verify_trade(proof: TradeExecutionProof<u64>) -> DispatchResult {
+    Spectre::verify_trade_execution(
+        RuntimeOrigin::none(),
+        TRADER,
+        USDT,
+        NETWORK,
+        proof,
+        TradeAction::Buy,
+    )
+}
+
+#[test]
+fn trade_proof_must_prove_the_pool_asset_balance() {
+    ExtBuilder::default().build().execute_with(|| {
+        fund_trader_on_network();
+        let trading_account = RemoteAccount::AccountId32([5u8; 32]);
+        let usdt_key = usdt_balance_storage().account_key(&trading_account);
+        let dot_key = RemoteBalanceStorage {
+            asset: BoundedVec::truncate_from(5u32.encode()),
+            ..usdt_balance_storage()
+        }
+        .account_key(&trading_account);
+
+        // another balance of the trading account can not stand for its pool asset balance
+        assert_noop!(
+            verify_trade(attested_trade_proof(5, vec![1, 2, 3], dot_key)),
+            Error::<Test>::InvalidBalanceStateProof
+        );
+        assert_ok!(verify_trade(attested_trade_proof(
+            5,
+            vec![1, 2, 3],
+            usdt_key
+        )));
+        assert_eq!(
+            TraderProfiles::<Test>::get(TRADER)
+                .unwrap()
+                .unrealized_balance,
+            600
+        );
+        assert_eq!(CapitalPool::<Test>::get(USDT).unrealized_balance, 1_100);
+    });
+}
+
+fn validate_trade(proof: TradeExecutionProof<u64>) -> TransactionValidity {
+    <Spectre as ValidateUnsigned>::validate_unsigned(
+        TransactionSource::External,
+        &Call::verify_trade_execution {
+            trader_id: TRADER,
+            asset_id: USDT,
+            network: NETWORK,
+            trade_execution_proof: proof,
+            trade_action: TradeAction::Buy,
+        },
+    )
+}
+
+#[test]
+fn trade_proof_can_not_be_replayed() {
+    ExtBuilder::default().build().execute_with(|| {
+        fund_trader_on_network();
+        let usdt_key = usdt_balance_storage().account_key(&RemoteAccount::AccountId32([5u8; 32]));
+        let proof = attested_trade_proof(5, vec![1, 2, 3], usdt_key);
+        assert_ok!(validate_trade(proof.clone()));
+        assert_ok!(verify_trade(proof.clone()));
+
+        assert_eq!(
+            validate_trade(proof.clone()),
+            Err(InvalidTransaction::Stale.into())
+        );
+        assert_noop!(
+            verify_trade(proof),
+            Error::<Test>::TradeProofAlreadyProcessed
+        );
+    });
+}
+
+#[test]
+fn trade_proof_of_an_older_block_is_rejected() {
+    ExtBuilder::default().build().execute_with(|| {
+        fund_trader_on_network();
+        let usdt_key = usdt_balance_storage().account_key(&RemoteAccount::AccountId32([5u8; 32]));
+        assert_ok!(verify_trade(attested_trade_proof(
+            6,
+            vec![1, 2, 3],
+            usdt_key.clone()
+        )));
+        assert_eq!(LastVerifiedBlock::<Test>::get(TRADER), Some(6));
+
+        let older = attested_trade_proof(5, vec![4, 5, 6], usdt_key);
+        assert_eq!(
+            validate_trade(older.clone()),
+            Err(InvalidTransaction::Stale.into())
+        );
+        assert_noop!(verify_trade(older), Error::<Test>::StaleTradeProof);
+    });
+}
+
+#[test]
+fn trade_proofs_need_a_registered_network_asset() {
+    ExtBuilder::default().build().execute_with(|| {
+        fund_trader_on_network();
+        let usdt_key = usdt_balance_storage().account_key(&RemoteAccount::AccountId32([5u8; 32]));
+        assert_ok!(Spectre::set_network_asset(
+            RawOrigin::Root.into(),
+            NETWORK,
+            USDT,
+            None
+        ));
+        assert_noop!(
+            verify_trade(attested_trade_proof(5, vec![1, 2, 3], usdt_key)),
+            Error::<Test>::NetworkAssetNotRegistered
+        );
+    });
+}
+
+#[test]
+fn network_roots_are_finalized_by_relayer_threshold() {
+    ExtBuilder::default().build().execute_with(|| {
+        assert_ok!(Spectre::set_target_network(
+            RawOrigin::Root.into(),
+            NETWORK,
+            substrate_network()
+        ));
+        assert_noop!(
+            Spectre::attest_network_roots(RuntimeOrigin::signed(ALICE), NETWORK, 5, roots(1)),
+            Error::<Test>::RelayerNotRegistered
+        );
+
+        assert_ok!(Spectre::attest_network_roots(
+            RuntimeOrigin::signed(RELAYER_1),
+            NETWORK,
+            5,
+            roots(1)
+        ));
+        assert_noop!(
+            Spectre::attest_network_roots(RuntimeOrigin::signed(RELAYER_1), NETWORK, 5, roots(1)),
+            Error::<Test>::AlreadyAttested
+        );
+        assert_noop!(
+            Spectre::attest_network_roots(RuntimeOrigin::signed(RELAYER_1), NETWORK, 5, roots(3)),
+            Error::<Test>::ConflictingAttestation
+        );
+        assert!(FinalizedNetworkRoots::<Test>::get(NETWORK, 5).is_none());
+
+        assert_ok!(Spectre::attest_network_roots(
+            RuntimeOrigin::signed(RELAYER_2),
+            NETWORK,
+            5,
+            roots(1)
+        ));
+        assert_eq!(
+            FinalizedNetworkRoots::<Test>::get(NETWORK, 5),
+            Some(roots(1))
+        );
+        assert_noop!(
+            Spectre::attest_network_roots(RuntimeOrigin::signed(RELAYER_3), NETWORK, 5, roots(1)),
+            Error::<Test>::RootsAlreadyFinalized
+        );
+    });
+}
+
+#[test]
+fn trade_proof_with_untrusted_roots_is_rejected() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 1_000);
+        register_active_trader(TRADER, 100);
+        assert_ok!(Spectre::allocate_capital(
+            RuntimeOrigin::signed(TRADER),
+            NETWORK,
+            500
+        ));
+        for relayer in [RELAYER_1, RELAYER_2] {
+            assert_ok!(Spectre::attest_network_roots(
+                RuntimeOrigin::signed(relayer),
+                NETWORK,
+                5,
+                roots(1)
+            ));
+        }
+
+        // roots made up by the submitter, or of a block without finalized roots
+        for proof in [trade_proof(5, &roots(3)), trade_proof(6, &roots(1))] {
+            let call = Call::verify_trade_execution {
+                trader_id: TRADER,
+                asset_id: USDT,
+                network: NETWORK,
+                trade_execution_proof: proof.clone(),
+                trade_action: TradeAction::Buy,
+            };
+            assert_eq!(
+                <Spectre as ValidateUnsigned>::validate_unsigned(
+                    TransactionSource::External,
+                    &call
+                ),
+                Err(InvalidTransaction::BadProof.into())
+            );
+            assert_noop!(
+                Spectre::verify_trade_execution(
+                    RuntimeOrigin::none(),
+                    TRADER,
+                    USDT,
+                    NETWORK,
+                    proof,
+                    TradeAction::Buy
+                ),
+                Error::<Test>::UntrustedStateRoot
+            );
+        }
+
+        // anchored proofs reach the trie verification
+        let call = Call::verify_trade_execution {
+            trader_id: TRADER,
+            asset_id: USDT,
+            network: NETWORK,
+            trade_execution_proof: trade_proof(5, &roots(1)),
+            trade_action: TradeAction::Buy,
+        };
+        assert_ok!(<Spectre as ValidateUnsigned>::validate_unsigned(
+            TransactionSource::External,
+            &call
+        ));
+        assert_noop!(
+            Spectre::verify_trade_execution(
+                RuntimeOrigin::none(),
+                TRADER,
+                USDT,
+                NETWORK,
+                trade_proof(5, &roots(1)),
+                TradeAction::Buy
+            ),
+            Error::<Test>::InvalidTxInclusion
+        );
+        assert_eq!(CapitalPool::<Test>::get(USDT).unrealized_balance, 1_000);
+    });
+}
+
+#[test]
+fn only_verifiable_networks_can_be_registered() {
+    ExtBuilder::default().build().execute_with(|| {
+        for info in [
+            NetworkInfo {
+                kind: Networks::Ethereum,
+                proof_kind: ProofKind::EthereumMpt,
+                hasher: HashAlgorithm::Keccak,
+                ..substrate_network()
+            },
+            NetworkInfo {
+                hasher: HashAlgorithm::Keccak,
+                ..substrate_network()
+            },
+            NetworkInfo {
+                finality: FinalitySource::ConsensusProof,
+                ..substrate_network()
+            },
+        ] {
+            assert_noop!(
+                Spectre::set_target_network(RawOrigin::Root.into(), NETWORK, info),
+                Error::<Test>::UnsupportedProofKind
+            );
+        }
+        assert_ok!(Spectre::set_target_network(
+            RawOrigin::Root.into(),
+            NETWORK,
+            NetworkInfo {
+                proof_kind: ProofKind::XcmQuery,
+                ..substrate_network()
+            }
+        ));
+    });
+}
+
+#[test]
+fn malformed_proof_roots_are_rejected() {
+    ExtBuilder::default().build().execute_with(|| {
+        let network = substrate_network();
+        assert!(
+            !<TradeExecutionVerifyV1 as TradeExecutionVerifier<Test>>::verify_trade_tx_inclusion(
+                &network,
+                TransactionInclusionProof::new(vec![1], vec![], vec![0], vec![1; 31]),
+            )
+        );
+        assert!(
+            <TradeExecutionVerifyV1 as TradeExecutionVerifier<Test>>::verify_state_acount_balance(
+                &network,
+                StateProof {
+                    state_root: vec![1; 33],
+                    state_proofs: vec![],
+                    state_key: vec![0],
+                },
+            )
+            .is_err()
+        );
+    });
+}
+
+#[test]
+fn failed_stale_trader_recall_is_rolled_back() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 1_000);
+        register_active_trader(TRADER, 100);
+        assert_ok!(Spectre::allocate_capital(
+            RuntimeOrigin::signed(TRADER),
+            NETWORK,
+            500
+        ));
+        // the recall can not find the trading account
+        OnChainTradingAccounts::<Test>::remove(TRADER);
+
+        System::set_block_number(1_002);
+        System::reset_events();
+        let weight = Spectre::liquidate_stale_traders(1_002);
+        assert!(weight.all_gte(<() as WeightInfo>::liquidate_trader()));
+        assert_eq!(
+            TraderProfiles::<Test>::get(TRADER).unwrap().status,
+            TraderStatus::Active
+        );
+        assert!(System::events().is_empty());
+    });
+}
+
+#[test]
+fn failing_redemption_does_not_block_the_queue() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 1_000);
+        deposit(BOB, 1_000);
+        register_active_trader(TRADER, 100);
+        assert_ok!(Spectre::allocate_capital(
+            RuntimeOrigin::signed(TRADER),
+            NETWORK,
+            1_800
+        ));
+        run_to_block(1 + WITHDRAW_PERIOD);
+
+        assert_ok!(Spectre::request_redemption(
+            RuntimeOrigin::signed(ALICE),
+            USDT,
+            500
+        ));
+        assert_ok!(Spectre::request_redemption(
+            RuntimeOrigin::signed(BOB),
+            USDT,
+            300
+        ));
+        assert_eq!(
+            Spectre::redemption_queue(USDT),
+            vec![(0, ALICE, 300), (1, BOB, 300)]
+        );
+
+        // the position of Alice can no longer be stored
+        let position = InvestorPositions::<Test>::take(ALICE, USDT).unwrap();
+        InvestorPositions::<Test>::insert(ALICE, 7, position.clone());
+        InvestorPositions::<Test>::insert(ALICE, 8, position);
+        return_and_settle(TRADER, 1_800);
+
+        System::assert_has_event(
+            Event::RedemptionFailed {
+                request_id: 0,
+                investor_id: ALICE,
+                asset_id: USDT,
+                shares: 300,
+                error: Error::<Test>::TooManyInvestorPools.into(),
+            }
+            .into(),
+        );
+        assert!(Spectre::redemption_queue(USDT).is_empty());
+        assert_eq!(
+            <Tokens as MultiReservableCurrency<AccountId>>::reserved_balance(USDT_SHARES, &ALICE),
+            0
+        );
+        assert_eq!(shares_of(&ALICE), 800);
+        assert_eq!(free_balance(USDT, &BOB), INITIAL_BALANCE - 700);
+    });
+}
+
+#[test]
+fn deposit_mints_previewed_shares() {
+    ExtBuilder::default().build().execute_with(|| {
+        deposit(ALICE, 100);
+        mark_pool_value(USDT, 200, 0);
+
+        // the value of 7 shares, rounded up, mints at least 7 shares
+        let assets = CapitalPool::<Test>::get(USDT).value_for_capital(7, Rounding::Up);
+        assert_eq!(assets, 21);
+        deposit(BOB, assets);
+        assert_eq!(shares_of(&BOB), 7);
+
+        let previewed = CapitalPool::<Test>::get(USDT).capital_for_value(10, Rounding::Down);
+        deposit(BOB, 10);
+        assert_eq!(shares_of(&BOB), 7 + previewed);
+    });
+}
+
+// Allocate 500 to the trader on the substrate network checked with XCM balance queries
+fn allocate_on_xcm_network() {
+    deposit(ALICE, 1_000);
+    register_active_trader(TRADER, 100);
+    assert_ok!(Spectre::set_target_network(
+        RawOrigin::Root.into(),
+        NETWORK,
+        NetworkInfo {
+            proof_kind: ProofKind::XcmQuery,
+            ..substrate_network()
+        }
+    ));
+    assert_ok!(Spectre::allocate_capital(
+        RuntimeOrigin::signed(TRADER),
+        NETWORK,
+        500
+    ));
+}
+
+// Claim the trader balance with the threshold of relayers, sending the balance query
+fn claim_balance(balance: Balance) {
+    for relayer in [RELAYER_1, RELAYER_2] {
+        assert_ok!(Spectre::request_balance_query(
+            RuntimeOrigin::signed(relayer),
+            TRADER,
+            balance
+        ));
+    }
+}
+
+#[test]
+fn balance_query_records_the_reported_balance() {
+    ExtBuilder::default().build().execute_with(|| {
+        allocate_on_xcm_network();
+
+        claim_balance(450);
+        assert_ok!(Spectre::balance_query_response(
+            RawOrigin::Root.into(),
+            0,
+            balance_report(520)
+        ));
+        assert_eq!(
+            TraderProfiles::<Test>::get(TRADER)
+                .unwrap()
+                .unrealized_balance,
+            520
+        );
+        System::assert_last_event(
+            Event::TraderBalanceVerified {
+                trader_id: TRADER,
+                query_id: 0,
+                balance: 520,
+            }
+            .into(),
+        );
+
+        // a report short of the claim records nothing
+        claim_balance(600);
+        assert_ok!(Spectre::balance_query_response(
+            RawOrigin::Root.into(),
+            1,
+            balance_report(550)
+        ));
+        assert_eq!(
+            TraderProfiles::<Test>::get(TRADER)
+                .unwrap()
+                .unrealized_balance,
+            520
+        );
+        System::assert_last_event(
+            Event::TraderBalanceNotVerified {
+                trader_id: TRADER,
+                query_id: 1,
+                reported: 550,
+            }
+            .into(),
+        );
+    });
+}
+
+#[test]
+fn balance_query_needs_threshold_claims() {
+    ExtBuilder::default().build().execute_with(|| {
+        allocate_on_xcm_network();
+
+        assert_ok!(Spectre::request_balance_query(
+            RuntimeOrigin::signed(RELAYER_1),
+            TRADER,
+            450
+        ));
+        assert!(BalanceQueries::<Test>::get(0).is_none());
+        assert_noop!(
+            Spectre::request_balance_query(RuntimeOrigin::signed(RELAYER_1), TRADER, 450),
+            Error::<Test>::AlreadyAttested
+        );
+
+        // the relayer moves its claim, the other relayer claiming the first balance is alone
+        assert_ok!(Spectre::request_balance_query(
+            RuntimeOrigin::signed(RELAYER_1),
+            TRADER,
+            300
+        ));
+        assert_ok!(Spectre::request_balance_query(
+            RuntimeOrigin::signed(RELAYER_2),
+            TRADER,
+            450
+        ));
+        assert!(BalanceQueries::<Test>::get(0).is_none());
+
+        assert_ok!(Spectre::request_balance_query(
+            RuntimeOrigin::signed(RELAYER_3),
+            TRADER,
+            450
+        ));
+        assert_eq!(BalanceQueries::<Test>::get(0).unwrap().balance, 450);
+        assert_eq!(BalanceClaims::<Test>::iter_prefix(TRADER).count(), 0);
+    });
+}
+
+#[test]
+fn balance_query_response_must_come_from_the_queried_network() {
+    ExtBuilder::default().build().execute_with(|| {
+        allocate_on_xcm_network();
+
+        QueryResponder::set(MultiLocation::here());
+        claim_balance(450);
+        assert_noop!(
+            Spectre::balance_query_response(RawOrigin::Root.into(), 0, balance_report(520)),
+            Error::<Test>::UnexpectedResponder
+        );
+        assert!(BalanceQueries::<Test>::get(0).is_some());
+    });
+}
+
+// Request a sell of 100 of the pool asset for asset 5 on the HydraDX venue of the trader network
+fn request_remote_swap() {
+    assert_ok!(Spectre::set_dex_venue(
+        RawOrigin::Root.into(),
+        1,
+        DexVenue {
+            network: NETWORK,
+            dex: SupportedDexs::HydraDx,
+            router: None,
+            factory: None,
+            pallet_index: Some(75),
+            swap_calls: Some(SwapCalls { sell: 4, buy: 5 }),
+            allowed_assets: BoundedVec::truncate_from(vec![BoundedVec::truncate_from(
+                5u32.encode()
+            )]),
+        }
+    ));
+    assert_ok!(Spectre::request_remote_swap(
+        RuntimeOrigin::signed(TRADER),
+        RemoteSwap {
+            venue: 1,
+            action: TradeAction::Sell,
+            asset_in: 10,
+            asset_out: 5,
+            amount: 100,
+            limit: 0,
+        }
+    ));
+}
+
+#[test]
+fn remote_swap_result_must_come_from_the_venue_network() {
+    ExtBuilder::default().build().execute_with(|| {
+        allocate_on_xcm_network();
+        QueryResponder::set(MultiLocation::here());
+        request_remote_swap();
+
+        assert_noop!(
+            Spectre::remote_swap_executed(
+                RawOrigin::Root.into(),
+                0,
+                Response::DispatchResult(MaybeErrorCode::Success)
+            ),
+            Error::<Test>::UnexpectedResponder
+        );
+    });
+}
+
+#[test]
+fn remote_swap_without_result_expires() {
+    ExtBuilder::default().build().execute_with(|| {
+        allocate_on_xcm_network();
+        request_remote_swap();
+
+        assert_noop!(
+            Spectre::expire_remote_swap(RuntimeOrigin::signed(BOB), 0),
+            Error::<Test>::RemoteSwapNotExpired
+        );
+        System::set_block_number(1 + REMOTE_SWAP_TIMEOUT);
+        assert_ok!(Spectre::expire_remote_swap(RuntimeOrigin::signed(BOB), 0));
+        assert!(RemoteSwaps::<Test>::get(0).is_none());
+        System::assert_last_event(
+            Event::RemoteSwapExpired {
+                trader_id: TRADER,
+                query_id: 0,
+            }
+            .into(),
+        );
+
+        // the late result finds nothing to report
+        assert_noop!(
+            Spectre::remote_swap_executed(
+                RawOrigin::Root.into(),
+                0,
+                Response::DispatchResult(MaybeErrorCode::Success)
+            ),
+            Error::<Test>::RemoteSwapNotFound
+        );
+    });
+}
+
+#[test]
+fn pool_queries_unverified_balances_on_xcm_networks() {
+    ExtBuilder::default().build().execute_with(|| {
+        allocate_on_xcm_network();
+
+        Spectre::query_trader_balances(50);
+        assert!(BalanceQueries::<Test>::get(0).is_none());
+
+        // the last recorded balance is due for a check
+        System::set_block_number(101);
+        Spectre::query_trader_balances(101);
+        assert_eq!(BalanceQueries::<Test>::get(0).unwrap().balance, 500);
+        assert_eq!(LastBalanceQuery::<Test>::get(TRADER), Some(101));
+
+        // not sent again while the response is pending
+        Spectre::query_trader_balances(150);
+        assert!(BalanceQueries::<Test>::get(1).is_none());
+
+        assert_ok!(Spectre::balance_query_response(
+            RawOrigin::Root.into(),
+            0,
+            balance_report(500)
+        ));
+        assert_eq!(
+            TraderProfiles::<Test>::get(TRADER).unwrap().last_report,
+            101
+        );
+    });
+}
@@ -3,7 +3,7 @@
 use {
     frame_support::{pallet_prelude::*, DefaultNoBound},
     frame_system::pallet_prelude::*,
-    sp_io::hashing::blake2_128,
+    sp_io::hashing::{blake2_128, twox_64},
     sp_std::{vec, vec::Vec},
 };
 
@@ -16,28 +16,47 @@ pub mod utils {
     extern crate alloc;
 
     use {
-        frame_support::sp_runtime::traits::BlakeTwo256,
+        frame_support::{
+            sp_runtime::traits::BlakeTwo256,
+            storage::{migration, with_storage_layer},
+            traits::{Contains, PalletInfoAccess},
+            BoundedBTreeMap,
+        },
         sp_core::{
             serde::{Deserialize, Serialize},
-            H256,
+            sr25519, H160, H256,
         },
         sp_trie::{LayoutV1, StorageProof, TrieDBBuilder},
+        staging_xcm::latest::{MultiLocation, QueryId, Response},
     };
 
     use {
         alloc::collections::BTreeMap,
-        frame_support::sp_runtime::{traits::TrailingZeroInput, MultiAddress},
-        sp_arithmetic::Permill,
+        frame_support::sp_runtime::{
+            traits::{
+                CheckedAdd, CheckedSub, Hash, SaturatedConversion, Saturating, StaticLookup,
+                TrailingZeroInput, Zero,
+            },
+            ArithmeticError, MultiAddress,
+        },
+        frame_system::RawOrigin,
+        orml_traits::{
+            asset_registry::AssetMetadata,
+            currency::{MutationHooks, OnTransfer},
+            BalanceStatus, MultiCurrency, MultiReservableCurrency,
+        },
+        sp_arithmetic::{
+            helpers_128bit::multiply_by_rational_with_rounding, FixedPointNumber, FixedU128,
+            Permill, Rounding,
+        },
     };
-    // use sp_core::{blake2_128, ConstU8};
     use {
-        //hash_db::HashDB,
-        parity_scale_codec::{Decode, Encode},
+        parity_scale_codec::{Decode, DecodeAll, Encode},
         sp_core::ConstU8,
         sp_trie::Trie,
     };
 
-    use super::*;
+    use {super::*, crate::WeightInfo};
 
     impl<T: Config> Pallet<T> {
         // helper function to generate onchain keyless account
@@ -49,165 +68,2015 @@ pub mod utils {
             pool_account_id
         }
 
-        // For trade execution verifier
-        // pub fn read_proof_check<H, I>(
-        //     root: &H::Out,
-        //     proof: StorageProof,
-        //     keys: I,
-        // ) -> Result<BTreeMap<Vec<u8>, Option<Vec<u8>>>, Error<T>>
-        // where
-        //     H: hash_db::Hasher,
-        //     H::Out: scale_info::prelude::fmt::Debug,
-        //     I: IntoIterator,
-        //     I::Item: AsRef<[u8]>,
-        // {
-        //     let db = proof.into_memory_db();
+        // helper function to generate the keyless insurance fund account of an asset
+        pub fn generate_insurance_account(asset_id: T::CurrencyId) -> AccountIdFor<T> {
+            let entropy = (b"spectre/insurance", asset_id).using_encoded(blake2_128);
+            let insurance_account_id =
+                Decode::decode(&mut TrailingZeroInput::new(entropy.as_ref()))
+                    .expect("Infinite length input, Cant create an account");
+
+            insurance_account_id
+        }
+
+        // helper function to move pool asset out of the keyless pool account
+        pub fn transfer_from_pool(
+            pool_account: AccountIdFor<T>,
+            dest: AccountIdFor<T>,
+            asset_id: T::CurrencyId,
+            amount: AssetBalance<T>,
+        ) -> DispatchResult {
+            <orml_tokens::Pallet<T>>::transfer(
+                RawOrigin::Signed(pool_account).into(),
+                T::Lookup::unlookup(dest),
+                asset_id,
+                amount,
+            )
+        }
+
+        // Recall every open allocation of the pool, returns true if nothing is left allocated
+        pub fn recall_pool_allocations(asset_id: T::CurrencyId) -> Result<bool, DispatchError> {
+            let mut fully_recalled = true;
+            for (trader_id, profile) in TraderProfiles::<T>::iter() {
+                if profile.asset_id != asset_id || profile.funds_allocated.is_zero() {
+                    continue;
+                }
+                fully_recalled = false;
+                Self::recall_trader_capital(&trader_id, &profile)?;
+            }
+
+            Ok(fully_recalled)
+        }
+
+        // Instruct the return of the capital allocated to the trader
+        pub fn recall_trader_capital(
+            trader_id: &AccountIdFor<T>,
+            profile: &TraderProfile<T>,
+        ) -> DispatchResult {
+            let network = profile.network.ok_or(Error::<T>::TraderNotFunded)?;
+            let (_, trading_account) = Self::trading_account_on(trader_id, network)?;
+            T::CapitalAllocator::recall_capital(
+                network,
+                profile.asset_id.clone(),
+                profile.unrealized_balance,
+                trader_id.clone(),
+                trading_account,
+            )?;
+            Self::deposit_event(Event::CapitalRecalled {
+                asset_id: profile.asset_id.clone(),
+                trader_id: trader_id.clone(),
+            });
+            Ok(())
+        }
+
+        // Fetch the registered target network and the trader account native to it
+        pub fn trading_account_on(
+            trader_id: &AccountIdFor<T>,
+            network: NetworkId,
+        ) -> Result<(NetworkInfo, RemoteAccount), DispatchError> {
+            let info = TargetNetworks::<T>::get(network).ok_or(Error::<T>::NetworkNotSupported)?;
+            let trading_account = OnChainTradingAccounts::<T>::get(trader_id)
+                .and_then(|accounts| accounts.get(&info.kind).cloned())
+                .ok_or(Error::<T>::TraderNotRegistered)?;
+            Ok((info, trading_account))
+        }
+
+        // Suspend the trader and recall its open allocation
+        pub fn do_suspend_trader(
+            trader_id: &AccountIdFor<T>,
+            reason: SuspensionReason,
+        ) -> DispatchResult {
+            let mut profile =
+                TraderProfiles::<T>::get(trader_id).ok_or(Error::<T>::TraderNotRegistered)?;
+            ensure!(
+                matches!(
+                    profile.status,
+                    TraderStatus::AccountsConfirmed | TraderStatus::Active
+                ),
+                Error::<T>::InvalidTraderStatus
+            );
+            if !profile.funds_allocated.is_zero() {
+                Self::recall_trader_capital(trader_id, &profile)?;
+            }
+            profile.status = TraderStatus::Suspended;
+            TraderProfiles::<T>::insert(trader_id, profile);
+
+            Self::deposit_event(Event::TraderSuspended {
+                trader_id: trader_id.clone(),
+                reason,
+            });
+            Ok(())
+        }
+
+        // Release the trader bond and remove the trader and its trading accounts
+        pub fn do_deregister_trader(
+            trader_id: &AccountIdFor<T>,
+            profile: TraderProfile<T>,
+        ) -> DispatchResult {
+            let not_released =
+                <orml_tokens::Pallet<T> as MultiReservableCurrency<AccountIdFor<T>>>::unreserve(
+                    profile.asset_id.clone(),
+                    trader_id,
+                    profile.bonded_amount.amount,
+                );
+            TraderProfiles::<T>::remove(trader_id);
+            OnChainTradingAccounts::<T>::remove(trader_id);
+            LastBalanceQuery::<T>::remove(trader_id);
+            LastVerifiedBlock::<T>::remove(trader_id);
+            let _ = ProcessedTradeProofs::<T>::clear_prefix(trader_id, u32::MAX, None);
+            let _ =
+                SettlementAttestations::<T>::clear_prefix(trader_id, T::MaxRelayers::get(), None);
+            let _ = BalanceClaims::<T>::clear_prefix(trader_id, T::MaxRelayers::get(), None);
+
+            Self::deposit_event(Event::TraderDeregistered {
+                trader_id: trader_id.clone(),
+                bond_released: profile.bonded_amount.amount.saturating_sub(not_released),
+            });
+            Ok(())
+        }
+
+        // Move part of the trader bond to the pool account, returns the slashed amount
+        pub fn slash_trader_bond(
+            trader_id: &AccountIdFor<T>,
+            profile: &mut TraderProfile<T>,
+            amount: AssetBalance<T>,
+        ) -> Result<AssetBalance<T>, DispatchError> {
+            let amount = amount.min(profile.bonded_amount.amount);
+            let pool_account = Self::generate_pool_account(Some(profile.asset_id.clone()));
+            let not_slashed = <orml_tokens::Pallet<T> as MultiReservableCurrency<
+                AccountIdFor<T>,
+            >>::repatriate_reserved(
+                profile.asset_id.clone(),
+                trader_id,
+                &pool_account,
+                amount,
+                BalanceStatus::Free,
+            )?;
+            let slashed = amount.saturating_sub(not_slashed);
+
+            profile.bonded_amount.amount = profile.bonded_amount.amount.saturating_sub(slashed);
+            CapitalPool::<T>::mutate(profile.asset_id.clone(), |pool| {
+                pool.remaining_capital = pool.remaining_capital.saturating_add(slashed);
+                pool.unrealized_balance = pool.unrealized_balance.saturating_add(slashed);
+            });
+            Ok(slashed)
+        }
+
+        // Evaluate the trades decoded from a verified transaction against the trader risk policy.
+        // Violations cost credits and part of the bond, too many of them suspend the trader
+        pub fn enforce_risk_policy(
+            trader_id: &AccountIdFor<T>,
+            network: NetworkId,
+            network_info: &NetworkInfo,
+            trading_account: &RemoteAccount,
+            transaction: &[u8],
+        ) -> DispatchResult {
+            let mut profile =
+                TraderProfiles::<T>::get(trader_id).ok_or(Error::<T>::TraderNotRegistered)?;
+            let policy = profile.risk_policy.clone();
+            let trades = T::TradeDecoder::decode_trades(
+                network,
+                network_info,
+                trading_account,
+                transaction,
+            )?;
+
+            let now = frame_system::Pallet::<T>::block_number();
+            if now.saturating_sub(profile.period_start) >= policy.period {
+                profile.period_start = now;
+                profile.period_trades = 0;
+            }
+            profile.period_trades = profile
+                .period_trades
+                .saturating_add(trades.len().max(1).saturated_into());
+
+            let mut violations = Vec::new();
+            if policy.max_trades_per_period != 0
+                && profile.period_trades > policy.max_trades_per_period
+            {
+                violations.push(RiskViolation::TradeFrequency);
+            }
+
+            let max_position = policy.max_position.mul_floor(profile.funds_allocated);
+            for trade in trades {
+                let venue = DexVenues::<T>::get(trade.venue).filter(|venue| {
+                    venue.network == network
+                        && (policy.allowed_venues.is_empty()
+                            || policy.allowed_venues.contains(&trade.venue))
+                });
+                match venue {
+                    Some(venue) => {
+                        let is_asset_allowed = venue.is_asset_allowed(&trade.asset_out)
+                            && (policy.allowed_assets.is_empty()
+                                || policy.allowed_assets.contains(&trade.asset_out));
+                        if !is_asset_allowed {
+                            violations.push(RiskViolation::AssetNotAllowed);
+                        }
+                    }
+                    None => violations.push(RiskViolation::VenueNotAllowed),
+                }
+                if trade.position_value > max_position {
+                    violations.push(RiskViolation::PositionTooLarge);
+                }
+            }
+
+            for violation in violations {
+                profile.violations = profile.violations.saturating_add(1);
+                profile.credits = profile.credits.saturating_sub(1);
+                let slash = T::RiskViolationSlash::get().mul_floor(profile.bonded_amount.amount);
+                let slashed = Self::slash_trader_bond(trader_id, &mut profile, slash)?;
+
+                Self::deposit_event(Event::RiskPolicyViolated {
+                    trader_id: trader_id.clone(),
+                    violation,
+                    slashed,
+                });
+            }
+
+            let suspend = profile.status == TraderStatus::Active
+                && profile.violations >= T::MaxRiskViolations::get();
+            TraderProfiles::<T>::insert(trader_id, profile);
+            if suspend {
+                Self::do_suspend_trader(trader_id, SuspensionReason::RiskPolicyViolation)?;
+            }
+            Ok(())
+        }
+
+        // Register the attested trading accounts of the trader, approving the trader if `AutoApproveTraders`
+        pub fn finalize_trader_registration(
+            trader_id: AccountIdFor<T>,
+            trading_accounts: TradingAccounts,
+            nonce: u64,
+        ) {
+            OnChainTradingAccounts::<T>::insert(trader_id.clone(), &trading_accounts);
+            TraderAttestationNonces::<T>::insert(&trader_id, nonce);
+            Self::deposit_event(Event::TraderRegistered {
+                id: trader_id.clone(),
+            });
+
+            let auto_approve = T::AutoApproveTraders::get();
+            TraderProfiles::<T>::mutate(&trader_id, |profile| {
+                if let Some(profile) = profile {
+                    profile.status = if auto_approve {
+                        TraderStatus::Active
+                    } else {
+                        TraderStatus::AccountsConfirmed
+                    };
+                }
+            });
+            if auto_approve {
+                Self::deposit_event(Event::TraderApproved { trader_id });
+            }
+        }
 
-        //     if !db.contains(root, hash_db::EMPTY_PREFIX) {
-        //         Err(Error::<T>::FailedTradeProof)?
-        //     }
+        // `AttestorAdminOrigin` manages every attestor, `TraderRegistrarOrigin` only the ones it added,
+        // returns whether the origin is the attestor admin
+        pub fn ensure_attestor_manager(origin: OriginFor<T>) -> Result<bool, DispatchError> {
+            match T::AttestorAdminOrigin::try_origin(origin) {
+                Ok(_) => Ok(true),
+                Err(origin) => {
+                    T::TraderRegistrarOrigin::ensure_origin(origin)?;
+                    Ok(false)
+                }
+            }
+        }
 
-        //     let trie = TrieDBBuilder::<LayoutV1<H>>::new(&db, root).build();
-        //     let mut result = BTreeMap::new();
+        // Check the trading accounts were generated by a trusted attestor for the trader,
+        // the nonce must be above the one of the last registration of the trader
+        pub fn ensure_trading_accounts_attested(
+            trader_id: &AccountIdFor<T>,
+            trading_accounts: &TradingAccounts,
+            attestation: &TradingAccountsAttestation,
+        ) -> DispatchResult {
+            ensure!(
+                Attestors::<T>::get(attestation.attestor).map_or(false, |info| info.is_trusted()),
+                Error::<T>::AttestorNotTrusted
+            );
+            ensure!(
+                attestation.nonce > TraderAttestationNonces::<T>::get(trader_id),
+                Error::<T>::StaleAttestation
+            );
+            let payload = attestation_payload(trader_id, trading_accounts, attestation.nonce);
+            ensure!(
+                sp_io::crypto::sr25519_verify(
+                    &attestation.signature,
+                    &payload,
+                    &attestation.attestor
+                ),
+                Error::<T>::InvalidAttestation
+            );
+            Ok(())
+        }
 
-        //     for key in keys.into_iter() {
-        //         let value = trie
-        //             .get(key.as_ref())
-        //             .map_err(|e| Error::<T>::FailedTradeProof)?
-        //             .and_then(|val| Decode::decode(&mut &val[..]).ok());
-        //         result.insert(key.as_ref().to_vec(), value);
-        //     }
+        // Check a swap executed by the pool against the trader risk policy. The pool executes it,
+        // so a violation refuses the swap instead of slashing the bond
+        pub fn ensure_remote_swap_allowed(
+            profile: &mut TraderProfile<T>,
+            network: NetworkId,
+            venue: &DexVenue,
+            swap: &RemoteSwap,
+        ) -> DispatchResult {
+            let policy = profile.risk_policy.clone();
+            ensure!(
+                venue.network == network
+                    && (policy.allowed_venues.is_empty()
+                        || policy.allowed_venues.contains(&swap.venue)),
+                Error::<T>::VenueNotAllowed
+            );
+            let asset_out = swap.asset_out_address();
+            ensure!(
+                venue.is_asset_allowed(&asset_out)
+                    && (policy.allowed_assets.is_empty()
+                        || policy.allowed_assets.contains(&asset_out)),
+                Error::<T>::AssetNotAllowed
+            );
+            let max_position = policy.max_position.mul_floor(profile.funds_allocated);
+            ensure!(
+                swap.max_sold().saturated_into::<AssetBalance<T>>() <= max_position,
+                Error::<T>::PositionTooLarge
+            );
+
+            let now = frame_system::Pallet::<T>::block_number();
+            if now.saturating_sub(profile.period_start) >= policy.period {
+                profile.period_start = now;
+                profile.period_trades = 0;
+            }
+            ensure!(
+                policy.max_trades_per_period == 0
+                    || profile.period_trades < policy.max_trades_per_period,
+                Error::<T>::TradeLimitExceeded
+            );
+            profile.period_trades = profile.period_trades.saturating_add(1);
+            Ok(())
+        }
+
+        // Ensure the proofs were built against roots final for the target network block.
+        // Parachain heads are not read from the relay chain state yet, their roots are attested by the relayer set too
+        pub fn ensure_trusted_roots(
+            network: NetworkId,
+            network_info: &NetworkInfo,
+            proofs: &TradeExecutionProof<BlockNumberFor<T>>,
+        ) -> DispatchResult {
+            match network_info.finality {
+                FinalitySource::ConsensusProof => {
+                    let consensus_proof = proofs
+                        .consensus_proof
+                        .clone()
+                        .ok_or(Error::<T>::InvalidTxInclusion)?;
+                    ensure!(
+                        T::TradeExecutionVerifier::verify_consensus_state(
+                            network_info,
+                            consensus_proof
+                        ),
+                        Error::<T>::InvalidTxInclusion
+                    );
+                }
+                FinalitySource::RelayChain | FinalitySource::Relayers => {
+                    let roots =
+                        FinalizedNetworkRoots::<T>::get(network, proofs.target_network_blocknumber)
+                            .ok_or(Error::<T>::UntrustedStateRoot)?;
+                    ensure!(roots.anchors(proofs), Error::<T>::UntrustedStateRoot);
+                }
+            }
+            Ok(())
+        }
+
+        // Hash identifying a trade proof by its network, block and proven transaction
+        pub fn trade_proof_hash(
+            network: NetworkId,
+            proofs: &TradeExecutionProof<BlockNumberFor<T>>,
+        ) -> T::Hash {
+            T::Hashing::hash_of(&(
+                network,
+                proofs.target_network_blocknumber,
+                proofs.transaction_inclusion.transaction(),
+            ))
+        }
+
+        // Reject proofs of a block before the last verified block of the trader, they would roll its balance back,
+        // and proofs verified already, they would apply their risk violations again
+        pub fn ensure_fresh_trade_proof(
+            trader_id: &AccountIdFor<T>,
+            network: NetworkId,
+            proofs: &TradeExecutionProof<BlockNumberFor<T>>,
+        ) -> DispatchResult {
+            if let Some(last_verified) = LastVerifiedBlock::<T>::get(trader_id) {
+                ensure!(
+                    proofs.target_network_blocknumber >= last_verified,
+                    Error::<T>::StaleTradeProof
+                );
+            }
+            ensure!(
+                !ProcessedTradeProofs::<T>::contains_key(
+                    trader_id,
+                    Self::trade_proof_hash(network, proofs)
+                ),
+                Error::<T>::TradeProofAlreadyProcessed
+            );
+            Ok(())
+        }
+
+        // Record a verified trade proof, only the proofs of the last verified block of the trader are kept
+        pub fn record_trade_proof(
+            trader_id: &AccountIdFor<T>,
+            network: NetworkId,
+            proofs: &TradeExecutionProof<BlockNumberFor<T>>,
+        ) {
+            let block_number = proofs.target_network_blocknumber;
+            if LastVerifiedBlock::<T>::get(trader_id) != Some(block_number) {
+                let _ = ProcessedTradeProofs::<T>::clear_prefix(trader_id, u32::MAX, None);
+                LastVerifiedBlock::<T>::insert(trader_id, block_number);
+            }
+            ProcessedTradeProofs::<T>::insert(
+                trader_id,
+                Self::trade_proof_hash(network, proofs),
+                (),
+            );
+        }
+
+        // Record the verified balance of the trader onchain trading account and mark the pool to it
+        pub fn record_trader_balance(
+            trader_id: &AccountIdFor<T>,
+            mut profile: TraderProfile<T>,
+            balance: AssetBalance<T>,
+        ) -> DispatchResult {
+            CapitalPool::<T>::mutate(profile.asset_id.clone(), |pool| {
+                pool.unrealized_balance = pool
+                    .unrealized_balance
+                    .saturating_add(balance)
+                    .saturating_sub(profile.unrealized_balance);
+            });
+            profile.update_unrealized_balance(balance);
+            profile.last_report = frame_system::Pallet::<T>::block_number();
+
+            // stop loss
+            let drawdown = profile.drawdown();
+            if profile.status == TraderStatus::Active
+                && drawdown >= profile.risk_policy.max_drawdown
+            {
+                Self::do_liquidate_trader(
+                    trader_id,
+                    &mut profile,
+                    LiquidationReason::Drawdown(drawdown),
+                )?;
+            }
+            TraderProfiles::<T>::insert(trader_id, profile);
+            Ok(())
+        }
+
+        // Freeze the trader allocations and instruct the return of its remaining capital to the pool
+        pub fn do_liquidate_trader(
+            trader_id: &AccountIdFor<T>,
+            profile: &mut TraderProfile<T>,
+            reason: LiquidationReason,
+        ) -> DispatchResult {
+            Self::recall_trader_capital(trader_id, profile)?;
+            profile.status = TraderStatus::Liquidating;
+
+            Self::deposit_event(Event::TraderLiquidating {
+                trader_id: trader_id.clone(),
+                reason,
+            });
+            Ok(())
+        }
+
+        // Liquidate funded traders whose balance was not verified for `MaxReportAge` blocks.
+        // Checks at most `MaxDrawdownChecks` traders per block, resuming from the last checked trader
+        pub fn liquidate_stale_traders(now: BlockNumberFor<T>) -> Weight {
+            let max_checks = T::MaxDrawdownChecks::get() as usize;
+            let mut iter = match DrawdownCursor::<T>::get() {
+                Some(cursor) => TraderProfiles::<T>::iter_from(cursor.into_inner()),
+                None => TraderProfiles::<T>::iter(),
+            };
+
+            let mut checked = 0usize;
+            let mut last_checked = None;
+            let mut stale = Vec::new();
+            while checked < max_checks {
+                let Some((trader_id, profile)) = iter.next() else {
+                    break;
+                };
+                checked += 1;
+                if profile.status == TraderStatus::Active
+                    && !profile.funds_allocated.is_zero()
+                    && now.saturating_sub(profile.last_report) > T::MaxReportAge::get()
+                {
+                    stale.push((trader_id.clone(), profile));
+                }
+                last_checked = Some(trader_id);
+            }
+
+            // wrap around once every trader was checked
+            let cursor = if checked < max_checks {
+                None
+            } else {
+                last_checked.and_then(|trader_id| {
+                    StorageCursor::try_from(TraderProfiles::<T>::hashed_key_for(trader_id)).ok()
+                })
+            };
+            DrawdownCursor::<T>::set(cursor);
+
+            let liquidations = stale.len() as u64;
+            for (trader_id, mut profile) in stale {
+                // a failed recall leaves no changes of the attempt behind, the trader is retried on the next pass
+                let _ = with_storage_layer(|| -> DispatchResult {
+                    Self::do_liquidate_trader(
+                        &trader_id,
+                        &mut profile,
+                        LiquidationReason::StaleReport,
+                    )?;
+                    TraderProfiles::<T>::insert(&trader_id, profile);
+                    Ok(())
+                });
+            }
+
+            T::DbWeight::get()
+                .reads_writes(checked as u64 + 1, 1)
+                .saturating_add(T::WeightInfo::liquidate_trader().saturating_mul(liquidations))
+        }
+
+        // Query the balance of the funded traders on `XcmQuery` networks not verified for `BalanceQueryInterval` blocks,
+        // expecting the account the pool keeps for them to still hold their last recorded balance.
+        // Checks at most `MaxBalanceQueryChecks` traders per block, resuming from the last checked trader
+        pub fn query_trader_balances(now: BlockNumberFor<T>) -> Weight {
+            let max_checks = T::MaxBalanceQueryChecks::get() as usize;
+            let interval = T::BalanceQueryInterval::get();
+            let mut iter = match BalanceQueryCursor::<T>::get() {
+                Some(cursor) => TraderProfiles::<T>::iter_from(cursor.into_inner()),
+                None => TraderProfiles::<T>::iter(),
+            };
+
+            let mut checked = 0usize;
+            let mut reads = 0u64;
+            let mut last_checked = None;
+            let mut due = Vec::new();
+            while checked < max_checks {
+                let Some((trader_id, profile)) = iter.next() else {
+                    break;
+                };
+                checked += 1;
+                if profile.status == TraderStatus::Active
+                    && !profile.funds_allocated.is_zero()
+                    && now.saturating_sub(profile.last_report) >= interval
+                {
+                    reads += 2;
+                    let on_xcm_network = profile
+                        .network
+                        .and_then(|network| TargetNetworks::<T>::get(network))
+                        .map_or(false, |info| info.proof_kind == ProofKind::XcmQuery);
+                    // a query waiting for its response is not sent again before the interval
+                    let queried = LastBalanceQuery::<T>::get(&trader_id)
+                        .map_or(false, |queried_at| {
+                            now.saturating_sub(queried_at) < interval
+                        });
+                    if on_xcm_network && !queried {
+                        due.push((trader_id.clone(), profile));
+                    }
+                }
+                last_checked = Some(trader_id);
+            }
+
+            // wrap around once every trader was checked
+            let cursor = if checked < max_checks {
+                None
+            } else {
+                last_checked.and_then(|trader_id| {
+                    StorageCursor::try_from(TraderProfiles::<T>::hashed_key_for(trader_id)).ok()
+                })
+            };
+            BalanceQueryCursor::<T>::set(cursor);
+
+            let queries = due.len() as u64;
+            for (trader_id, profile) in due {
+                // a query that could not be sent leaves nothing behind and is retried on the next pass
+                let _ = with_storage_layer(|| {
+                    Self::send_balance_query(&trader_id, &profile, profile.unrealized_balance)
+                });
+            }
+
+            T::DbWeight::get()
+                .reads_writes(checked as u64 + reads + 1, 1)
+                .saturating_add(T::WeightInfo::request_balance_query(0).saturating_mul(queries))
+        }
+
+        // Query whether the account the pool keeps for the trader on its `XcmQuery` network holds `balance`
+        pub fn send_balance_query(
+            trader_id: &AccountIdFor<T>,
+            profile: &TraderProfile<T>,
+            balance: AssetBalance<T>,
+        ) -> DispatchResult {
+            ensure!(
+                !profile.funds_allocated.is_zero(),
+                Error::<T>::TraderNotFunded
+            );
+            let network = profile.network.ok_or(Error::<T>::TraderNotFunded)?;
+            let network_info =
+                TargetNetworks::<T>::get(network).ok_or(Error::<T>::NetworkNotSupported)?;
+            ensure!(
+                network_info.proof_kind == ProofKind::XcmQuery,
+                Error::<T>::BalanceQueriesNotSupported
+            );
+
+            let pool = CapitalPool::<T>::get(profile.asset_id.clone());
+            let (query_id, responder) = T::RemoteBalanceQuerier::query_balance(
+                &pool.account_id,
+                trader_id,
+                &network_info,
+                &profile.asset_id,
+                balance,
+            )?;
+            let now = <frame_system::Pallet<T>>::block_number();
+            BalanceQueries::<T>::insert(
+                query_id,
+                PendingBalanceQuery {
+                    trader_id: trader_id.clone(),
+                    network,
+                    balance,
+                    requested_at: now,
+                    responder,
+                },
+            );
+            LastBalanceQuery::<T>::insert(trader_id, now);
+            Self::deposit_event(Event::TraderBalanceQueried {
+                trader_id: trader_id.clone(),
+                query_id,
+                balance,
+            });
+            Ok(())
+        }
+
+        // Settle the trader allocation once `returned` is back in the pool account
+        pub fn do_settle_allocation(
+            trader_id: &AccountIdFor<T>,
+            returned: AssetBalance<T>,
+        ) -> DispatchResult {
+            let mut profile =
+                TraderProfiles::<T>::get(trader_id).ok_or(Error::<T>::TraderNotRegistered)?;
+            ensure!(
+                !profile.funds_allocated.is_zero(),
+                Error::<T>::TraderNotFunded
+            );
+            let asset_id = profile.asset_id.clone();
+            let allocated = profile.funds_allocated;
+
+            let pool_closed = CapitalPool::<T>::try_mutate(
+                asset_id.clone(),
+                |pool| -> Result<bool, DispatchError> {
+                    let pool_balance =
+                        <orml_tokens::Pallet<T> as MultiCurrency<AccountIdFor<T>>>::free_balance(
+                            asset_id.clone(),
+                            &pool.account_id,
+                        );
+                    // the distributed profit waiting for claims is in the pool account too
+                    ensure!(
+                        pool_balance
+                            >= pool
+                                .remaining_capital
+                                .saturating_add(pool.profit_reserve)
+                                .saturating_add(returned),
+                        Error::<T>::CapitalNotReturned
+                    );
+
+                    pool.remaining_capital = pool
+                        .remaining_capital
+                        .checked_add(&returned)
+                        .ok_or(ArithmeticError::Overflow)?;
+                    pool.total_allocated_capital =
+                        pool.total_allocated_capital.saturating_sub(allocated);
+                    pool.unrealized_balance = pool
+                        .unrealized_balance
+                        .saturating_add(returned)
+                        .saturating_sub(profile.unrealized_balance);
+
+                    let pool_closed = pool.status == PoolStatus::Closing
+                        && pool.total_allocated_capital.is_zero();
+                    if pool_closed {
+                        pool.status = PoolStatus::Closed;
+                    }
+                    Ok(pool_closed)
+                },
+            )?;
+
+            // the returned capital is the final balance of the allocation, its performance fee
+            // is charged at the end of the epoch
+            let hurdle_rate = CapitalPool::<T>::get(asset_id.clone()).params.hurdle_rate;
+            profile.settle_high_water_mark(returned, hurdle_rate);
+
+            let loss = allocated.saturating_sub(returned);
+            if !loss.is_zero() {
+                Self::apply_loss_waterfall(trader_id, &mut profile, loss)?;
+            }
+            let profit = returned.saturating_sub(allocated);
+            if !profit.is_zero() {
+                let collected = Self::collect_settlement_fees(asset_id.clone(), profit)?;
+                // what is left after fees is realized for the investors and distributed at epoch end
+                let investor_profit = profit
+                    .saturating_sub(collected)
+                    .saturating_sub(profile.allocation_fees);
+                CapitalPool::<T>::mutate(asset_id.clone(), |pool| {
+                    pool.epoch_profit = pool.epoch_profit.saturating_add(investor_profit);
+                });
+            }
+
+            profile.funds_allocated = AssetBalance::<T>::default();
+            profile.unrealized_balance = AssetBalance::<T>::default();
+            profile.peak_balance = AssetBalance::<T>::default();
+            profile.high_water_mark = AssetBalance::<T>::default();
+            profile.allocation_fees = AssetBalance::<T>::default();
+            profile.network = None;
+            // a liquidated trader needs to be reinstated before getting capital again
+            if profile.status == TraderStatus::Liquidating {
+                profile.status = TraderStatus::Suspended;
+            }
+
+            Self::deposit_event(Event::AllocationSettled {
+                trader_id: trader_id.clone(),
+                asset_id: asset_id.clone(),
+                allocated,
+                returned,
+            });
+            if pool_closed {
+                Self::deposit_event(Event::PoolClosed {
+                    asset_id: asset_id.clone(),
+                });
+            }
+
+            // returned capital fills the pending redemptions first
+            Self::process_redemption_queue(asset_id);
+
+            if profile.status == TraderStatus::Exiting {
+                return Self::do_deregister_trader(trader_id, profile);
+            }
+            TraderProfiles::<T>::insert(trader_id, profile);
+            Ok(())
+        }
 
-        //     Ok(result)
-        // }
+        // Cover a realized loss with the trader bond first, then with the insurance fund.
+        // What is left is absorbed by the investors pro rata through the pool unrealized balance
+        pub fn apply_loss_waterfall(
+            trader_id: &AccountIdFor<T>,
+            profile: &mut TraderProfile<T>,
+            loss: AssetBalance<T>,
+        ) -> DispatchResult {
+            let asset_id = profile.asset_id.clone();
+
+            let covered_by_bond = Self::slash_trader_bond(trader_id, profile, loss)?;
+            let mut remaining_loss = loss.saturating_sub(covered_by_bond);
+            if !covered_by_bond.is_zero() {
+                Self::deposit_event(Event::LossCoveredByBond {
+                    trader_id: trader_id.clone(),
+                    asset_id: asset_id.clone(),
+                    amount: covered_by_bond,
+                });
+            }
+            if remaining_loss.is_zero() {
+                return Ok(());
+            }
+
+            let insurance_account = Self::generate_insurance_account(asset_id.clone());
+            let insurance_balance =
+                <orml_tokens::Pallet<T> as MultiCurrency<AccountIdFor<T>>>::free_balance(
+                    asset_id.clone(),
+                    &insurance_account,
+                );
+            let covered_by_insurance = remaining_loss.min(insurance_balance);
+            if !covered_by_insurance.is_zero() {
+                let pool_account = Self::generate_pool_account(Some(asset_id.clone()));
+                Self::transfer_from_pool(
+                    insurance_account,
+                    pool_account,
+                    asset_id.clone(),
+                    covered_by_insurance,
+                )?;
+                CapitalPool::<T>::mutate(asset_id.clone(), |pool| {
+                    pool.remaining_capital =
+                        pool.remaining_capital.saturating_add(covered_by_insurance);
+                    pool.unrealized_balance =
+                        pool.unrealized_balance.saturating_add(covered_by_insurance);
+                });
+                remaining_loss = remaining_loss.saturating_sub(covered_by_insurance);
+
+                Self::deposit_event(Event::LossCoveredByInsurance {
+                    asset_id: asset_id.clone(),
+                    amount: covered_by_insurance,
+                });
+            }
+
+            // the pool unrealized balance already reflects the loss, investors redeem less per deposit
+            if !remaining_loss.is_zero() {
+                Self::deposit_event(Event::LossAbsorbedByInvestors {
+                    asset_id,
+                    amount: remaining_loss,
+                });
+            }
+            Ok(())
+        }
+
+        // Take the pool protocol fee out of a realized profit. The insurance fund receives
+        // `InsuranceFeeCut` of the fee and `InsuranceProfitCut` of the profit left to the pool
+        pub fn collect_settlement_fees(
+            asset_id: T::CurrencyId,
+            profit: AssetBalance<T>,
+        ) -> Result<AssetBalance<T>, DispatchError> {
+            let pool = CapitalPool::<T>::get(asset_id.clone());
+            let protocol_fee = pool.params.fee.mul_floor(profit);
+            let insurance_fee = T::InsuranceFeeCut::get().mul_floor(protocol_fee);
+            let insurance_profit =
+                T::InsuranceProfitCut::get().mul_floor(profit.saturating_sub(protocol_fee));
+            let to_insurance = insurance_fee.saturating_add(insurance_profit);
+            let to_treasury = protocol_fee.saturating_sub(insurance_fee);
+
+            if !to_treasury.is_zero() {
+                Self::transfer_from_pool(
+                    pool.account_id.clone(),
+                    T::TreasuryAccount::get(),
+                    asset_id.clone(),
+                    to_treasury,
+                )?;
+                Self::deposit_event(Event::ProtocolFeeCollected {
+                    asset_id: asset_id.clone(),
+                    amount: to_treasury,
+                });
+            }
+            if !to_insurance.is_zero() {
+                Self::transfer_from_pool(
+                    pool.account_id.clone(),
+                    Self::generate_insurance_account(asset_id.clone()),
+                    asset_id.clone(),
+                    to_insurance,
+                )?;
+                Self::deposit_event(Event::InsuranceFunded {
+                    asset_id: asset_id.clone(),
+                    amount: to_insurance,
+                });
+            }
+
+            let collected = to_treasury.saturating_add(to_insurance);
+            CapitalPool::<T>::mutate(asset_id, |pool| {
+                pool.remaining_capital = pool.remaining_capital.saturating_sub(collected);
+                pool.unrealized_balance = pool.unrealized_balance.saturating_sub(collected);
+            });
+            Ok(collected)
+        }
+
+        pub fn insurance_fund_balance(asset_id: T::CurrencyId) -> AssetBalance<T> {
+            <orml_tokens::Pallet<T> as MultiCurrency<AccountIdFor<T>>>::free_balance(
+                asset_id.clone(),
+                &Self::generate_insurance_account(asset_id),
+            )
+        }
+
+        // Insurance fund balance relative to the capital at risk with traders
+        pub fn insurance_coverage_ratio(asset_id: T::CurrencyId) -> Permill {
+            let allocated = CapitalPool::<T>::get(asset_id.clone()).total_allocated_capital;
+            if allocated.is_zero() {
+                return Permill::one();
+            }
+            Permill::from_rational(
+                Self::insurance_fund_balance(asset_id).min(allocated),
+                allocated,
+            )
+        }
+
+        // Charge the trader performance fee on new highs only: the gain of the open allocation above
+        // its high water mark raised by the pool hurdle rate, and the gain of the allocations settled
+        // during the epoch. The fee is owed to the trader by the pool and leaves the pool unrealized balance
+        pub fn crystallize_performance_fee(
+            trader_id: &AccountIdFor<T>,
+            profile: &mut TraderProfile<T>,
+        ) -> AssetBalance<T> {
+            let hurdle_rate = CapitalPool::<T>::get(profile.asset_id.clone())
+                .params
+                .hurdle_rate;
+            let open_gain = if profile.funds_allocated.is_zero() {
+                AssetBalance::<T>::default()
+            } else {
+                let hurdle = profile
+                    .high_water_mark
+                    .saturating_add(hurdle_rate.mul_floor(profile.high_water_mark));
+                let gain = profile.unrealized_balance.saturating_sub(hurdle);
+                if !gain.is_zero() {
+                    profile.high_water_mark = profile.unrealized_balance;
+                }
+                gain
+            };
+            let settled_gain = core::mem::take(&mut profile.settled_gain);
+
+            let rate = Permill::from_percent(T::TraderPoolOwnership::get().into());
+            let open_fee = rate.mul_floor(open_gain);
+            let settled_fee = rate.mul_floor(settled_gain);
+            let fee = open_fee.saturating_add(settled_fee);
+            if fee.is_zero() {
+                return fee;
+            }
+
+            profile.accrued_fees = profile.accrued_fees.saturating_add(fee);
+            profile.allocation_fees = profile.allocation_fees.saturating_add(open_fee);
+            CapitalPool::<T>::mutate(profile.asset_id.clone(), |pool| {
+                pool.unrealized_balance = pool.unrealized_balance.saturating_sub(fee);
+                pool.trader_fees_owed = pool.trader_fees_owed.saturating_add(fee);
+                pool.epoch_performance_fees = pool.epoch_performance_fees.saturating_add(fee);
+                // the settled gain is already counted in the profit realized for the investors
+                pool.epoch_profit = pool.epoch_profit.saturating_sub(settled_fee);
+            });
+
+            Self::deposit_event(Event::PerformanceFeeCrystallized {
+                trader_id: trader_id.clone(),
+                asset_id: profile.asset_id.clone(),
+                fee,
+                high_water_mark: profile.high_water_mark,
+            });
+            fee
+        }
+
+        // Credit the profit distributed to the pool since the last accrual to the investor deposit
+        pub fn accrue_investor_rewards(
+            investor_id: &AccountIdFor<T>,
+            asset_id: &T::CurrencyId,
+            reward_per_share: FixedU128,
+            deposited: AssetBalance<T>,
+        ) {
+            InvestorRewards::<T>::mutate(investor_id, asset_id, |reward| {
+                let earned: AssetBalance<T> = reward_per_share
+                    .saturating_sub(reward.reward_per_share_paid)
+                    .saturating_mul_int(deposited.saturated_into::<u128>())
+                    .saturated_into();
+                reward.pending = reward.pending.saturating_add(earned);
+                reward.reward_per_share_paid = reward_per_share;
+            });
+        }
+
+        // Active and paused pools can only be redeemed once the investor withdraw period elapsed,
+        // the `shares` to redeem must not be locked by a deposit made within the withdraw period
+        pub fn ensure_withdraw_period_elapsed(
+            pool: &InvestorCapitalPool<T>,
+            position: &InvestorPosition<T>,
+            shares: AssetBalance<T>,
+        ) -> DispatchResult {
+            if matches!(pool.status, PoolStatus::Active | PoolStatus::Paused) {
+                let now = <frame_system::Pallet<T>>::block_number();
+                ensure!(
+                    now >= position.opened_at.saturating_add(position.withdraw_period),
+                    Error::<T>::WithdrawPeriodNotElapsed
+                );
+                ensure!(
+                    shares
+                        <= position
+                            .deposited
+                            .saturating_sub(position.locked_shares(now)),
+                    Error::<T>::WithdrawPeriodNotElapsed
+                );
+            }
+            Ok(())
+        }
+
+        // Redeem `amount` deposited capital of the investor for its share of the pool value,
+        // paid from the pool unallocated capital
+        pub fn redeem_deposit(
+            investor_id: &AccountIdFor<T>,
+            asset_id: &T::CurrencyId,
+            pool: &mut InvestorCapitalPool<T>,
+            position: &mut InvestorPosition<T>,
+            amount: AssetBalance<T>,
+        ) -> Result<AssetBalance<T>, DispatchError> {
+            Self::accrue_investor_rewards(
+                investor_id,
+                asset_id,
+                pool.reward_per_share,
+                position.deposited,
+            );
+            position.withdraw_capital(amount)?;
+            // burn the redeemed pool shares
+            if let Some(share_asset_id) = pool.share_asset_id.clone() {
+                <orml_tokens::Pallet<T> as MultiCurrency<AccountIdFor<T>>>::withdraw(
+                    share_asset_id,
+                    investor_id,
+                    amount,
+                )?;
+            }
+
+            let payout = pool.redemption_value(amount);
+            ensure!(
+                pool.remaining_capital >= payout,
+                Error::<T>::InsufficientPoolLiquidity
+            );
+            pool.redeem_capital(amount, payout)?;
+            Self::transfer_from_pool(
+                pool.account_id.clone(),
+                investor_id.clone(),
+                asset_id.clone(),
+                payout,
+            )?;
+            Ok(payout)
+        }
+
+        // Load the investor position and bring it in line with the pool shares held by the investor.
+        // Shares that moved without the transfer hook, e.g. cross chain, only earn profit from now on
+        pub fn sync_investor_position(
+            investor_id: &AccountIdFor<T>,
+            asset_id: &T::CurrencyId,
+            pool: &InvestorCapitalPool<T>,
+        ) -> InvestorPosition<T> {
+            let mut position = InvestorPositions::<T>::get(investor_id, asset_id.clone())
+                .unwrap_or_else(
+                    || InvestorPosition::new(<frame_system::Pallet<T>>::block_number()),
+                );
+            let shares = match pool.share_asset_id.clone() {
+                Some(share_asset_id) => <orml_tokens::Pallet<T> as MultiCurrency<
+                    AccountIdFor<T>,
+                >>::total_balance(
+                    share_asset_id, investor_id
+                ),
+                None => position.deposited,
+            };
+            Self::accrue_investor_rewards(
+                investor_id,
+                asset_id,
+                pool.reward_per_share,
+                position.deposited.min(shares),
+            );
+            position.deposited = shares;
+            position
+        }
+
+        // Store the position and keep track of the investor pools, a fully redeemed position frees one of them
+        pub fn store_investor_position(
+            investor_id: &AccountIdFor<T>,
+            asset_id: T::CurrencyId,
+            position: InvestorPosition<T>,
+        ) -> DispatchResult {
+            if position.deposited.is_zero() {
+                InvestorPositions::<T>::remove(investor_id, asset_id);
+                return Ok(());
+            }
+            if !InvestorPositions::<T>::contains_key(investor_id, asset_id.clone()) {
+                // the open positions are bounded by `MaxInvestorPools`
+                let open_pools = InvestorPositions::<T>::iter_key_prefix(investor_id).count();
+                ensure!(
+                    open_pools < T::MaxInvestorPools::get() as usize,
+                    Error::<T>::TooManyInvestorPools
+                );
+            }
+            InvestorPositions::<T>::insert(investor_id, asset_id, position);
+            Ok(())
+        }
+
+        // Storage version 1: clear the investor profiles and their counter left by the former layout,
+        // the pools of an investor are the keys of its `InvestorPositions`
+        pub fn remove_investor_profiles() -> Weight {
+            if Pallet::<T>::on_chain_storage_version() >= 1 {
+                return T::DbWeight::get().reads(1);
+            }
+            let pallet = <Pallet<T> as PalletInfoAccess>::name().as_bytes();
+            let removed =
+                migration::clear_storage_prefix(pallet, b"InvestorProfiles", b"", None, None);
+            let _ = migration::clear_storage_prefix(
+                pallet,
+                b"CounterForInvestorProfiles",
+                b"",
+                None,
+                None,
+            );
+            Pallet::<T>::current_storage_version().put::<Pallet<T>>();
+            T::DbWeight::get().reads_writes(
+                u64::from(removed.loops).saturating_add(1),
+                u64::from(removed.unique).saturating_add(2),
+            )
+        }
+
+        // Deposit capital of the investor into the pool of `asset_id` and mint the pool shares, returned.
+        // Shared by `register_investor`, the deposits arriving over XCM and the vault precompile
+        pub fn deposit_capital(
+            investor: AccountIdFor<T>,
+            asset_id: T::CurrencyId,
+            capital_amount: AssetBalance<T>,
+        ) -> Result<AssetBalance<T>, DispatchError> {
+            ensure!(
+                CapitalPool::<T>::contains_key(asset_id.clone()),
+                Error::<T>::AssetPoolNotSupported
+            );
+            T::DepositFilter::can_deposit(&investor, &asset_id, capital_amount).map_err(
+                |rejection| match rejection {
+                    DepositRejection::NotAllowlisted => Error::<T>::InvestorNotAllowlisted,
+                    DepositRejection::Blocked => Error::<T>::InvestorBlocked,
+                },
+            )?;
+
+            let shares = CapitalPool::<T>::try_mutate(asset_id.clone(), |pool| {
+                pool.ensure_active()?;
+                ensure!(
+                    !capital_amount.is_zero() && capital_amount >= pool.params.min_deposit,
+                    Error::<T>::InsufficientDeposit
+                );
+                // shares of a pool that lost all its value cannot be priced
+                ensure!(
+                    pool.total_capital.is_zero() || !pool.unrealized_balance.is_zero(),
+                    Error::<T>::PoolValueDepleted
+                );
+                // shares are minted at the current pool value, so the deposit redeems for what was paid in
+                let shares = pool.capital_for_value(capital_amount, Rounding::Down);
+                ensure!(!shares.is_zero(), Error::<T>::InsufficientDeposit);
+                // the caps are in pool asset, checked against the pool value and the value of the position
+                if let Some(max_capital) = pool.params.max_capital {
+                    let pool_value = pool
+                        .unrealized_balance
+                        .checked_add(&capital_amount)
+                        .ok_or(ArithmeticError::Overflow)?;
+                    ensure!(pool_value <= max_capital, Error::<T>::PoolCapExceeded);
+                }
+                // update the pool & investor profile with correct ownership
+                // transfer from investor to pool
+                let pool_id_source = T::Lookup::unlookup(pool.account_id.clone());
+                // update investor position
+                let now = <frame_system::Pallet<T>>::block_number();
+                let mut position = Self::sync_investor_position(&investor, &asset_id, pool);
+                if let Some(max_investor_capital) = pool.params.max_investor_capital {
+                    let position_value = pool
+                        .redemption_value(position.deposited)
+                        .checked_add(&capital_amount)
+                        .ok_or(ArithmeticError::Overflow)?;
+                    ensure!(
+                        position_value <= max_investor_capital,
+                        Error::<T>::InvestorCapExceeded
+                    );
+                }
+                // update pool
+                pool.add_capital(shares, capital_amount)?;
+                position.deposit(shares, now);
+                // actual depositing of asset
+                <orml_tokens::Pallet<T>>::transfer_keep_alive(
+                    RawOrigin::Signed(investor.clone()).into(),
+                    pool_id_source,
+                    asset_id.clone(),
+                    capital_amount,
+                )
+                .map_err(|_| Error::<T>::FailedToTransferCapitalToPool)?;
+                // mint the pool shares
+                if let Some(share_asset_id) = pool.share_asset_id.clone() {
+                    <orml_tokens::Pallet<T> as MultiCurrency<AccountIdFor<T>>>::deposit(
+                        share_asset_id,
+                        &investor,
+                        shares,
+                    )?;
+                }
+                Self::store_investor_position(&investor, asset_id.clone(), position)?;
+                Ok::<_, DispatchError>(shares)
+            })?;
+            Self::deposit_event(Event::CapitalDeposited {
+                investor_id: investor,
+                asset_id: asset_id.clone(),
+                amount: capital_amount,
+                shares,
+            });
+
+            // new capital fills the pending redemptions first
+            Self::process_redemption_queue(asset_id);
+            Ok(shares)
+        }
+
+        // Deposit capital arriving over XCM, the assets are already credited to the investor account.
+        // Any sender may name any investor: the deposit only moves assets the investor received into a position
+        // of its own, without touching its withdraw period, so it is accepted like a plain transfer to the investor
+        pub fn deposit_xcm_capital(
+            investor: AccountIdFor<T>,
+            asset_id: T::CurrencyId,
+            amount: AssetBalance<T>,
+        ) {
+            match with_storage_layer(|| {
+                Self::deposit_capital(investor.clone(), asset_id.clone(), amount)
+            }) {
+                Ok(_) => Self::deposit_event(Event::XcmDepositAccepted {
+                    investor_id: investor,
+                    asset_id,
+                    amount,
+                }),
+                Err(error) => Self::deposit_event(Event::XcmDepositFailed {
+                    investor_id: investor,
+                    asset_id,
+                    amount,
+                    error,
+                }),
+            }
+        }
+
+        // Register the share token of the pool of `asset_id` in the asset registry
+        pub fn register_share_asset(
+            asset_id: &T::CurrencyId,
+        ) -> Result<T::CurrencyId, DispatchError> {
+            let share_asset_id = T::PoolShares::share_asset_id(asset_id);
+            let metadata =
+                T::PoolShares::share_metadata(asset_id).ok_or(Error::<T>::AssetMetadataNotFound)?;
+            orml_asset_registry::module::Pallet::<T>::do_register_asset_without_asset_processor(
+                metadata,
+                share_asset_id.clone(),
+            )?;
+            PoolShareAssets::<T>::insert(share_asset_id.clone(), asset_id.clone());
+            Ok(share_asset_id)
+        }
+
+        // Move the position behind `amount` pool shares along with the share tokens.
+        // The receiver inherits the withdraw period of the sender when it is later than its own,
+        // and the deposit locks of the shares moved
+        pub fn transfer_pool_shares(
+            share_asset_id: T::CurrencyId,
+            from: &AccountIdFor<T>,
+            to: &AccountIdFor<T>,
+            amount: AssetBalance<T>,
+        ) -> DispatchResult {
+            let Some(asset_id) = PoolShareAssets::<T>::get(share_asset_id) else {
+                return Ok(());
+            };
+            if from == to || amount.is_zero() {
+                return Ok(());
+            }
+
+            let pool = CapitalPool::<T>::get(asset_id.clone());
+            let mut sender = Self::sync_investor_position(from, &asset_id, &pool);
+            let mut receiver = Self::sync_investor_position(to, &asset_id, &pool);
+            let now = <frame_system::Pallet<T>>::block_number();
+            let locked = sender.take_locked_shares(amount, now);
+            sender.withdraw_capital(amount)?;
+            receiver.receive_shares(amount, sender.opened_at);
+            for record in locked {
+                receiver.lock_shares(record.amount, record.block_number, now);
+            }
+            Self::store_investor_position(from, asset_id.clone(), sender)?;
+            Self::store_investor_position(to, asset_id, receiver)
+        }
+
+        // Deposited capital of every position of the investor
+        pub fn investor_positions(
+            investor_id: &AccountIdFor<T>,
+        ) -> Vec<(T::CurrencyId, AssetBalance<T>)> {
+            InvestorPositions::<T>::iter_prefix(investor_id)
+                .map(|(asset_id, position)| (asset_id, position.deposited))
+                .collect()
+        }
+
+        // Deposited capital of the investor locked in redemption requests
+        pub fn queued_redemptions(
+            investor_id: &AccountIdFor<T>,
+            asset_id: T::CurrencyId,
+        ) -> AssetBalance<T> {
+            RedemptionQueues::<T>::get(asset_id)
+                .iter()
+                .filter(|request| &request.investor_id == investor_id)
+                .fold(AssetBalance::<T>::default(), |locked, request| {
+                    locked.saturating_add(request.shares)
+                })
+        }
+
+        // Fill the redemption requests of the pool in order with its unallocated capital.
+        // The first request that cannot be fully filled is partially filled and stops the queue.
+        // A request that fails to be filled is dropped with its shares unlocked, so it can not hold up the queue
+        pub fn process_redemption_queue(asset_id: T::CurrencyId) {
+            let mut queue = RedemptionQueues::<T>::get(asset_id.clone());
+            if queue.is_empty() {
+                return;
+            }
+            let mut pool = CapitalPool::<T>::get(asset_id.clone());
+
+            while let Some(request) = queue.first_mut() {
+                let mut filled_pool = pool.clone();
+                let filled = with_storage_layer(|| {
+                    Self::fill_redemption(&asset_id, &mut filled_pool, request)
+                });
+                match filled {
+                    Ok(shares) if shares.is_zero() => break,
+                    Ok(shares) => {
+                        pool = filled_pool;
+                        request.shares = request.shares.saturating_sub(shares);
+                        if !request.shares.is_zero() {
+                            break;
+                        }
+                    }
+                    Err(error) => {
+                        if let Some(share_asset_id) = pool.share_asset_id.clone() {
+                            <orml_tokens::Pallet<T> as MultiReservableCurrency<
+                                AccountIdFor<T>,
+                            >>::unreserve(
+                                share_asset_id, &request.investor_id, request.shares
+                            );
+                        }
+                        Self::deposit_event(Event::RedemptionFailed {
+                            request_id: request.id,
+                            investor_id: request.investor_id.clone(),
+                            asset_id: asset_id.clone(),
+                            shares: request.shares,
+                            error,
+                        });
+                    }
+                }
+                queue.remove(0);
+            }
+
+            CapitalPool::<T>::insert(asset_id.clone(), pool);
+            RedemptionQueues::<T>::insert(asset_id, queue);
+        }
+
+        // Fill the redemption request as far as the pool unallocated capital allows, returns the filled shares
+        fn fill_redemption(
+            asset_id: &T::CurrencyId,
+            pool: &mut InvestorCapitalPool<T>,
+            request: &RedemptionRequest<AccountIdFor<T>, AssetBalance<T>, BlockNumberFor<T>>,
+        ) -> Result<AssetBalance<T>, DispatchError> {
+            let full_payout = pool.redemption_value(request.shares);
+            let shares = if pool.remaining_capital >= full_payout {
+                request.shares
+            } else {
+                multiply_by_rational_with_rounding(
+                    request.shares.saturated_into::<u128>(),
+                    pool.remaining_capital.saturated_into::<u128>(),
+                    full_payout.saturated_into::<u128>(),
+                    Rounding::Down,
+                )
+                .unwrap_or_default()
+                .saturated_into()
+            };
+            if shares.is_zero() {
+                return Ok(shares);
+            }
+
+            let mut position = Self::sync_investor_position(&request.investor_id, asset_id, pool);
+            if let Some(share_asset_id) = pool.share_asset_id.clone() {
+                <orml_tokens::Pallet<T> as MultiReservableCurrency<AccountIdFor<T>>>::unreserve(
+                    share_asset_id,
+                    &request.investor_id,
+                    shares,
+                );
+            }
+            let payout =
+                Self::redeem_deposit(&request.investor_id, asset_id, pool, &mut position, shares)?;
+            Self::store_investor_position(&request.investor_id, asset_id.clone(), position)?;
+
+            Self::deposit_event(Event::RedemptionFilled {
+                request_id: request.id,
+                investor_id: request.investor_id.clone(),
+                asset_id: asset_id.clone(),
+                shares,
+                payout,
+                remaining_shares: request.shares.saturating_sub(shares),
+            });
+            Ok(shares)
+        }
+
+        pub fn redemption_queue(
+            asset_id: T::CurrencyId,
+        ) -> Vec<(RedemptionId, AccountIdFor<T>, AssetBalance<T>)> {
+            RedemptionQueues::<T>::get(asset_id)
+                .into_iter()
+                .map(|request| (request.id, request.investor_id, request.shares))
+                .collect()
+        }
+
+        // Profit the investor can claim from the pool, including the profit not accrued yet
+        pub fn claimable_profit(
+            investor_id: &AccountIdFor<T>,
+            asset_id: T::CurrencyId,
+        ) -> AssetBalance<T> {
+            let reward = InvestorRewards::<T>::get(investor_id, asset_id.clone());
+            let deposited = InvestorPositions::<T>::get(investor_id, asset_id.clone())
+                .map(|position| position.deposited)
+                .unwrap_or_default();
+            let unaccrued: AssetBalance<T> = CapitalPool::<T>::get(asset_id)
+                .reward_per_share
+                .saturating_sub(reward.reward_per_share_paid)
+                .saturating_mul_int(deposited.saturated_into::<u128>())
+                .saturated_into();
+            reward.pending.saturating_add(unaccrued)
+        }
+
+        pub fn claimable_performance_fees(trader_id: &AccountIdFor<T>) -> AssetBalance<T> {
+            TraderProfiles::<T>::get(trader_id)
+                .map(|profile| profile.accrued_fees)
+                .unwrap_or_default()
+        }
+
+        // Drive the epoch settlement: once `EpochLength` blocks passed, crystallize the performance
+        // fees of every funded trader, then close the epoch of every pool.
+        // At most `MaxEpochSteps` traders or pools are processed per block
+        pub fn process_epoch(now: BlockNumberFor<T>) -> Weight {
+            let db_weight = T::DbWeight::get();
+            let phase = match EpochSettlement::<T>::get() {
+                Some(phase) => phase,
+                None => {
+                    if now.saturating_sub(EpochStart::<T>::get()) < T::EpochLength::get() {
+                        return db_weight.reads(2);
+                    }
+                    EpochPhase::Traders(None)
+                }
+            };
+            let max_steps = T::MaxEpochSteps::get() as usize;
+
+            let (steps, next_phase) = match phase {
+                EpochPhase::Traders(cursor) => {
+                    let traders: Vec<_> = match cursor {
+                        Some(cursor) => TraderProfiles::<T>::iter_from(cursor.into_inner()),
+                        None => TraderProfiles::<T>::iter(),
+                    }
+                    .take(max_steps)
+                    .collect();
+
+                    let steps = traders.len();
+                    let last_key = traders
+                        .last()
+                        .map(|(trader_id, _)| TraderProfiles::<T>::hashed_key_for(trader_id));
+                    for (trader_id, mut profile) in traders {
+                        if profile.funds_allocated.is_zero() && profile.settled_gain.is_zero() {
+                            continue;
+                        }
+                        Self::crystallize_performance_fee(&trader_id, &mut profile);
+                        TraderProfiles::<T>::insert(&trader_id, profile);
+                    }
+
+                    let next_phase =
+                        match last_key.and_then(|key| StorageCursor::try_from(key).ok()) {
+                            Some(cursor) if steps == max_steps => EpochPhase::Traders(Some(cursor)),
+                            _ => EpochPhase::Pools(None),
+                        };
+                    (steps, Some(next_phase))
+                }
+                EpochPhase::Pools(cursor) => {
+                    let pools: Vec<_> = match cursor {
+                        Some(cursor) => CapitalPool::<T>::iter_from(cursor.into_inner()),
+                        None => CapitalPool::<T>::iter(),
+                    }
+                    .take(max_steps)
+                    .collect();
+
+                    let steps = pools.len();
+                    let last_key = pools
+                        .last()
+                        .map(|(asset_id, _)| CapitalPool::<T>::hashed_key_for(asset_id));
+                    let epoch = CurrentEpoch::<T>::get();
+                    for (asset_id, pool) in pools {
+                        Self::close_pool_epoch(asset_id, pool, epoch, now);
+                    }
+
+                    let next_phase = match last_key
+                        .and_then(|key| StorageCursor::try_from(key).ok())
+                    {
+                        Some(cursor) if steps == max_steps => Some(EpochPhase::Pools(Some(cursor))),
+                        _ => None,
+                    };
+                    if next_phase.is_none() {
+                        CurrentEpoch::<T>::put(epoch.saturating_add(1));
+                        EpochStart::<T>::put(now);
+                        Self::deposit_event(Event::EpochClosed { epoch });
+                    }
+                    (steps, next_phase)
+                }
+            };
+            EpochSettlement::<T>::set(next_phase);
+
+            let steps = steps as u64;
+            db_weight.reads_writes(steps * 2 + 3, steps * 2 + 3)
+        }
+
+        // Snapshot the pool NAV and distribute the profit realized during the epoch to the investors
+        pub fn close_pool_epoch(
+            asset_id: T::CurrencyId,
+            mut pool: InvestorCapitalPool<T>,
+            epoch: EpochIndex,
+            now: BlockNumberFor<T>,
+        ) {
+            let mut distributed = AssetBalance::<T>::default();
+            if let Some(profit_per_share) = FixedU128::checked_from_rational(
+                pool.epoch_profit.saturated_into::<u128>(),
+                pool.total_capital.saturated_into::<u128>(),
+            ) {
+                distributed = pool.epoch_profit;
+                pool.reward_per_share = pool.reward_per_share.saturating_add(profit_per_share);
+                // distributed profit is set aside for claims and no longer part of the pool value
+                pool.remaining_capital = pool.remaining_capital.saturating_sub(distributed);
+                pool.unrealized_balance = pool.unrealized_balance.saturating_sub(distributed);
+                pool.profit_reserve = pool.profit_reserve.saturating_add(distributed);
+            }
+
+            let record = EpochRecord {
+                nav: pool.unrealized_balance,
+                total_capital: pool.total_capital,
+                distributed_profit: distributed,
+                performance_fees: pool.epoch_performance_fees,
+                reward_per_share: pool.reward_per_share,
+                closed_at: now,
+            };
+            pool.epoch_profit = AssetBalance::<T>::default();
+            pool.epoch_performance_fees = AssetBalance::<T>::default();
+            CapitalPool::<T>::insert(asset_id.clone(), pool);
+            PoolEpochs::<T>::insert(asset_id.clone(), epoch, record);
+
+            Self::deposit_event(Event::PoolEpochClosed {
+                asset_id,
+                epoch,
+                nav: record.nav,
+                distributed_profit: distributed,
+            });
+        }
+
+        // Remove the relayer from the set and keep the threshold reachable by the remaining relayers
+        pub fn do_remove_relayer(relayer: &AccountIdFor<T>) -> DispatchResult {
+            let remaining = Relayers::<T>::try_mutate(|relayers| -> Result<u32, DispatchError> {
+                let index = relayers
+                    .iter()
+                    .position(|registered| registered == relayer)
+                    .ok_or(Error::<T>::RelayerNotRegistered)?;
+                relayers.remove(index);
+                Ok(relayers.len() as u32)
+            })?;
+            RelayerReports::<T>::remove(relayer);
+            Self::deposit_event(Event::RelayerRemoved {
+                relayer: relayer.clone(),
+            });
+
+            let threshold = RelayerThreshold::<T>::get();
+            if remaining > 0 && threshold > remaining {
+                RelayerThreshold::<T>::put(remaining);
+                Self::deposit_event(Event::RelayerThresholdSet {
+                    threshold: remaining,
+                });
+            }
+            Ok(())
+        }
     }
 
     /// Tracking Trader activities
-    /// `trading account`: The linked on chain trading account per trader sovereign account
+    /// `asset_id`: The pool asset the trader applied to trade
+    /// `status`: Lifecycle state of the trader
     /// `bonded amount`: Amount placed into hold by the trader signifying conviction
+    /// `network`: Network the allocated funds are deployed on
     /// `funds allocated`: Total amount allocated to trader from pool
+    /// `unrealized_balance`: Last verified balance of the on chain trading account
     /// `credits`: Metrics to measure trader performance
+    /// `risk_policy`: Constraints on the trades executed with the allocated capital
+    /// `period_start`, `period_trades`: Trades verified in the current risk policy period
+    /// `violations`: Number of risk policy violations recorded
+    /// `peak_balance`: Highest verified balance of the open allocation, drawdowns are measured from it
+    /// `last_report`: Block of the last balance verification
+    /// `high_water_mark`: Highest balance of the open allocation a performance fee was charged on
+    /// `settled_gain`: Gain above the high water mark of allocations settled during the epoch
+    /// `loss_carryforward`: Loss below the high water mark of settled allocations, to recover before new fees
+    /// `accrued_fees`: Performance fees owed to the trader by the pool
+    /// `allocation_fees`: Performance fees charged on the open allocation
     #[derive(Encode, Decode, Clone, RuntimeDebug, MaxEncodedLen, TypeInfo)]
     #[scale_info(skip_type_params(T))]
     pub struct TraderProfile<T: Config> {
-        pub trading_account: AccountIdFor<T>,
         pub asset_id: T::CurrencyId,
+        pub status: TraderStatus,
         pub bonded_amount: TraderBond<T>,
+        pub network: Option<NetworkId>,
         pub funds_allocated: AssetBalance<T>, //BalanceOf<T>,
         pub unrealized_balance: AssetBalance<T>,
         pub credits: u8,
         pub trades_executed: u16,
+        pub risk_policy: RiskPolicy<BlockNumberFor<T>>,
+        pub period_start: BlockNumberFor<T>,
+        pub period_trades: u16,
+        pub violations: u8,
+        pub peak_balance: AssetBalance<T>,
+        pub last_report: BlockNumberFor<T>,
+        pub high_water_mark: AssetBalance<T>,
+        pub settled_gain: AssetBalance<T>,
+        pub loss_carryforward: AssetBalance<T>,
+        pub accrued_fees: AssetBalance<T>,
+        pub allocation_fees: AssetBalance<T>,
     }
 
     impl<T: Config> TraderProfile<T> {
         pub fn update_unrealized_balance(&mut self, balance: AssetBalance<T>) {
             self.unrealized_balance = balance;
-            self.trades_executed += 1
+            self.peak_balance = self.peak_balance.max(balance);
+            self.trades_executed = self.trades_executed.saturating_add(1)
+        }
+
+        // allocated capital sits in the trading account until trades are verified,
+        // the allocation is bounded by the pool capital
+        pub fn deposit_allocated_funds(&mut self, balance: AssetBalance<T>) {
+            // a new allocation has to recover the losses of the previous ones before paying fees
+            if self.funds_allocated.is_zero() {
+                self.high_water_mark = core::mem::take(&mut self.loss_carryforward);
+            }
+            self.funds_allocated = self.funds_allocated.saturating_add(balance);
+            self.unrealized_balance = self.unrealized_balance.saturating_add(balance);
+            self.peak_balance = self.peak_balance.saturating_add(balance);
+            self.high_water_mark = self.high_water_mark.saturating_add(balance)
         }
 
-        pub fn deposit_allocated_funds(&mut self, balance: AssetBalance<T>) {
-            self.funds_allocated += balance
-        }
+        // Carry the final balance of a settled allocation against its high water mark
+        pub fn settle_high_water_mark(&mut self, balance: AssetBalance<T>, hurdle_rate: Permill) {
+            let hurdle = self
+                .high_water_mark
+                .saturating_add(hurdle_rate.mul_floor(self.high_water_mark));
+            self.settled_gain = self
+                .settled_gain
+                .saturating_add(balance.saturating_sub(hurdle));
+            self.loss_carryforward = self
+                .loss_carryforward
+                .saturating_add(self.high_water_mark.saturating_sub(balance));
+        }
+
+        // loss of the open allocation from its peak balance
+        pub fn drawdown(&self) -> Permill {
+            if self.peak_balance.is_zero() {
+                return Permill::zero();
+            }
+            Permill::from_rational(
+                self.peak_balance.saturating_sub(self.unrealized_balance),
+                self.peak_balance,
+            )
+        }
+
+        pub fn new(asset_id: T::CurrencyId, bond: AssetBalance<T>) -> Self {
+            Self {
+                asset_id,
+                status: TraderStatus::Applied,
+                bonded_amount: TraderBond {
+                    amount: bond,
+                    stake: false,
+                },
+                network: None,
+                funds_allocated: AssetBalance::<T>::default(),
+                unrealized_balance: AssetBalance::<T>::default(),
+                credits: 0,
+                trades_executed: 0,
+                risk_policy: RiskPolicy::default(),
+                period_start: BlockNumberFor::<T>::default(),
+                period_trades: 0,
+                violations: 0,
+                peak_balance: AssetBalance::<T>::default(),
+                last_report: BlockNumberFor::<T>::default(),
+                high_water_mark: AssetBalance::<T>::default(),
+                settled_gain: AssetBalance::<T>::default(),
+                loss_carryforward: AssetBalance::<T>::default(),
+                accrued_fees: AssetBalance::<T>::default(),
+                allocation_fees: AssetBalance::<T>::default(),
+            }
+        }
+    }
+
+    /// Lifecycle of a trader
+    #[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub enum TraderStatus {
+        /// Bond placed, waiting for the relayers to confirm the generated trading accounts
+        Applied,
+        /// Trading accounts confirmed, waiting for approval by `TraderAdminOrigin`
+        AccountsConfirmed,
+        /// Approved, the trader can request capital allocation
+        Active,
+        /// Suspended for misconduct, the open allocation is recalled
+        Suspended,
+        /// Voluntarily leaving, deregistered once the open allocation is settled
+        Exiting,
+        /// Stop loss triggered, the remaining capital is recalled and the trader suspended once settled
+        Liquidating,
+    }
+
+    /// Reason for liquidating a trader allocation
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub enum LiquidationReason {
+        /// The verified balance fell past the risk policy `max_drawdown`
+        Drawdown(Permill),
+        /// The balance was not verified for `MaxReportAge` blocks
+        StaleReport,
+    }
+
+    /// Reason for suspending a trader
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub enum SuspensionReason {
+        /// Suspended by `TraderAdminOrigin`
+        Governance,
+        /// Reached the maximum number of risk policy violations
+        RiskPolicyViolation,
+    }
+
+    pub type MaxPolicyAssets = ConstU32<16>;
+    pub type MaxPolicyVenues = ConstU32<16>;
+
+    /// Constraints on the trades a funded trader can execute
+    /// `allowed_assets`: Assets the trader can buy, empty allows every asset of the venue
+    /// `allowed_venues`: Dex venues the trader can trade on, empty allows every venue of the network
+    /// `max_position`: Maximum value of a single position as a share of the allocated capital
+    /// `max_trades_per_period`: Maximum trades verified per `period` blocks, 0 disables the limit
+    /// `max_drawdown`: Loss from the peak balance that triggers the recall of the allocation
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub struct RiskPolicy<BlockNumber> {
+        pub allowed_assets: BoundedVec<RemoteAddress, MaxPolicyAssets>,
+        pub allowed_venues: BoundedVec<VenueId, MaxPolicyVenues>,
+        pub max_position: Permill,
+        pub max_trades_per_period: u16,
+        pub period: BlockNumber,
+        pub max_drawdown: Permill,
+    }
 
-        pub fn new(asset_id: T::CurrencyId, trading_account: AccountIdFor<T>) -> Self {
-            // needs to calculate credits upon registering new trader
-            // TODO!!!!
+    impl<BlockNumber: Default> Default for RiskPolicy<BlockNumber> {
+        fn default() -> Self {
             Self {
-                trading_account,
-                asset_id,
-                bonded_amount: TraderBond::<T>::default(),
-                funds_allocated: AssetBalance::<T>::default(),
-                unrealized_balance: AssetBalance::<T>::default(),
-                credits: 0,
-                trades_executed: 0,
+                allowed_assets: BoundedVec::default(),
+                allowed_venues: BoundedVec::default(),
+                max_position: Permill::one(),
+                max_trades_per_period: 0,
+                period: BlockNumber::default(),
+                max_drawdown: Permill::one(),
             }
         }
     }
 
-    /// Tracking investor investments
-    /// `deposited_capital`: Total capital deposited/ contributed to the pool
-    /// `lp_ownership`: Total pool percentage ownerhip per ownership
-    /// `accumulated profit`: Total points representing profits to be later claimed
-    /// `withdraw_period`: Total time that should elapse for investor to withdraw capital + profit
-    #[derive(Encode, Decode, Clone, TypeInfo)]
+    /// Risk policy rule broken by a verified trade
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub enum RiskViolation {
+        VenueNotAllowed,
+        AssetNotAllowed,
+        PositionTooLarge,
+        TradeFrequency,
+    }
+
+    pub type MaxDepositHistory = ConstU32<32>;
+
+    /// A single deposit in a pool
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub struct DepositRecord<Balance, BlockNumber> {
+        pub amount: Balance,
+        pub block_number: BlockNumber,
+    }
+
+    /// Investor position in a capital pool
+    /// `deposited`: Pool shares held for the capital deposited and not redeemed yet
+    /// `deposits`: Latest deposits, oldest first, at most `MaxDepositHistory`. The shares of each deposit stay locked for `withdraw_period`
+    /// `opened_at`: Block of the first deposit
+    /// `withdraw_period`: Total time that should elapse from `opened_at` and from each deposit for investor to withdraw capital + profit
+    #[derive(Encode, Decode, Clone, RuntimeDebug, MaxEncodedLen, TypeInfo)]
     #[scale_info(skip_type_params(T))]
-    pub struct InvestorProfile<T: Config> {
-        pub investor_id: Option<AccountIdFor<T>>,
-        pub deposited_capital: Vec<(T::CurrencyId, AssetBalance<T>)>, //This should be BoundedBTreeMap but am getting lots of errors TODO! consider fixing
-        //pub lp_ownership: Permill,
-        pub block_number: BlockNumberFor<T>,
-        pub claimed_profit: u32,
+    pub struct InvestorPosition<T: Config> {
+        pub deposited: AssetBalance<T>,
+        pub deposits:
+            BoundedVec<DepositRecord<AssetBalance<T>, BlockNumberFor<T>>, MaxDepositHistory>,
+        pub opened_at: BlockNumberFor<T>,
         pub withdraw_period: BlockNumberFor<T>,
     }
 
-    impl<T: Config> Default for InvestorProfile<T> {
-        fn default() -> Self {
+    impl<T: Config> InvestorPosition<T> {
+        pub fn new(now: BlockNumberFor<T>) -> Self {
             Self {
-                deposited_capital: vec![],
-                block_number: <frame_system::Pallet<T>>::block_number(),
-                claimed_profit: 0,
+                deposited: AssetBalance::<T>::default(),
+                deposits: BoundedVec::default(),
+                opened_at: now,
                 withdraw_period: T::WithdrawPeriod::get(),
-                investor_id: None,
             }
         }
-    }
 
-    impl<T: Config> InvestorProfile<T> {
-        pub fn register_capital(
+        pub fn deposit(&mut self, amount: AssetBalance<T>, now: BlockNumberFor<T>) {
+            // bounded by the pool shares issued
+            self.deposited = self.deposited.saturating_add(amount);
+            self.lock_shares(amount, now, now);
+        }
+
+        // Lock `amount` shares for the withdraw period from `block_number`, keeping the latest deposits only.
+        // The oldest deposit of a full history is dropped once unlocked, otherwise merged into the next one
+        pub fn lock_shares(
             &mut self,
-            investor_id: AccountIdFor<T>,
-            asset_id: T::CurrencyId,
             amount: AssetBalance<T>,
+            block_number: BlockNumberFor<T>,
+            now: BlockNumberFor<T>,
         ) {
-            self.investor_id = Some(investor_id);
-            // check if the capital under the asset has been already provided
-            self.deposited_capital
-                .clone()
+            let mut record = DepositRecord {
+                amount,
+                block_number,
+            };
+            if self.deposits.len() >= MaxDepositHistory::get() as usize {
+                let oldest = self.deposits.remove(0);
+                if self.is_locked(&oldest, now) {
+                    let next = self.deposits.first_mut().unwrap_or(&mut record);
+                    next.amount = next.amount.saturating_add(oldest.amount);
+                    next.block_number = next.block_number.max(oldest.block_number);
+                }
+            }
+            let _ = self.deposits.try_push(record);
+        }
+
+        pub fn is_locked(
+            &self,
+            record: &DepositRecord<AssetBalance<T>, BlockNumberFor<T>>,
+            now: BlockNumberFor<T>,
+        ) -> bool {
+            now < record.block_number.saturating_add(self.withdraw_period)
+        }
+
+        // Shares of the deposits made less than `withdraw_period` blocks ago
+        pub fn locked_shares(&self, now: BlockNumberFor<T>) -> AssetBalance<T> {
+            self.deposits
                 .iter()
-                .for_each(|(inner_asset_id, mut balance)| {
-                    if &asset_id == inner_asset_id {
-                        balance += amount
-                    } else {
-                        self.deposited_capital.push((asset_id.clone(), amount))
-                    }
+                .filter(|record| self.is_locked(record, now))
+                .fold(AssetBalance::<T>::default(), |locked, record| {
+                    locked.saturating_add(record.amount)
+                })
+                .min(self.deposited)
+        }
+
+        // Take the deposit locks of `amount` shares moved out of the position, the unlocked shares move first
+        pub fn take_locked_shares(
+            &mut self,
+            amount: AssetBalance<T>,
+            now: BlockNumberFor<T>,
+        ) -> Vec<DepositRecord<AssetBalance<T>, BlockNumberFor<T>>> {
+            let unlocked = self.deposited.saturating_sub(self.locked_shares(now));
+            let mut remaining = amount.saturating_sub(unlocked);
+            let withdraw_period = self.withdraw_period;
+            let mut records = Vec::new();
+            for record in self.deposits.iter_mut().rev() {
+                if remaining.is_zero() {
+                    break;
+                }
+                if now >= record.block_number.saturating_add(withdraw_period) {
+                    continue;
+                }
+                let amount = record.amount.min(remaining);
+                remaining = remaining.saturating_sub(amount);
+                record.amount = record.amount.saturating_sub(amount);
+                records.push(DepositRecord {
+                    amount,
+                    block_number: record.block_number,
                 });
+            }
+            self.deposits.retain(|record| !record.amount.is_zero());
+            records
+        }
+
+        pub fn receive_shares(&mut self, amount: AssetBalance<T>, opened_at: BlockNumberFor<T>) {
+            if self.deposited.is_zero() || opened_at > self.opened_at {
+                self.opened_at = opened_at;
+            }
+            self.deposited = self.deposited.saturating_add(amount);
+        }
+
+        pub fn withdraw_capital(&mut self, amount: AssetBalance<T>) -> Result<(), Error<T>> {
+            self.deposited = self
+                .deposited
+                .checked_sub(&amount)
+                .ok_or(Error::<T>::InsufficientInvestorCapital)?;
+            Ok(())
         }
     }
 
     /// Capital Pool management
-    /// `total_capital`: Total contributed asset amount
+    /// `share_asset_id`: Share token minted to the investors for their deposits, one share per unit of `total_capital`
+    /// `total_capital`: Pool shares outstanding, minted for each deposit at the pool value of the time
     /// `remaining_capital`: Total capital after allocation
     /// `total_allocated_capital`: Total allocated funds to traders
-    /// `unrealized_balance`: `total_capital` + profits after trades
+    /// `unrealized_balance`: Pool value, the deposited capital + profits after trades
+    /// `params`: Governance configured fee and deposit/allocation limits
+    /// `status`: Current lifecycle state of the pool
+    /// `epoch_profit`: Profit realized for the investors during the current epoch
+    /// `epoch_performance_fees`: Trader performance fees charged during the current epoch
+    /// `trader_fees_owed`: Performance fees owed to the traders
+    /// `profit_reserve`: Distributed profit held in the pool account until claimed
+    /// `reward_per_share`: Profit distributed per unit of deposited capital since the pool creation
     #[derive(Encode, Decode, Clone, RuntimeDebug, MaxEncodedLen, TypeInfo)]
     #[scale_info(skip_type_params(T))]
     pub struct InvestorCapitalPool<T: Config> {
         pub asset_name: Option<T::CurrencyId>,
+        pub share_asset_id: Option<T::CurrencyId>,
         pub total_capital: AssetBalance<T>, //BalanceOf<T>,
         pub remaining_capital: AssetBalance<T>,
         pub total_allocated_capital: AssetBalance<T>,
         pub unrealized_balance: AssetBalance<T>,
-        pub fee: u8, // in percentage,
+        pub params: PoolParams<AssetBalance<T>>,
+        pub status: PoolStatus,
         pub account_id: AccountIdFor<T>,
+        pub epoch_profit: AssetBalance<T>,
+        pub epoch_performance_fees: AssetBalance<T>,
+        pub trader_fees_owed: AssetBalance<T>,
+        pub profit_reserve: AssetBalance<T>,
+        pub reward_per_share: FixedU128,
     }
 
     impl<T: Config> InvestorCapitalPool<T> {
-        pub fn update_allocated_funds(&mut self, amount: AssetBalance<T>) {
-            self.remaining_capital -= amount;
-            self.total_allocated_capital += amount
+        pub fn new(asset_id: T::CurrencyId, params: PoolParams<AssetBalance<T>>) -> Self {
+            let account_id = Pallet::<T>::generate_pool_account(Some(asset_id.clone()));
+            Self {
+                share_asset_id: Some(T::PoolShares::share_asset_id(&asset_id)),
+                asset_name: Some(asset_id),
+                params,
+                account_id,
+                ..Default::default()
+            }
+        }
+
+        pub fn update_allocated_funds(&mut self, amount: AssetBalance<T>) -> DispatchResult {
+            self.remaining_capital = self
+                .remaining_capital
+                .checked_sub(&amount)
+                .ok_or(ArithmeticError::Underflow)?;
+            self.total_allocated_capital = self
+                .total_allocated_capital
+                .checked_add(&amount)
+                .ok_or(ArithmeticError::Overflow)?;
+            Ok(())
+        }
+
+        /// Add `amount` deposited pool asset, paid for with `shares` newly minted pool shares
+        pub fn add_capital(
+            &mut self,
+            shares: AssetBalance<T>,
+            amount: AssetBalance<T>,
+        ) -> DispatchResult {
+            self.total_capital = self
+                .total_capital
+                .checked_add(&shares)
+                .ok_or(ArithmeticError::Overflow)?;
+            self.remaining_capital = self
+                .remaining_capital
+                .checked_add(&amount)
+                .ok_or(ArithmeticError::Overflow)?;
+            self.unrealized_balance = self
+                .unrealized_balance
+                .checked_add(&amount)
+                .ok_or(ArithmeticError::Overflow)?;
+            Ok(())
+        }
+
+        /// Value in pool asset of `amount` deposited capital, following pool gains and losses
+        pub fn redemption_value(&self, amount: AssetBalance<T>) -> AssetBalance<T> {
+            if self.total_capital.is_zero() {
+                return AssetBalance::<T>::default();
+            }
+            multiply_by_rational_with_rounding(
+                amount.saturated_into::<u128>(),
+                self.unrealized_balance.saturated_into::<u128>(),
+                self.total_capital.saturated_into::<u128>(),
+                Rounding::Down,
+            )
+            .unwrap_or_default()
+            .saturated_into()
+        }
+
+        /// Deposited capital redeeming for `value` pool asset, inverse of `redemption_value`
+        pub fn capital_for_value(
+            &self,
+            value: AssetBalance<T>,
+            rounding: Rounding,
+        ) -> AssetBalance<T> {
+            if self.total_capital.is_zero() || self.unrealized_balance.is_zero() {
+                return value;
+            }
+            multiply_by_rational_with_rounding(
+                value.saturated_into::<u128>(),
+                self.total_capital.saturated_into::<u128>(),
+                self.unrealized_balance.saturated_into::<u128>(),
+                rounding,
+            )
+            .unwrap_or_default()
+            .saturated_into()
+        }
+
+        /// Pool asset to deposit for `shares` newly minted pool shares, the deposit side inverse of `capital_for_value`
+        pub fn value_for_capital(
+            &self,
+            shares: AssetBalance<T>,
+            rounding: Rounding,
+        ) -> AssetBalance<T> {
+            if self.total_capital.is_zero() || self.unrealized_balance.is_zero() {
+                return shares;
+            }
+            multiply_by_rational_with_rounding(
+                shares.saturated_into::<u128>(),
+                self.unrealized_balance.saturated_into::<u128>(),
+                self.total_capital.saturated_into::<u128>(),
+                rounding,
+            )
+            .unwrap_or_default()
+            .saturated_into()
         }
 
-        pub fn add_capital(&mut self, amount: AssetBalance<T>) {
-            self.total_capital += amount;
-            self.remaining_capital += amount;
+        pub fn redeem_capital(
+            &mut self,
+            amount: AssetBalance<T>,
+            payout: AssetBalance<T>,
+        ) -> DispatchResult {
+            self.total_capital = self
+                .total_capital
+                .checked_sub(&amount)
+                .ok_or(ArithmeticError::Underflow)?;
+            self.remaining_capital = self
+                .remaining_capital
+                .checked_sub(&payout)
+                .ok_or(ArithmeticError::Underflow)?;
+            self.unrealized_balance = self.unrealized_balance.saturating_sub(payout);
+            Ok(())
+        }
+
+        pub fn ensure_active(&self) -> Result<(), Error<T>> {
+            match self.status {
+                PoolStatus::Active => Ok(()),
+                PoolStatus::Paused => Err(Error::<T>::PoolPaused),
+                PoolStatus::Closing | PoolStatus::Closed => Err(Error::<T>::PoolClosed),
+            }
         }
 
         pub fn deduct_unreliazed_balance(&mut self, amount: AssetBalance<T>) {
-            self.unrealized_balance -= amount
+            self.unrealized_balance = self.unrealized_balance.saturating_sub(amount)
         }
 
         pub fn add_unrealized_balance(&mut self, amount: AssetBalance<T>) {
-            self.unrealized_balance += amount
+            self.unrealized_balance = self.unrealized_balance.saturating_add(amount)
         }
     }
 
@@ -217,51 +2086,308 @@ pub mod utils {
             Self {
                 asset_name: None,
                 total_capital: AssetBalance::<T>::default(),
-                fee: 1,
                 remaining_capital: AssetBalance::<T>::default(),
                 total_allocated_capital: AssetBalance::<T>::default(),
                 unrealized_balance: AssetBalance::<T>::default(),
+                params: PoolParams::default(),
+                status: PoolStatus::default(),
                 account_id,
+                epoch_profit: AssetBalance::<T>::default(),
+                epoch_performance_fees: AssetBalance::<T>::default(),
+                trader_fees_owed: AssetBalance::<T>::default(),
+                profit_reserve: AssetBalance::<T>::default(),
+                reward_per_share: FixedU128::zero(),
             }
         }
     }
 
+    pub type RedemptionId = u32;
+
+    /// Request to redeem deposited capital queued until the pool has enough unallocated capital
+    /// `shares`: Deposited capital locked in the request and not redeemed yet
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub struct RedemptionRequest<AccountId, Balance, BlockNumber> {
+        pub id: RedemptionId,
+        pub investor_id: AccountId,
+        pub shares: Balance,
+        pub requested_at: BlockNumber,
+    }
+
+    /// Raw storage key hooks resume their iteration from
+    pub type StorageCursor = BoundedVec<u8, ConstU32<128>>;
+
+    pub type EpochIndex = u32;
+
+    /// Step of the epoch settlement in progress, with the key of the last processed item
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub enum EpochPhase {
+        /// Crystallizing trader performance fees
+        Traders(Option<StorageCursor>),
+        /// Snapshotting pools and distributing realized profit
+        Pools(Option<StorageCursor>),
+    }
+
+    /// Pool state at the end of an epoch
+    /// `nav`: Pool unrealized balance after the profit distribution
+    /// `distributed_profit`: Realized profit credited to the investors
+    /// `performance_fees`: Trader performance fees charged during the epoch
+    #[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub struct EpochRecord<Balance, BlockNumber> {
+        pub nav: Balance,
+        pub total_capital: Balance,
+        pub distributed_profit: Balance,
+        pub performance_fees: Balance,
+        pub reward_per_share: FixedU128,
+        pub closed_at: BlockNumber,
+    }
+
+    /// Profit credited to an investor deposit
+    /// `reward_per_share_paid`: Pool `reward_per_share` at the last accrual
+    /// `pending`: Profit credited and not claimed yet
+    /// `claimed`: Total profit claimed
+    #[derive(
+        Encode, Decode, Clone, PartialEq, Eq, Default, RuntimeDebug, MaxEncodedLen, TypeInfo,
+    )]
+    pub struct InvestorReward<Balance> {
+        pub reward_per_share_paid: FixedU128,
+        pub pending: Balance,
+        pub claimed: Balance,
+    }
+
+    /// Pool parameters configurable by `PoolAdminOrigin`
+    /// `fee`: Fee charged by the pool on trading profits
+    /// `max_capital`: Cap on the pool value in pool asset a deposit may bring it to, `None` for uncapped
+    /// `min_deposit`: Minimum capital accepted per deposit
+    /// `max_trader_allocation`: Cap on the capital allocated to a single trader, `None` for uncapped
+    /// `min_trader_bond`: Minimum bond a trader must hold to apply to the pool
+    /// `hurdle_rate`: Gain over the high water mark per epoch before traders earn performance fees, zero to disable
+    /// `max_investor_capital`: Cap on the value in pool asset of the position of a single investor after a deposit, `None` for uncapped
+    #[derive(
+        Encode, Decode, Clone, PartialEq, Eq, Default, RuntimeDebug, MaxEncodedLen, TypeInfo,
+    )]
+    pub struct PoolParams<Balance> {
+        pub fee: Permill,
+        pub max_capital: Option<Balance>,
+        pub min_deposit: Balance,
+        pub max_trader_allocation: Option<Balance>,
+        pub min_trader_bond: Balance,
+        pub hurdle_rate: Permill,
+        pub max_investor_capital: Option<Balance>,
+    }
+
+    /// Lifecycle of a capital pool
+    #[derive(
+        Encode, Decode, Clone, Copy, PartialEq, Eq, Default, RuntimeDebug, MaxEncodedLen, TypeInfo,
+    )]
+    pub enum PoolStatus {
+        /// Accepting deposits and allocating capital to traders
+        #[default]
+        Active,
+        /// Deposits and allocations are halted, investors can still withdraw
+        Paused,
+        /// Winding down, allocations are being recalled and investors can only redeem
+        Closing,
+        /// Every allocation is back in the pool, investors redeem what is left
+        Closed,
+    }
+
     /// Trader bond details and indicator if the bond should be staked for more rewards
+    /// `amount`: Amount of the pool asset held from the trader
     #[derive(Encode, Decode, Clone, DefaultNoBound, RuntimeDebug, MaxEncodedLen, TypeInfo)]
     #[scale_info(skip_type_params(T))]
     pub struct TraderBond<T: Config> {
-        pub amount: Option<T::CurrencyId>,
+        pub amount: AssetBalance<T>,
         pub stake: bool,
     }
 
+    /// Maximum number of onchain trading accounts per trader, one per network
+    pub type MaxTradingAccounts = ConstU32<8>;
+
+    /// Onchain trading accounts generated in the contract for a trader, keyed by network
     #[derive(
-        Encode, Decode, Clone, PartialEq, RuntimeDebug, DefaultNoBound, MaxEncodedLen, TypeInfo,
+        Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, Default, MaxEncodedLen, TypeInfo,
     )]
-    pub struct TradingAccounts<AccountId> {
-        pub substrate: Option<AccountId>,
-        pub ethereum: Option<AccountId>,
-        pub solana: Option<AccountId>,
+    pub struct TradingAccounts(pub BoundedBTreeMap<Networks, RemoteAccount, MaxTradingAccounts>);
+
+    impl TradingAccounts {
+        pub fn get(&self, network: &Networks) -> Option<&RemoteAccount> {
+            self.0.get(network)
+        }
+
+        /// Every account must be in the native address format of its network
+        pub fn is_valid(&self) -> bool {
+            self.0
+                .iter()
+                .all(|(network, account)| account.is_native_to(network))
+        }
+    }
+
+    /// Account on a target network in the address format native to that network
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub enum RemoteAccount {
+        /// 32 byte substrate account, e.g HydraDX
+        AccountId32([u8; 32]),
+        /// 20 byte ethereum compatible address
+        H160(H160),
+        /// 32 byte ed25519 public key, e.g Solana
+        Ed25519([u8; 32]),
+    }
+
+    impl RemoteAccount {
+        pub fn as_bytes(&self) -> &[u8] {
+            match self {
+                RemoteAccount::AccountId32(account) | RemoteAccount::Ed25519(account) => {
+                    &account[..]
+                }
+                RemoteAccount::H160(account) => account.as_bytes(),
+            }
+        }
+
+        pub fn is_native_to(&self, network: &Networks) -> bool {
+            matches!(
+                (network, self),
+                (Networks::Substrate, RemoteAccount::AccountId32(_))
+                    | (Networks::Ethereum | Networks::Sei, RemoteAccount::H160(_))
+                    | (Networks::Solana, RemoteAccount::Ed25519(_))
+            )
+        }
+    }
+
+    /// Balances map holding a pool asset in the state of a substrate target network, an `orml_tokens`
+    /// `Accounts` map keyed by `Blake2_128Concat` of the account and `Twox64Concat` of the asset id
+    /// `prefix`: `twox_128(pallet) ++ twox_128(storage)` of the map, e.g. HydraDX `Tokens::Accounts`
+    /// `asset`: SCALE encoded id of the pool asset on the network
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub struct RemoteBalanceStorage {
+        pub prefix: [u8; 32],
+        pub asset: RemoteAddress,
+    }
+
+    impl RemoteBalanceStorage {
+        /// State key of the balance of `account`, the only key a trade proof of the account may prove
+        pub fn account_key(&self, account: &RemoteAccount) -> Vec<u8> {
+            let mut key = self.prefix.to_vec();
+            key.extend_from_slice(&blake2_128(account.as_bytes()));
+            key.extend_from_slice(account.as_bytes());
+            key.extend_from_slice(&twox_64(&self.asset));
+            key.extend_from_slice(&self.asset);
+            key
+        }
+    }
+
+    /// Value of an `orml_tokens` `Accounts` entry on a substrate target network
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, TypeInfo)]
+    pub struct RemoteAccountData {
+        pub free: u128,
+        pub reserved: u128,
+        pub frozen: u128,
     }
 
     /// Hashing algorithm for the state proof
-    // #[derive(Debug, Encode, Decode, Clone, Serialize, Deserialize)]
-    // pub enum HashAlgorithm {
-    //     /// For chains that use keccak as their hashing algo
-    //     Keccak,
-    //     /// For chains that use blake2 as their hashing algo
-    //     Blake2,
-    // }
-
-    /// Holds the relevant data needed for state proof verification
-    // #[derive(Debug, Encode, Decode, Clone)]
-    // pub struct SubstrateStateProof {
-    //     /// Algorithm to use for state proof verification
-    //     pub hasher: HashAlgorithm,
-    //     /// Storage proof for the parachain headers
-    //     pub storage_proof: Vec<Vec<u8>>,
-    // }
-
-    #[derive(Debug, Encode, Decode, Clone)]
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub enum HashAlgorithm {
+        /// For chains that use keccak as their hashing algo
+        Keccak,
+        /// For chains that use blake2 as their hashing algo
+        Blake2,
+    }
+
+    /// Kind of proofs the trades executed on a target network are verified with
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub enum ProofKind {
+        /// Substrate storage trie proofs
+        SubstrateTrie,
+        /// Ethereum Merkle Patricia trie proofs ( receipts and account storage )
+        EthereumMpt,
+        /// Solana account state proofs
+        SolanaAccount,
+        /// No proofs, balances are checked by XCM queries to the network, for parachains reachable over XCMP
+        XcmQuery,
+    }
+
+    /// Source of the finalized state roots the trade proofs are checked against
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub enum FinalitySource {
+        /// Parachain sharing the relay chain security, roots are final once included
+        RelayChain,
+        /// Light client consensus proofs submitted along the trade proofs
+        ConsensusProof,
+        /// Roots attested by the relayer set
+        Relayers,
+    }
+
+    /// Identifier of a target network in the network registry
+    pub type NetworkId = u32;
+
+    /// Identifier of a dex venue in the venue registry
+    pub type VenueId = u32;
+
+    /// Contract, program or asset address on a target network
+    pub type RemoteAddress = BoundedVec<u8, ConstU32<64>>;
+
+    pub type MaxVenueAssets = ConstU32<32>;
+
+    /// Target network capital can be deployed on
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub struct NetworkInfo {
+        /// Network family, defines the native address format of the trading accounts
+        pub kind: Networks,
+        /// EVM chain id, parachain id or the network genesis derived id
+        pub chain_id: u64,
+        pub hasher: HashAlgorithm,
+        pub proof_kind: ProofKind,
+        pub finality: FinalitySource,
+        /// Disabled networks keep their venues but reject new allocations and trade proofs
+        pub enabled: bool,
+    }
+
+    impl NetworkInfo {
+        /// Whether the trades on the network can be verified, only substrate networks are for now:
+        /// blake2 trie proofs against relayer attested roots, or XCM balance queries.
+        /// The other network families and proof kinds describe networks without a verifier yet
+        pub fn is_supported(&self) -> bool {
+            self.kind == Networks::Substrate
+                && match self.proof_kind {
+                    ProofKind::SubstrateTrie => {
+                        self.hasher == HashAlgorithm::Blake2
+                            && self.finality != FinalitySource::ConsensusProof
+                    }
+                    ProofKind::XcmQuery => true,
+                    ProofKind::EthereumMpt | ProofKind::SolanaAccount => false,
+                }
+        }
+    }
+
+    /// Dex venue traders are allowed to trade on
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub struct DexVenue {
+        pub network: NetworkId,
+        pub dex: SupportedDexs,
+        pub router: Option<RemoteAddress>,
+        pub factory: Option<RemoteAddress>,
+        /// Index of the dex pallet for substrate based venues
+        pub pallet_index: Option<u8>,
+        /// Indices of the swap calls of the dex pallet for substrate based venues
+        pub swap_calls: Option<SwapCalls>,
+        pub allowed_assets: BoundedVec<RemoteAddress, MaxVenueAssets>,
+    }
+
+    /// Call indices of the sell and buy calls of a substrate dex pallet, e.g. the HydraDX Omnipool `sell` and `buy`
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub struct SwapCalls {
+        pub sell: u8,
+        pub buy: u8,
+    }
+
+    impl DexVenue {
+        pub fn is_asset_allowed(&self, asset: &[u8]) -> bool {
+            self.allowed_assets
+                .iter()
+                .any(|allowed| &allowed[..] == asset)
+        }
+    }
+
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
     pub enum SupportedDexs {
         HydraDx,
         StellaSwap,
@@ -277,7 +2403,7 @@ pub mod utils {
     #[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug, TypeInfo)]
     #[scale_info(skip_type_params(T))]
     pub struct TradeExecutionProof<BlockNumber> {
-        pub target_network: Networks,
+        pub target_network: NetworkId,
         pub target_network_blocknumber: BlockNumber,
         pub transaction_inclusion: TransactionInclusionProof,
         pub state_proof: StateProof,
@@ -285,14 +2411,52 @@ pub mod utils {
     }
 
     /// Data to verify inclusion of the trade transaction
+    /// `transaction`: The encoded transaction, proven as the value under `key` in the transactions trie of the block
     #[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug, TypeInfo)]
     pub struct TransactionInclusionProof {
-        tx_id: Vec<u8>,
+        transaction: Vec<u8>,
         tx_proof: Vec<Vec<u8>>,
         key: Vec<u8>,
         tx_state_root: Vec<u8>,
     }
 
+    impl TransactionInclusionProof {
+        pub fn new(
+            transaction: Vec<u8>,
+            tx_proof: Vec<Vec<u8>>,
+            key: Vec<u8>,
+            tx_state_root: Vec<u8>,
+        ) -> Self {
+            Self {
+                transaction,
+                tx_proof,
+                key,
+                tx_state_root,
+            }
+        }
+
+        // the proven transaction as included in the target network block
+        pub fn transaction(&self) -> &[u8] {
+            &self.transaction
+        }
+    }
+
+    /// Roots of a finalized block of a target network, attested by the relayer set.
+    /// Trade proofs are only checked against these, never against the roots they carry themselves
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub struct FinalizedRoots {
+        pub state_root: H256,
+        pub extrinsics_root: H256,
+    }
+
+    impl FinalizedRoots {
+        /// Whether both the transaction inclusion and the state proof were built against these roots
+        pub fn anchors<BlockNumber>(&self, proofs: &TradeExecutionProof<BlockNumber>) -> bool {
+            proofs.transaction_inclusion.tx_state_root[..] == self.extrinsics_root[..]
+                && proofs.state_proof.state_root[..] == self.state_root[..]
+        }
+    }
+
     /// Data to verify and read account balance after trade transaction
     #[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug, TypeInfo)]
     pub struct StateProof {
@@ -310,7 +2474,11 @@ pub mod utils {
         pub consensus_digest_key: Vec<u8>,
     }
 
-    #[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    /// Network family, the concrete networks live in the `TargetNetworks` registry.
+    /// Defines the native address format of the trading accounts, a new family is a runtime upgrade
+    #[derive(
+        Encode, Decode, Clone, PartialEq, Eq, PartialOrd, Ord, RuntimeDebug, MaxEncodedLen, TypeInfo,
+    )]
     pub enum Networks {
         Substrate,
         Ethereum,
@@ -324,36 +2492,334 @@ pub mod utils {
         Sell,
     }
 
+    /// Swap executed on a remote venue by the pool account on behalf of a trader
+    /// `asset_in`, `asset_out`: Asset ids of the venue network, e.g HydraDX asset ids
+    /// `amount`: Sold amount of `asset_in` for a sell, bought amount of `asset_out` for a buy
+    /// `limit`: Minimum bought amount for a sell, maximum sold amount for a buy
+    #[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub struct RemoteSwap {
+        pub venue: VenueId,
+        pub action: TradeAction,
+        pub asset_in: u32,
+        pub asset_out: u32,
+        pub amount: u128,
+        pub limit: u128,
+    }
+
+    impl RemoteSwap {
+        /// Asset bought by the swap as listed in the venue and risk policy allowed assets
+        pub fn asset_out_address(&self) -> RemoteAddress {
+            BoundedVec::truncate_from(self.asset_out.encode())
+        }
+
+        /// Upper bound of the `asset_in` amount sold by the swap
+        pub fn max_sold(&self) -> u128 {
+            match self.action {
+                TradeAction::Sell => self.amount,
+                TradeAction::Buy => self.limit,
+            }
+        }
+    }
+
+    /// Public key of a Phala worker or contract attesting the trading accounts it generated
+    pub type AttestorKey = sr25519::Public;
+
+    /// Attestation of the trading accounts generated for a trader
+    /// `attestor`: Key of the worker or contract that generated the accounts
+    /// `nonce`: Above the nonce of the previous registration of the trader, prevents replaying old accounts
+    /// `signature`: Signature of `attestation_payload` by the attestor
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, TypeInfo)]
+    pub struct TradingAccountsAttestation {
+        pub attestor: AttestorKey,
+        pub nonce: u64,
+        pub signature: sr25519::Signature,
+    }
+
+    /// Message signed by the attestor for the trading accounts of a trader
+    pub fn attestation_payload<AccountId: Encode>(
+        trader_id: &AccountId,
+        trading_accounts: &TradingAccounts,
+        nonce: u64,
+    ) -> Vec<u8> {
+        (b"spectre/attestation", trader_id, trading_accounts, nonce).encode()
+    }
+
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub enum AttestorStatus<BlockNumber> {
+        Trusted,
+        /// Replaced by `new`, kept to trace the attestations it signed
+        Rotated {
+            new: AttestorKey,
+            at: BlockNumber,
+        },
+        Revoked {
+            at: BlockNumber,
+        },
+    }
+
+    /// Registered attestor key
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub struct AttestorInfo<BlockNumber> {
+        pub status: AttestorStatus<BlockNumber>,
+        pub registered_at: BlockNumber,
+    }
+
+    impl<BlockNumber> AttestorInfo<BlockNumber> {
+        pub fn new(registered_at: BlockNumber) -> Self {
+            Self {
+                status: AttestorStatus::Trusted,
+                registered_at,
+            }
+        }
+
+        pub fn is_trusted(&self) -> bool {
+            matches!(self.status, AttestorStatus::Trusted)
+        }
+    }
+
+    /// XCM balance query of the account the pool controls for a trader, waiting for its response
+    /// `balance`: Balance of the pool asset claimed by the relayers, recorded once the response confirms it
+    /// `responder`: Location of the queried network, the only location the response is accepted from
+    #[derive(Encode, Decode, Clone, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    #[scale_info(skip_type_params(T))]
+    pub struct PendingBalanceQuery<T: Config> {
+        pub trader_id: AccountIdFor<T>,
+        pub network: NetworkId,
+        pub balance: AssetBalance<T>,
+        pub requested_at: BlockNumberFor<T>,
+        pub responder: MultiLocation,
+    }
+
+    /// Remote swap waiting for its result
+    /// `responder`: Location of the venue network, the only location the result is accepted from
+    #[derive(Encode, Decode, Clone, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    #[scale_info(skip_type_params(T))]
+    pub struct PendingRemoteSwap<T: Config> {
+        pub trader_id: AccountIdFor<T>,
+        pub network: NetworkId,
+        pub swap: RemoteSwap,
+        pub requested_at: BlockNumberFor<T>,
+        pub responder: MultiLocation,
+    }
+
     // Traits and implementations
 
     /// Responsible for allocating funds from different pools to trader on chain trading account
     pub trait CapitalAllocator<T: Config> {
         fn allocate_capital(
-            network: Networks,
+            network: NetworkId,
+            asset_id: T::CurrencyId,
+            amount: AssetBalance<T>,
+            trader_id: AccountIdFor<T>,
+            onchain_trading_account: RemoteAccount,
+        ) -> DispatchResult;
+
+        // Instruct the return of the capital held by the on chain trading account to the pool,
+        // `amount` is the last verified balance of the trading account
+        fn recall_capital(
+            network: NetworkId,
+            asset_id: T::CurrencyId,
+            amount: AssetBalance<T>,
             trader_id: AccountIdFor<T>,
-            onchain_trading_account: AccountIdFor<T>,
+            onchain_trading_account: RemoteAccount,
         ) -> DispatchResult;
     }
 
     impl<T: Config> CapitalAllocator<T> for () {
         fn allocate_capital(
-            network: Networks,
+            network: NetworkId,
+            asset_id: T::CurrencyId,
+            amount: AssetBalance<T>,
+            trader_id: AccountIdFor<T>,
+            onchain_trading_account: RemoteAccount,
+        ) -> DispatchResult {
+            Ok(())
+        }
+
+        fn recall_capital(
+            network: NetworkId,
+            asset_id: T::CurrencyId,
+            amount: AssetBalance<T>,
             trader_id: AccountIdFor<T>,
-            onchain_trading_account: AccountIdFor<T>,
+            onchain_trading_account: RemoteAccount,
         ) -> DispatchResult {
             Ok(())
         }
     }
 
-    /// Responsible for claiming Return on Investment by Investor and trader
-    pub trait Withdraw<T: Config> {
-        fn investor_instant_withdraw() -> DispatchResult;
+    /// Responsible for executing the swaps of the pool on remote venues, e.g. through XCM `Transact`
+    pub trait RemoteSwapExecutor<T: Config> {
+        /// Send the swap from the account of the pool dedicated to the trader, the execution is paid in the pool
+        /// asset `asset_id`. Returns the query its result is reported to and the location the result must come from
+        fn execute_swap(
+            pool_account: &AccountIdFor<T>,
+            trader_id: &AccountIdFor<T>,
+            asset_id: &T::CurrencyId,
+            network: &NetworkInfo,
+            venue: &DexVenue,
+            swap: &RemoteSwap,
+        ) -> Result<(QueryId, MultiLocation), DispatchError>;
+    }
+
+    impl<T: Config> RemoteSwapExecutor<T> for () {
+        fn execute_swap(
+            pool_account: &AccountIdFor<T>,
+            trader_id: &AccountIdFor<T>,
+            asset_id: &T::CurrencyId,
+            network: &NetworkInfo,
+            venue: &DexVenue,
+            swap: &RemoteSwap,
+        ) -> Result<(QueryId, MultiLocation), DispatchError> {
+            Err(Error::<T>::RemoteSwapsNotSupported.into())
+        }
+    }
+
+    /// Responsible for checking the balance of the accounts the pools control for traders on remote networks
+    pub trait RemoteBalanceQuerier<T: Config> {
+        /// Send a query reporting whether the account the pool keeps for the trader holds `balance`
+        /// of the pool asset, returns the id of the query and the location its response must come from
+        fn query_balance(
+            pool_account: &AccountIdFor<T>,
+            trader_id: &AccountIdFor<T>,
+            network: &NetworkInfo,
+            asset_id: &T::CurrencyId,
+            balance: AssetBalance<T>,
+        ) -> Result<(QueryId, MultiLocation), DispatchError>;
+
+        /// Balance of the pool asset reported by the query response
+        fn reported_balance(asset_id: &T::CurrencyId, response: &Response) -> AssetBalance<T>;
+    }
+
+    impl<T: Config> RemoteBalanceQuerier<T> for () {
+        fn query_balance(
+            pool_account: &AccountIdFor<T>,
+            trader_id: &AccountIdFor<T>,
+            network: &NetworkInfo,
+            asset_id: &T::CurrencyId,
+            balance: AssetBalance<T>,
+        ) -> Result<(QueryId, MultiLocation), DispatchError> {
+            Err(Error::<T>::BalanceQueriesNotSupported.into())
+        }
+
+        fn reported_balance(asset_id: &T::CurrencyId, response: &Response) -> AssetBalance<T> {
+            Zero::zero()
+        }
+    }
+
+    /// Reason a deposit is refused by the `DepositFilter`
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+    pub enum DepositRejection {
+        /// The investor is not on the allowlist of the pool, e.g. missing KYC attestation
+        NotAllowlisted,
+        /// The investor is blocked, e.g. on a sanctions list
+        Blocked,
+    }
+
+    /// Decides which investors can deposit in a pool
+    pub trait DepositFilter<T: Config> {
+        fn can_deposit(
+            investor_id: &AccountIdFor<T>,
+            asset_id: &T::CurrencyId,
+            amount: AssetBalance<T>,
+        ) -> Result<(), DepositRejection>;
+    }
+
+    // Every investor can deposit
+    impl<T: Config> DepositFilter<T> for () {
+        fn can_deposit(
+            investor_id: &AccountIdFor<T>,
+            asset_id: &T::CurrencyId,
+            amount: AssetBalance<T>,
+        ) -> Result<(), DepositRejection> {
+            Ok(())
+        }
+    }
+
+    pub type AssetMetadataOf<T> = AssetMetadata<
+        <T as orml_asset_registry::module::Config>::Balance,
+        <T as orml_asset_registry::module::Config>::CustomMetadata,
+        <T as orml_asset_registry::module::Config>::StringLimit,
+    >;
+
+    /// Derives the share token of each capital pool
+    pub trait PoolShareAsset<T: Config> {
+        /// Share token `CurrencyId` of the pool of `asset_id`
+        fn share_asset_id(asset_id: &T::CurrencyId) -> T::CurrencyId;
+
+        /// Asset registry metadata of the share token, `None` if the pool asset is unknown
+        fn share_metadata(asset_id: &T::CurrencyId) -> Option<AssetMetadataOf<T>>;
+    }
+
+    /// `orml_tokens` hooks moving the investor positions along with the pool share tokens
+    pub struct PoolShareHooks<T>(PhantomData<T>);
+
+    impl<T: Config> MutationHooks<AccountIdFor<T>, T::CurrencyId, AssetBalance<T>>
+        for PoolShareHooks<T>
+    {
+        type OnDust = ();
+        type OnSlash = ();
+        type PreDeposit = ();
+        type PostDeposit = ();
+        type PreTransfer = Self;
+        type PostTransfer = ();
+        type OnNewTokenAccount = ();
+        type OnKilledTokenAccount = ();
+    }
+
+    impl<T: Config> OnTransfer<AccountIdFor<T>, T::CurrencyId, AssetBalance<T>> for PoolShareHooks<T> {
+        fn on_transfer(
+            currency_id: T::CurrencyId,
+            from: &AccountIdFor<T>,
+            to: &AccountIdFor<T>,
+            amount: AssetBalance<T>,
+        ) -> DispatchResult {
+            Pallet::<T>::transfer_pool_shares(currency_id, from, to, amount)
+        }
+    }
+
+    /// Matches the location set in `XcmTraderRegistrar`, to build the XCM origin of the trader registrar contract
+    pub struct IsXcmTraderRegistrar<T>(PhantomData<T>);
+
+    impl<T: Config> Contains<MultiLocation> for IsXcmTraderRegistrar<T> {
+        fn contains(location: &MultiLocation) -> bool {
+            XcmTraderRegistrar::<T>::get().as_ref() == Some(location)
+        }
+    }
 
-        fn investor_schedule_withdraw() -> DispatchResult;
+    /// Swap decoded from a trade transaction
+    /// `position_value`: Value of the bought asset in units of the allocated asset
+    #[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug, TypeInfo)]
+    pub struct DecodedTrade<Balance> {
+        pub venue: VenueId,
+        pub asset_in: RemoteAddress,
+        pub asset_out: RemoteAddress,
+        pub amount_in: u128,
+        pub amount_out: u128,
+        pub position_value: Balance,
+    }
 
-        fn trader_instant_withdraw() -> DispatchResult;
+    /// Responsible for decoding the swaps executed by a verified trade transaction.
+    /// `transaction` is the encoded transaction proven in the target network block, it must be
+    /// signed by `trading_account`, the trader can not be held to the transactions of others
+    pub trait TradeDecoder<T: Config> {
+        fn decode_trades(
+            network: NetworkId,
+            network_info: &NetworkInfo,
+            trading_account: &RemoteAccount,
+            transaction: &[u8],
+        ) -> Result<Vec<DecodedTrade<AssetBalance<T>>>, DispatchError>;
+    }
 
-        fn trader_schedule_withdraw() -> DispatchResult;
+    // Decodes no trades, only the trade frequency of the risk policy is enforced
+    impl<T: Config> TradeDecoder<T> for () {
+        fn decode_trades(
+            network: NetworkId,
+            network_info: &NetworkInfo,
+            trading_account: &RemoteAccount,
+            transaction: &[u8],
+        ) -> Result<Vec<DecodedTrade<AssetBalance<T>>>, DispatchError> {
+            Ok(Vec::new())
+        }
     }
 
     /// Responsible for verifying trade execution proofs
@@ -362,24 +2828,32 @@ pub mod utils {
         // Verify Trade execution in a foreign Dex in a target network
         fn verify_trade_execution(
             trader_id: AccountIdFor<T>,
-            trading_account: AccountIdFor<T>,
+            trading_account: RemoteAccount,
             asset_id: T::CurrencyId,
-            network: Networks,
+            network: NetworkId,
             proofs: TradeExecutionProof<BlockNumberFor<T>>,
             trade_action: TradeAction,
         ) -> DispatchResult;
 
         // Verify trade transaction inclusion in the block of the target network ( Blockchain )
-        fn verify_trade_tx_inclusion(network: Networks, proofs: TransactionInclusionProof) -> bool;
+        fn verify_trade_tx_inclusion(
+            network: &NetworkInfo,
+            proofs: TransactionInclusionProof,
+        ) -> bool;
 
         // Verify state proofs and read the account balance
         fn verify_state_acount_balance(
-            network: Networks,
+            network: &NetworkInfo,
             proofs: StateProof,
         ) -> Result<BTreeMap<Vec<u8>, Option<Vec<u8>>>, Error<T>>;
 
         // Verify consensus commitment on N blockheight
-        fn verify_consensus_state(network: Networks, proofs: ConsensusProofs) -> bool;
+        fn verify_consensus_state(network: &NetworkInfo, proofs: ConsensusProofs) -> bool;
+    }
+
+    // Root of a blake2 trie, `None` unless it is 32 bytes long
+    fn trie_root(root: &[u8]) -> Option<H256> {
+        <[u8; 32]>::try_from(root).ok().map(H256::from)
     }
 
     pub struct TradeExecutionVerifyV1;
@@ -387,20 +2861,18 @@ pub mod utils {
     impl<T: Config> TradeExecutionVerifier<T> for TradeExecutionVerifyV1 {
         fn verify_trade_execution(
             trader_id: AccountIdFor<T>,
-            trading_account: AccountIdFor<T>,
+            trading_account: RemoteAccount,
             asset_id: T::CurrencyId,
-            network: Networks,
+            network: NetworkId,
             proofs: TradeExecutionProof<BlockNumberFor<T>>,
             trade_action: TradeAction,
         ) -> DispatchResult {
-            // This will be crucial once targeting other non shared security chains
-            // let is_consensus_valid = T::TradeExecutionVerifier::verify_consensus_state(
-            //     network.clone(),
-            //     proofs.consensus_proof,
-            // );
+            // the roots the proofs carry are checked against the finalized roots before, see `Pallet::ensure_trusted_roots`
+            let network_info =
+                TargetNetworks::<T>::get(network).ok_or(Error::<T>::NetworkNotSupported)?;
 
             let is_tx_valid = T::TradeExecutionVerifier::verify_trade_tx_inclusion(
-                network.clone(),
+                &network_info,
                 proofs.transaction_inclusion,
             );
 
@@ -408,9 +2880,29 @@ pub mod utils {
                 return Err(Error::<T>::InvalidTxInclusion.into()); // The Tx was not found
             }
 
-            let state_account_balance =
-                T::TradeExecutionVerifier::verify_state_acount_balance(network, proofs.state_proof)
-                    .map_err(|_| Error::<T>::InvalidBalanceStateProof)?;
+            let trader_profile =
+                TraderProfiles::<T>::get(&trader_id).ok_or(Error::<T>::TraderNotRegistered)?;
+            ensure!(
+                trader_profile.asset_id == asset_id,
+                Error::<T>::AssetPoolNotSupported
+            );
+
+            // the proven state must be the pool asset balance of the trading account, nothing else it holds
+            let balance_storage = NetworkAssets::<T>::get(network, &asset_id)
+                .ok_or(Error::<T>::NetworkAssetNotRegistered)?;
+            let state_key = balance_storage.account_key(&trading_account);
+            ensure!(
+                trading_account.is_native_to(&network_info.kind)
+                    && network_info.proof_kind == ProofKind::SubstrateTrie
+                    && proofs.state_proof.state_key == state_key,
+                Error::<T>::InvalidBalanceStateProof
+            );
+
+            let state_account_balance = T::TradeExecutionVerifier::verify_state_acount_balance(
+                &network_info,
+                proofs.state_proof,
+            )
+            .map_err(|_| Error::<T>::InvalidBalanceStateProof)?;
 
             // update pool and trader balance
             ensure!(
@@ -418,58 +2910,61 @@ pub mod utils {
                 Error::<T>::AssetPoolNotSupported
             );
 
-            // check the asset id and fetch the associated account id for trader
-
-            let mut trader_profile = TraderProfiles::<T>::get(trader_id, trading_account.clone())
-                .ok_or(Error::<T>::TraderNotFunded)?;
+            ensure!(
+                !trader_profile.funds_allocated.is_zero(),
+                Error::<T>::TraderNotFunded
+            );
 
             let capital_pool = CapitalPool::<T>::get(asset_id);
 
             // get the trader trading balance remaining in on chain trading account
-            let trading_acc_vec = trading_account.encode();
             let rem_trading_balance_encoded = state_account_balance
-                .get(&trading_acc_vec)
+                .get(&state_key)
                 .ok_or(Error::<T>::InvalidBalanceStateProof)?
                 .clone()
                 .ok_or(Error::<T>::InvalidBalanceStateProof)?;
 
             let rem_trading_balance: AssetBalance<T> =
-                Decode::decode(&mut &rem_trading_balance_encoded[..])
-                    .map_err(|_| Error::<T>::FailedToDecodeValue)?;
+                RemoteAccountData::decode_all(&mut &rem_trading_balance_encoded[..])
+                    .map_err(|_| Error::<T>::FailedToDecodeValue)?
+                    .free
+                    .saturated_into();
 
             // get the net positive or negative balance
             // let loss = allocated_balance  - rem_trading_balance;
             // let profit = rem_trading_balance - allocated_balance;
 
             match trade_action {
-                TradeAction::Buy => {
-                    trader_profile.update_unrealized_balance(rem_trading_balance);
-                    // update the pool
-                }
-                TradeAction::Sell => {
-                    trader_profile.update_unrealized_balance(rem_trading_balance);
-                    // update the pool
+                TradeAction::Buy | TradeAction::Sell => {
+                    // update the trader and the pool
+                    Pallet::<T>::record_trader_balance(
+                        &trader_id,
+                        trader_profile,
+                        rem_trading_balance,
+                    )?;
                 }
             }
 
             Ok(())
         }
 
-        fn verify_consensus_state(network: Networks, proofs: ConsensusProofs) -> bool {
-            true
+        fn verify_consensus_state(network: &NetworkInfo, proofs: ConsensusProofs) -> bool {
+            // light client verification is not available yet for any network family
+            false
         }
 
         fn verify_state_acount_balance(
-            network: Networks,
+            network: &NetworkInfo,
             proofs: StateProof,
         ) -> Result<BTreeMap<Vec<u8>, Option<Vec<u8>>>, Error<T>> {
-            match network {
-                Networks::Substrate => {
+            match (&network.proof_kind, &network.hasher) {
+                (ProofKind::SubstrateTrie, HashAlgorithm::Blake2) => {
                     let data = {
                         let db =
                             StorageProof::new(proofs.state_proofs).into_memory_db::<BlakeTwo256>();
 
-                        let state_proof_root = H256::from_slice(&proofs.state_root[..]);
+                        let state_proof_root = trie_root(&proofs.state_root)
+                            .ok_or(Error::<T>::InvalidBalanceStateProof)?;
 
                         let trie =
                             TrieDBBuilder::<LayoutV1<BlakeTwo256>>::new(&db, &state_proof_root)
@@ -486,14 +2981,19 @@ pub mod utils {
 
                     Ok(data)
                 }
-                _ => todo!(),
+                _ => Err(Error::<T>::UnsupportedProofKind),
             }
         }
 
-        fn verify_trade_tx_inclusion(network: Networks, proofs: TransactionInclusionProof) -> bool {
-            match network {
-                Networks::Substrate => {
-                    let tx_root = H256::from_slice(&proofs.tx_state_root[..]);
+        fn verify_trade_tx_inclusion(
+            network: &NetworkInfo,
+            proofs: TransactionInclusionProof,
+        ) -> bool {
+            match (&network.proof_kind, &network.hasher) {
+                (ProofKind::SubstrateTrie, HashAlgorithm::Blake2) => {
+                    let Some(tx_root) = trie_root(&proofs.tx_state_root) else {
+                        return false;
+                    };
                     let is_valid = sp_trie::verify_trie_proof::<
                         sp_trie::LayoutV1<BlakeTwo256>,
                         _,
@@ -502,15 +3002,12 @@ pub mod utils {
                     >(
                         &tx_root,
                         &*proofs.tx_proof,
-                        &[(proofs.key, Some(proofs.tx_id))],
+                        &[(proofs.key, Some(proofs.transaction))],
                     );
-                    if is_valid.is_ok() {
-                        true
-                    } else {
-                        false
-                    }
+                    is_valid.is_ok()
                 }
-                _ => todo!("Ethereum implementation"),
+                // Ethereum receipts and Solana proofs are not supported yet
+                _ => false,
             }
         }
     }
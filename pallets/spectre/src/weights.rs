@@ -0,0 +1,212 @@
+//! Weights of the spectre pallet extrinsics.
+//!
+//! The pallet has no benchmarks yet, the weights below are estimated from the storage items each
+//! extrinsic reads and writes, with a fixed execution cost per extrinsic. Calls filling the redemption
+//! queue are charged for `MaxRedemptionRequests` fills, calls going through the relayer set for `MaxRelayers` relayers.
+
+use frame_support::{
+    traits::Get,
+    weights::{constants::RocksDbWeight, Weight},
+};
+
+/// Weight functions needed for pallet_spectre.
+pub trait WeightInfo {
+    fn register_investor(r: u32) -> Weight;
+    fn register_trader(m: u32) -> Weight;
+    fn allocate_capital() -> Weight;
+    fn verify_trade_execution() -> Weight;
+    fn create_pool() -> Weight;
+    fn set_pool_params() -> Weight;
+    fn pause_pool() -> Weight;
+    fn resume_pool() -> Weight;
+    fn close_pool() -> Weight;
+    fn withdraw_capital() -> Weight;
+    fn add_relayer(m: u32) -> Weight;
+    fn remove_relayer(m: u32) -> Weight;
+    fn rotate_relayer(m: u32) -> Weight;
+    fn set_relayer_threshold() -> Weight;
+    fn report_relayer(m: u32) -> Weight;
+    fn apply_as_trader() -> Weight;
+    fn approve_trader() -> Weight;
+    fn suspend_trader() -> Weight;
+    fn reinstate_trader() -> Weight;
+    fn deregister_trader() -> Weight;
+    fn settle_allocation(m: u32, r: u32) -> Weight;
+    fn set_target_network() -> Weight;
+    fn remove_target_network() -> Weight;
+    fn set_dex_venue() -> Weight;
+    fn remove_dex_venue() -> Weight;
+    fn set_network_asset() -> Weight;
+    fn set_risk_policy() -> Weight;
+    fn pay_insurance_claim() -> Weight;
+    fn claim_profit() -> Weight;
+    fn claim_performance_fees() -> Weight;
+    fn request_redemption(r: u32) -> Weight;
+    fn cancel_redemption(r: u32) -> Weight;
+    fn request_remote_swap() -> Weight;
+    fn remote_swap_executed() -> Weight;
+    fn expire_remote_swap() -> Weight;
+    fn request_balance_query(m: u32) -> Weight;
+    fn balance_query_response() -> Weight;
+    fn add_attestor() -> Weight;
+    fn rotate_attestor() -> Weight;
+    fn revoke_attestor() -> Weight;
+    fn set_xcm_trader_registrar() -> Weight;
+    fn attest_network_roots(m: u32) -> Weight;
+    fn liquidate_trader() -> Weight;
+}
+
+/// Estimated weights, on the RocksDb storage weights
+impl WeightInfo for () {
+    fn register_investor(r: u32) -> Weight {
+        estimate(60_000_000, 9, 8).saturating_add(fill_redemptions(r))
+    }
+    fn register_trader(m: u32) -> Weight {
+        estimate(90_000_000, 7, 5)
+            .saturating_add(Weight::from_parts(1_000_000, 0).saturating_mul(m.into()))
+    }
+    fn allocate_capital() -> Weight {
+        estimate(55_000_000, 6, 3)
+    }
+    fn verify_trade_execution() -> Weight {
+        estimate(150_000_000, 9, 6)
+    }
+    fn create_pool() -> Weight {
+        estimate(40_000_000, 4, 4)
+    }
+    fn set_pool_params() -> Weight {
+        estimate(20_000_000, 1, 1)
+    }
+    fn pause_pool() -> Weight {
+        estimate(18_000_000, 1, 1)
+    }
+    fn resume_pool() -> Weight {
+        estimate(18_000_000, 1, 1)
+    }
+    fn close_pool() -> Weight {
+        estimate(80_000_000, 20, 20)
+    }
+    fn withdraw_capital() -> Weight {
+        estimate(60_000_000, 9, 8)
+    }
+    fn add_relayer(m: u32) -> Weight {
+        estimate(15_000_000, 1, 1)
+            .saturating_add(Weight::from_parts(200_000, 0).saturating_mul(m.into()))
+    }
+    fn remove_relayer(m: u32) -> Weight {
+        estimate(20_000_000, 2, 3)
+            .saturating_add(Weight::from_parts(200_000, 0).saturating_mul(m.into()))
+    }
+    fn rotate_relayer(m: u32) -> Weight {
+        estimate(18_000_000, 1, 2)
+            .saturating_add(Weight::from_parts(200_000, 0).saturating_mul(m.into()))
+    }
+    fn set_relayer_threshold() -> Weight {
+        estimate(12_000_000, 1, 1)
+    }
+    fn report_relayer(m: u32) -> Weight {
+        estimate(25_000_000, 4, 4)
+            .saturating_add(Weight::from_parts(400_000, 0).saturating_mul(m.into()))
+    }
+    fn apply_as_trader() -> Weight {
+        estimate(35_000_000, 4, 2)
+    }
+    fn approve_trader() -> Weight {
+        estimate(18_000_000, 1, 1)
+    }
+    fn suspend_trader() -> Weight {
+        estimate(30_000_000, 3, 2)
+    }
+    fn reinstate_trader() -> Weight {
+        estimate(18_000_000, 1, 1)
+    }
+    fn deregister_trader() -> Weight {
+        estimate(35_000_000, 4, 4)
+    }
+    fn settle_allocation(m: u32, r: u32) -> Weight {
+        estimate(120_000_000, 16, 13)
+            .saturating_add(Weight::from_parts(1_000_000, 0).saturating_mul(m.into()))
+            .saturating_add(fill_redemptions(r))
+    }
+    fn set_target_network() -> Weight {
+        estimate(12_000_000, 0, 1)
+    }
+    fn remove_target_network() -> Weight {
+        estimate(40_000_000, 100, 1)
+    }
+    fn set_dex_venue() -> Weight {
+        estimate(15_000_000, 1, 1)
+    }
+    fn remove_dex_venue() -> Weight {
+        estimate(12_000_000, 1, 1)
+    }
+    fn set_network_asset() -> Weight {
+        estimate(20_000_000, 2, 1)
+    }
+    fn set_risk_policy() -> Weight {
+        estimate(20_000_000, 9, 1)
+    }
+    fn pay_insurance_claim() -> Weight {
+        estimate(35_000_000, 3, 2)
+    }
+    fn claim_profit() -> Weight {
+        estimate(45_000_000, 7, 6)
+    }
+    fn claim_performance_fees() -> Weight {
+        estimate(40_000_000, 4, 4)
+    }
+    fn request_redemption(r: u32) -> Weight {
+        estimate(45_000_000, 8, 6).saturating_add(fill_redemptions(r))
+    }
+    fn cancel_redemption(r: u32) -> Weight {
+        estimate(30_000_000, 3, 2).saturating_add(fill_redemptions(r))
+    }
+    fn request_remote_swap() -> Weight {
+        estimate(70_000_000, 8, 5)
+    }
+    fn remote_swap_executed() -> Weight {
+        estimate(20_000_000, 2, 2)
+    }
+    fn expire_remote_swap() -> Weight {
+        estimate(15_000_000, 1, 1)
+    }
+    fn request_balance_query(m: u32) -> Weight {
+        estimate(60_000_000, 8, 6)
+            .saturating_add(Weight::from_parts(1_000_000, 0).saturating_mul(m.into()))
+    }
+    fn balance_query_response() -> Weight {
+        estimate(50_000_000, 5, 4)
+    }
+    fn add_attestor() -> Weight {
+        estimate(20_000_000, 2, 2)
+    }
+    fn rotate_attestor() -> Weight {
+        estimate(25_000_000, 3, 3)
+    }
+    fn revoke_attestor() -> Weight {
+        estimate(20_000_000, 2, 2)
+    }
+    fn set_xcm_trader_registrar() -> Weight {
+        estimate(10_000_000, 0, 1)
+    }
+    fn attest_network_roots(m: u32) -> Weight {
+        estimate(30_000_000, 4, 3)
+            .saturating_add(Weight::from_parts(1_000_000, 0).saturating_mul(m.into()))
+    }
+    // Liquidation of a stale trader in `on_initialize`, the capital recall sending an XCM message included
+    fn liquidate_trader() -> Weight {
+        estimate(150_000_000, 8, 6)
+    }
+}
+
+// Fixed execution cost with `reads` and `writes` storage accesses
+fn estimate(ref_time: u64, reads: u64, writes: u64) -> Weight {
+    Weight::from_parts(ref_time, 0).saturating_add(RocksDbWeight::get().reads_writes(reads, writes))
+}
+
+// Filling `r` redemption requests, each one syncs and stores the investor position and pays it out
+fn fill_redemptions(r: u32) -> Weight {
+    Weight::from_parts(30_000_000, 0)
+        .saturating_add(RocksDbWeight::get().reads_writes(6, 6))
+        .saturating_mul(r.into())
+}
@@ -64,14 +64,21 @@ pub mod spectre_finance_container {
         super::*,
         crate::{
             integration_test_xcm::accounts::{sudo_key, ALICE},
-            AssetId, Balance, EXISTENTIAL_DEPOSIT,
+            AssetId, Balance, CustomMetadata, EXISTENTIAL_DEPOSIT,
         },
         orml_traits::asset_registry::AssetMetadata,
         sp_core::crypto::Ss58Codec,
-        staging_xcm::{latest::Junction, VersionedMultiLocation},
+        staging_xcm::{
+            latest::prelude::{GeneralIndex, PalletInstance, Parachain, X3},
+            VersionedMultiLocation,
+        },
     };
 
     pub const PARA_ID: u32 = 2000;
+    pub const ASSET_HUB_PARA_ID: u32 = 1000;
+    pub const ASSETS_PALLET_INDEX: u8 = 50;
+    pub const USDT_ID: u32 = 1984;
+    pub const USDC_ID: u32 = 1337;
     pub const ED: Balance = EXISTENTIAL_DEPOSIT;
     pub fn genesis() -> Storage {
         // Calculate parachain Soverign account id
@@ -81,39 +88,40 @@ pub mod spectre_finance_container {
 
         // ---******* GENESIS CONFIG ********---//
 
-        // asset metadata
-        let sf_dot = AssetMetadata::<Balance, Vec<u8>, ConstU32<10>> {
-            decimals: 12,
-            name: BoundedVec::truncate_from("sfDOT".as_bytes().to_vec()),
-            symbol: BoundedVec::truncate_from("sfDOT".as_bytes().to_vec()),
-            existential_deposit: 0,
-            location: Some(VersionedMultiLocation::V3(MultiLocation::new(
-                0,
-                Junction::GeneralIndex(1),
-            ))),
-            additional: b"spectre finance derived token".to_vec(),
-        };
-        let sf_usdt = AssetMetadata::<Balance, Vec<u8>, ConstU32<10>> {
-            decimals: 12,
-            name: BoundedVec::truncate_from("sfUSDT".as_bytes().to_vec()),
-            symbol: BoundedVec::truncate_from("sfUSDT".as_bytes().to_vec()),
-            existential_deposit: 0,
+        // asset metadata, the stablecoins are reserve transferred from AssetHub
+        let usdt = AssetMetadata::<Balance, CustomMetadata, ConstU32<30>> {
+            decimals: 6,
+            name: BoundedVec::truncate_from("Tether USD".as_bytes().to_vec()),
+            symbol: BoundedVec::truncate_from("USDT".as_bytes().to_vec()),
+            existential_deposit: 1_000,
             location: Some(VersionedMultiLocation::V3(MultiLocation::new(
-                0,
-                Junction::GeneralIndex(2),
+                1,
+                X3(
+                    Parachain(ASSET_HUB_PARA_ID),
+                    PalletInstance(ASSETS_PALLET_INDEX),
+                    GeneralIndex(USDT_ID.into()),
+                ),
             ))),
-            additional: b"spectre finance derived token".to_vec(),
+            additional: CustomMetadata {
+                fee_per_second: 1_000_000,
+            },
         };
-        let sf_usdc = AssetMetadata::<Balance, Vec<u8>, ConstU32<10>> {
-            decimals: 12,
-            name: BoundedVec::truncate_from("sfUSDC".as_bytes().to_vec()),
-            symbol: BoundedVec::truncate_from("sUSDC".as_bytes().to_vec()),
-            existential_deposit: 0,
+        let usdc = AssetMetadata::<Balance, CustomMetadata, ConstU32<30>> {
+            decimals: 6,
+            name: BoundedVec::truncate_from("USD Coin".as_bytes().to_vec()),
+            symbol: BoundedVec::truncate_from("USDC".as_bytes().to_vec()),
+            existential_deposit: 1_000,
             location: Some(VersionedMultiLocation::V3(MultiLocation::new(
-                0,
-                Junction::GeneralIndex(3),
+                1,
+                X3(
+                    Parachain(ASSET_HUB_PARA_ID),
+                    PalletInstance(ASSETS_PALLET_INDEX),
+                    GeneralIndex(USDC_ID.into()),
+                ),
             ))),
-            additional: b"spectre finance derived token".to_vec(),
+            additional: CustomMetadata {
+                fee_per_second: 1_000_000,
+            },
         };
 
         let revert_bytecode = vec![0x60, 0x00, 0x60, 0x00, 0xFD];
@@ -162,15 +170,15 @@ pub mod spectre_finance_container {
             tx_pause: Default::default(),
             asset_registry: crate::AssetRegistryConfig {
                 assets: vec![
-                    (1, sf_dot.encode()),
-                    (2, sf_usdt.encode()),
-                    (3, sf_usdc.encode()),
+                    (crate::USDT_ASSET_ID, usdt.encode()),
+                    (crate::USDC_ASSET_ID, usdc.encode()),
                 ],
-                last_asset_id: 3,
+                last_asset_id: crate::USDC_ASSET_ID,
             },
             assets: crate::AssetsConfig { balances: vec![] },
             spectre: crate::SpectreConfig {
-                relayer: Some(alice.into()),
+                relayers: vec![alice.into()],
+                relayer_threshold: 1,
                 initial_capital: 0,
                 supported_assets: vec![],
                 fee: 10, // percentage
@@ -311,7 +319,34 @@ fn system_para_to_para_reserve_transfer_assets(t: SystemParaToParaTest) -> Dispa
 
 #[cfg(test)]
 mod tests {
-    use {super::*, fp_account::AccountId20, hex_literal::hex};
+    use {
+        super::*,
+        crate::{
+            precompiles::FOREIGN_ASSET_PRECOMPILE_ADDRESS_PREFIX,
+            xcm_config::{xc20_address, HydraDxOmnipoolTrades, MAX_XC20_ASSET_ID},
+            AssetId, Balance, DOT_ASSET_ID, POOL_SHARE_ASSET_OFFSET, UNIT, USDT_ASSET_ID,
+        },
+        fp_account::AccountId20,
+        frame_support::{assert_noop, assert_ok},
+        hex_literal::hex,
+        orml_traits::MultiCurrency,
+        pallet_evm_precompileset_assets_erc20::AccountIdAssetIdConversion,
+        pallet_spectre::{
+            util::{
+                attestation_payload, DexVenue, FinalitySource, HashAlgorithm, NetworkId,
+                NetworkInfo, Networks, PoolParams, ProofKind, RemoteAccount, SupportedDexs,
+                SwapCalls, TradeDecoder, TraderStatus, TradingAccounts, TradingAccountsAttestation,
+            },
+            CapitalPool, InvestorPositions, RedemptionQueues, RegistrarAttestors, TraderProfiles,
+        },
+        parity_scale_codec::Compact,
+        sp_core::{sr25519, Pair as _},
+        sp_std::collections::btree_map::BTreeMap,
+        staging_xcm::latest::prelude::{
+            AccountId32, AccountKey20, GeneralIndex, Here, Junctions, PalletInstance, Parachain,
+            WeightLimit, X2,
+        },
+    };
 
     #[test]
     fn trader_registration_works() {
@@ -341,12 +376,713 @@ mod tests {
         relay_to_para_test.assert();
     }
 
+    const INVESTOR: [u8; 20] = hex!("3Cd0A705a2DC65e5b1E1205896BaA2be8A07c6e0");
+    const TRADER: [u8; 20] = hex!("798d4Ba9baf0064Ec19eB4F0a1a45785ae9D6DFc");
+    const TRADING_NETWORK: NetworkId = 1;
+
+    type SpectreOrigin = <SpectreFinanceContainer<RococoNet> as Chain>::RuntimeOrigin;
+    type AssetHubOrigin = <AssetHubRococo<RococoNet> as Chain>::RuntimeOrigin;
+
+    fn beneficiary_location(account: AccountId20) -> MultiLocation {
+        AccountKey20 {
+            network: None,
+            key: account.into(),
+        }
+        .into()
+    }
+
+    /// Reserve transfer DOT from the relay chain to an account of the spectre chain
+    fn transfer_dot(beneficiary: AccountId20, amount: Balance) {
+        transfer_dot_to(beneficiary_location(beneficiary), beneficiary, amount)
+    }
+
+    fn transfer_dot_to(beneficiary: MultiLocation, receiver: AccountId20, amount: Balance) {
+        let test_args = TestContext {
+            sender: RococoRelaySender::get(),
+            receiver,
+            args: TestArgs {
+                dest: Rococo::<RococoNet>::child_location_of(
+                    SpectreFinanceContainer::<RococoNet>::para_id(),
+                ),
+                beneficiary,
+                amount,
+                assets: (Here, amount).into(),
+                asset_id: None,
+                fee_asset_item: 0,
+                weight_limit: WeightLimit::Unlimited,
+            },
+        };
+
+        let mut relay_to_para_test = RelayToParaTest::new(test_args);
+        relay_to_para_test
+            .set_dispatchable::<Rococo<RococoNet>>(relay_to_para_reserve_transfer_assets);
+        relay_to_para_test.assert();
+    }
+
+    /// Create USDT on AssetHub, owned by the AssetHub sender
+    fn create_usdt() {
+        AssetHubRococo::<RococoNet>::execute_with(|| {
+            type Assets = <AssetHubRococo<RococoNet> as AssetHubRococoParaPallet>::Assets;
+            assert_ok!(Assets::force_create(
+                AssetHubOrigin::root(),
+                USDT_ID.into(),
+                AssetHubRococoParaSender::get().into(),
+                true,
+                1_000,
+            ));
+        });
+    }
+
+    /// Mint USDT on AssetHub and reserve transfer it to an account of the spectre chain
+    fn transfer_usdt(beneficiary: AccountId20, amount: Balance) {
+        transfer_usdt_to(beneficiary_location(beneficiary), beneficiary, amount)
+    }
+
+    fn transfer_usdt_to(beneficiary: MultiLocation, receiver: AccountId20, amount: Balance) {
+        let owner = AssetHubRococoParaSender::get();
+        AssetHubRococo::<RococoNet>::execute_with(|| {
+            type Assets = <AssetHubRococo<RococoNet> as AssetHubRococoParaPallet>::Assets;
+            assert_ok!(Assets::mint(
+                AssetHubOrigin::signed(owner.clone()),
+                USDT_ID.into(),
+                owner.clone().into(),
+                amount,
+            ));
+        });
+
+        let usdt = MultiLocation::new(
+            0,
+            X2(
+                PalletInstance(ASSETS_PALLET_INDEX),
+                GeneralIndex(USDT_ID.into()),
+            ),
+        );
+        let test_args = TestContext {
+            sender: owner,
+            receiver,
+            args: TestArgs {
+                dest: AssetHubRococo::<RococoNet>::sibling_location_of(SpectreFinanceContainer::<
+                    RococoNet,
+                >::para_id(
+                )),
+                beneficiary,
+                amount,
+                assets: (usdt, amount).into(),
+                asset_id: Some(USDT_ID),
+                fee_asset_item: 0,
+                weight_limit: WeightLimit::Unlimited,
+            },
+        };
+
+        let mut system_para_to_para_test = SystemParaToParaTest::new(test_args);
+        system_para_to_para_test.set_dispatchable::<AssetHubRococo<RococoNet>>(
+            system_para_to_para_reserve_transfer_assets,
+        );
+        system_para_to_para_test.assert();
+    }
+
+    fn free_balance(asset_id: AssetId, account: &AccountId20) -> Balance {
+        <crate::Assets as MultiCurrency<AccountId20>>::free_balance(asset_id, account)
+    }
+
+    fn create_pool(asset_id: AssetId) {
+        SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            assert_ok!(crate::Spectre::create_pool(
+                SpectreOrigin::root(),
+                asset_id,
+                PoolParams::default(),
+            ));
+        });
+    }
+
+    /// Create the pool of an asset and deposit the investor capital in it
+    fn register_investor(asset_id: AssetId, capital: Balance) {
+        let investor = AccountId20::from(INVESTOR);
+        create_pool(asset_id);
+        SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            assert_ok!(crate::Spectre::register_investor(
+                SpectreOrigin::signed(investor),
+                asset_id,
+                capital,
+            ));
+
+            let pool = CapitalPool::<crate::Runtime>::get(asset_id);
+            assert_eq!(pool.total_capital, capital);
+            assert_eq!(free_balance(asset_id, &pool.account_id), capital);
+            assert_eq!(
+                free_balance(pool.share_asset_id.unwrap(), &investor),
+                capital
+            );
+            assert_eq!(
+                InvestorPositions::<crate::Runtime>::get(investor, asset_id)
+                    .unwrap()
+                    .deposited,
+                capital
+            );
+        });
+    }
+
+    /// Key of the worker that generates the trading accounts of the traders and attests them
+    fn attestor() -> sr25519::Pair {
+        sr25519::Pair::from_string("//Attestor", None).unwrap()
+    }
+
+    /// Substrate trading account of the trader with its attestation by `attestor`
+    fn attested_trading_accounts(
+        trader: AccountId20,
+    ) -> (TradingAccounts, TradingAccountsAttestation) {
+        let trading_accounts = TradingAccounts(
+            BoundedBTreeMap::try_from(BTreeMap::from([(
+                Networks::Substrate,
+                RemoteAccount::AccountId32([1u8; 32]),
+            )]))
+            .unwrap(),
+        );
+        let attestation = TradingAccountsAttestation {
+            attestor: attestor().public(),
+            nonce: 1,
+            signature: attestor().sign(&attestation_payload(&trader, &trading_accounts, 1)),
+        };
+        (trading_accounts, attestation)
+    }
+
+    /// Target network of the trader, HydraDX checked with trade proofs or Asset Hub checked with XCM queries
+    fn trading_network(proof_kind: ProofKind) -> NetworkInfo {
+        let chain_id = match proof_kind {
+            ProofKind::XcmQuery => ASSET_HUB_PARA_ID.into(),
+            _ => 2034,
+        };
+        NetworkInfo {
+            kind: Networks::Substrate,
+            chain_id,
+            hasher: HashAlgorithm::Blake2,
+            proof_kind,
+            finality: FinalitySource::RelayChain,
+            enabled: true,
+        }
+    }
+
+    /// Bond, register and approve the trader, then allocate capital to its trading account
+    fn allocate_capital(asset_id: AssetId, bond: Balance, allocation: Balance) {
+        allocate_capital_on(
+            trading_network(ProofKind::SubstrateTrie),
+            asset_id,
+            bond,
+            allocation,
+        )
+    }
+
+    fn allocate_capital_on(
+        network: NetworkInfo,
+        asset_id: AssetId,
+        bond: Balance,
+        allocation: Balance,
+    ) {
+        let trader = AccountId20::from(TRADER);
+        SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            assert_ok!(crate::Spectre::apply_as_trader(
+                SpectreOrigin::signed(trader),
+                asset_id,
+                bond,
+            ));
+            assert_ok!(crate::Spectre::add_attestor(
+                SpectreOrigin::root(),
+                attestor().public()
+            ));
+            let (trading_accounts, attestation) = attested_trading_accounts(trader);
+            assert_ok!(crate::Spectre::register_trader(
+                SpectreOrigin::signed(accounts::sudo_key()),
+                trader,
+                trading_accounts,
+                attestation,
+            ));
+            assert_ok!(crate::Spectre::approve_trader(
+                SpectreOrigin::root(),
+                trader
+            ));
+            assert_ok!(crate::Spectre::set_target_network(
+                SpectreOrigin::root(),
+                TRADING_NETWORK,
+                network,
+            ));
+            assert_ok!(crate::Spectre::allocate_capital(
+                SpectreOrigin::signed(trader),
+                TRADING_NETWORK,
+                allocation,
+            ));
+
+            let pool = CapitalPool::<crate::Runtime>::get(asset_id);
+            assert_eq!(pool.total_allocated_capital, allocation);
+            let trader_profile = TraderProfiles::<crate::Runtime>::get(trader).unwrap();
+            assert_eq!(trader_profile.status, TraderStatus::Active);
+            assert_eq!(trader_profile.funds_allocated, allocation);
+        });
+    }
+
+    #[test]
+    fn investor_registration_works() {
+        let investor = AccountId20::from(INVESTOR);
+        let amount = 10 * UNIT;
+        transfer_dot(investor, amount);
+
+        // the DOT minus the execution fees lands in orml_tokens
+        let received = SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            free_balance(DOT_ASSET_ID, &investor)
+        });
+        assert!(received > 0 && received <= amount);
+
+        register_investor(DOT_ASSET_ID, received / 2);
+        SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            assert_eq!(
+                free_balance(DOT_ASSET_ID, &investor),
+                received - received / 2
+            );
+        });
+    }
+
+    #[test]
+    fn capital_allocation_works_dot() {
+        let investor = AccountId20::from(INVESTOR);
+        let trader = AccountId20::from(TRADER);
+        transfer_dot(investor, 10 * UNIT);
+        transfer_dot(trader, UNIT);
+
+        register_investor(DOT_ASSET_ID, 5 * UNIT);
+        allocate_capital(DOT_ASSET_ID, UNIT / 2, 2 * UNIT);
+    }
+
+    #[test]
+    fn capital_allocation_funds_the_pool_trading_account_on_xcm_networks() {
+        let investor = AccountId20::from(INVESTOR);
+        let trader = AccountId20::from(TRADER);
+        transfer_dot(investor, 10 * UNIT);
+        transfer_dot(trader, UNIT);
+        register_investor(DOT_ASSET_ID, 5 * UNIT);
+
+        // the capital leaves the pool account for the account the remote swaps run from
+        allocate_capital_on(
+            trading_network(ProofKind::XcmQuery),
+            DOT_ASSET_ID,
+            UNIT / 2,
+            2 * UNIT,
+        );
+        SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            let pool = CapitalPool::<crate::Runtime>::get(DOT_ASSET_ID);
+            assert_eq!(free_balance(DOT_ASSET_ID, &pool.account_id), 3 * UNIT);
+        });
+    }
+
+    #[test]
+    fn exit_recalls_the_capital_of_the_pool_trading_account_on_xcm_networks() {
+        let investor = AccountId20::from(INVESTOR);
+        let trader = AccountId20::from(TRADER);
+        transfer_dot(investor, 10 * UNIT);
+        transfer_dot(trader, UNIT);
+        register_investor(DOT_ASSET_ID, 5 * UNIT);
+        allocate_capital_on(
+            trading_network(ProofKind::XcmQuery),
+            DOT_ASSET_ID,
+            UNIT / 2,
+            2 * UNIT,
+        );
+
+        SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            crate::System::reset_events();
+            assert_ok!(crate::Spectre::deregister_trader(SpectreOrigin::signed(
+                trader
+            )));
+            assert_eq!(
+                TraderProfiles::<crate::Runtime>::get(trader)
+                    .unwrap()
+                    .status,
+                TraderStatus::Exiting
+            );
+            // the recall leaves for the trading network, the allocation is settled once it is back
+            assert!(crate::System::events().iter().any(|record| matches!(
+                record.event,
+                crate::RuntimeEvent::XcmpQueue(
+                    cumulus_pallet_xcmp_queue::Event::XcmpMessageSent { .. }
+                )
+            )));
+            let pool = CapitalPool::<crate::Runtime>::get(DOT_ASSET_ID);
+            assert_eq!(pool.total_allocated_capital, 2 * UNIT);
+            assert_eq!(free_balance(DOT_ASSET_ID, &pool.account_id), 3 * UNIT);
+        });
+    }
+
+    #[test]
+    fn capital_allocation_works_stablecoin() {
+        let investor = AccountId20::from(INVESTOR);
+        let trader = AccountId20::from(TRADER);
+        let usdt_unit = 1_000_000;
+        create_usdt();
+        transfer_usdt(investor, 1_000 * usdt_unit);
+        transfer_usdt(trader, 100 * usdt_unit);
+
+        SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            assert!(free_balance(USDT_ASSET_ID, &investor) > 0);
+        });
+
+        register_investor(USDT_ASSET_ID, 500 * usdt_unit);
+        allocate_capital(USDT_ASSET_ID, 50 * usdt_unit, 200 * usdt_unit);
+    }
+
+    /// Move the spectre chain past the investor withdraw period
+    fn pass_withdraw_period() {
+        SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            let now = crate::System::block_number();
+            crate::System::set_block_number(now + crate::WithdrawPeriod::get());
+        });
+    }
+
+    /// Return `returned` capital of the trader allocation to the pool account and settle it as the relayer
+    fn settle_allocation(asset_id: AssetId, returned: Balance) {
+        let trader = AccountId20::from(TRADER);
+        SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            let pool = CapitalPool::<crate::Runtime>::get(asset_id);
+            assert_ok!(<crate::Assets as MultiCurrency<AccountId20>>::deposit(
+                asset_id,
+                &pool.account_id,
+                returned,
+            ));
+            assert_ok!(crate::Spectre::settle_allocation(
+                SpectreOrigin::signed(accounts::sudo_key()),
+                trader,
+                returned,
+            ));
+        });
+    }
+
+    #[test]
+    fn capital_withdrawal_works() {
+        let investor = AccountId20::from(INVESTOR);
+        transfer_dot(investor, 10 * UNIT);
+        register_investor(DOT_ASSET_ID, 5 * UNIT);
+
+        SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            assert_noop!(
+                crate::Spectre::withdraw_capital(
+                    SpectreOrigin::signed(investor),
+                    DOT_ASSET_ID,
+                    UNIT,
+                ),
+                pallet_spectre::Error::<crate::Runtime>::WithdrawPeriodNotElapsed
+            );
+        });
+
+        pass_withdraw_period();
+        SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            let balance = free_balance(DOT_ASSET_ID, &investor);
+            assert_ok!(crate::Spectre::withdraw_capital(
+                SpectreOrigin::signed(investor),
+                DOT_ASSET_ID,
+                2 * UNIT,
+            ));
+
+            let pool = CapitalPool::<crate::Runtime>::get(DOT_ASSET_ID);
+            assert_eq!(pool.total_capital, 3 * UNIT);
+            assert_eq!(free_balance(DOT_ASSET_ID, &investor), balance + 2 * UNIT);
+            assert_eq!(
+                free_balance(pool.share_asset_id.unwrap(), &investor),
+                3 * UNIT
+            );
+        });
+    }
+
+    #[test]
+    fn allocation_settlement_works() {
+        let investor = AccountId20::from(INVESTOR);
+        let trader = AccountId20::from(TRADER);
+        transfer_dot(investor, 10 * UNIT);
+        transfer_dot(trader, UNIT);
+        register_investor(DOT_ASSET_ID, 5 * UNIT);
+        allocate_capital(DOT_ASSET_ID, UNIT / 2, 2 * UNIT);
+
+        SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            // nothing came back to the pool account yet
+            assert_noop!(
+                crate::Spectre::settle_allocation(
+                    SpectreOrigin::signed(accounts::sudo_key()),
+                    trader,
+                    2 * UNIT,
+                ),
+                pallet_spectre::Error::<crate::Runtime>::CapitalNotReturned
+            );
+        });
+
+        settle_allocation(DOT_ASSET_ID, 2 * UNIT);
+        SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            let pool = CapitalPool::<crate::Runtime>::get(DOT_ASSET_ID);
+            assert_eq!(pool.total_allocated_capital, 0);
+            assert_eq!(pool.remaining_capital, 5 * UNIT);
+            let trader_profile = TraderProfiles::<crate::Runtime>::get(trader).unwrap();
+            assert_eq!(trader_profile.funds_allocated, 0);
+            assert_eq!(trader_profile.network, None);
+        });
+    }
+
     #[test]
-    fn investor_registration_works() {}
+    fn redemption_is_filled_by_returned_capital() {
+        let investor = AccountId20::from(INVESTOR);
+        let trader = AccountId20::from(TRADER);
+        transfer_dot(investor, 10 * UNIT);
+        transfer_dot(trader, UNIT);
+        register_investor(DOT_ASSET_ID, 5 * UNIT);
+        allocate_capital(DOT_ASSET_ID, UNIT / 2, 2 * UNIT);
+        pass_withdraw_period();
+
+        let share_asset_id = SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            let share_asset_id = CapitalPool::<crate::Runtime>::get(DOT_ASSET_ID)
+                .share_asset_id
+                .unwrap();
+            // the 3 unallocated DOT fill the request partially, the rest waits for the allocation
+            assert_ok!(crate::Spectre::request_redemption(
+                SpectreOrigin::signed(investor),
+                DOT_ASSET_ID,
+                4 * UNIT,
+            ));
+            let queue = RedemptionQueues::<crate::Runtime>::get(DOT_ASSET_ID);
+            assert_eq!(queue.len(), 1);
+            assert_eq!(queue[0].shares, UNIT);
+            assert_eq!(
+                CapitalPool::<crate::Runtime>::get(DOT_ASSET_ID).remaining_capital,
+                0
+            );
+            share_asset_id
+        });
+
+        let balance = SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            free_balance(DOT_ASSET_ID, &investor)
+        });
+        settle_allocation(DOT_ASSET_ID, 2 * UNIT);
+        SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            assert!(RedemptionQueues::<crate::Runtime>::get(DOT_ASSET_ID).is_empty());
+            assert_eq!(free_balance(DOT_ASSET_ID, &investor), balance + UNIT);
+            assert_eq!(free_balance(share_asset_id, &investor), UNIT);
+            assert_eq!(
+                CapitalPool::<crate::Runtime>::get(DOT_ASSET_ID).remaining_capital,
+                UNIT
+            );
+        });
+    }
 
     #[test]
-    fn capital_allocation_works_dot() {}
+    fn xc20_addresses_only_cover_u16_asset_ids() {
+        let prefix = FOREIGN_ASSET_PRECOMPILE_ADDRESS_PREFIX;
+        let address = xc20_address(prefix, MAX_XC20_ASSET_ID).unwrap();
+        assert_eq!(
+            crate::Runtime::account_to_asset_id(address),
+            Some((prefix.to_vec(), MAX_XC20_ASSET_ID))
+        );
+
+        // Larger ids, the pool share tokens included, do not alias the asset with their lowest 2 bytes
+        let share_asset_id = DOT_ASSET_ID | POOL_SHARE_ASSET_OFFSET;
+        assert_eq!(xc20_address(prefix, share_asset_id), None);
+        assert_eq!(xc20_address(prefix, MAX_XC20_ASSET_ID + 1), None);
+        let account = crate::Runtime::asset_id_to_account(prefix, share_asset_id);
+        assert_eq!(crate::Runtime::account_to_asset_id(account), None);
+    }
+
+    /// Location under which the deposited assets go to the spectre pool of the asset
+    fn pool_deposit_location(investor: impl Into<Junctions>) -> MultiLocation {
+        let spectre = PalletInstance(<crate::Spectre as PalletInfoAccess>::index() as u8);
+        let mut location = MultiLocation::new(0, investor);
+        assert_ok!(location.push_front_interior(spectre));
+        location
+    }
 
     #[test]
-    fn capital_allocation_works_stablecoin() {}
+    fn xcm_pool_deposit_works() {
+        let investor = AccountId20::from(INVESTOR);
+        create_pool(DOT_ASSET_ID);
+
+        // a relay chain transfer names a local investor account
+        let amount = 10 * UNIT;
+        transfer_dot_to(
+            pool_deposit_location(AccountKey20 {
+                network: None,
+                key: investor.into(),
+            }),
+            investor,
+            amount,
+        );
+        SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            let position =
+                InvestorPositions::<crate::Runtime>::get(investor, DOT_ASSET_ID).unwrap();
+            // the whole deposit minus the execution fees is in the pool
+            assert!(position.deposited > 0 && position.deposited <= amount);
+            assert_eq!(free_balance(DOT_ASSET_ID, &investor), 0);
+            let pool = CapitalPool::<crate::Runtime>::get(DOT_ASSET_ID);
+            assert_eq!(pool.total_capital, position.deposited);
+        });
+
+        // an AssetHub investor is credited to the account its `Transact`s dispatch from
+        let sibling_investor = AccountId32 {
+            network: None,
+            id: AssetHubRococoParaSender::get().into(),
+        };
+        let sibling_investor_account = SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            crate::xcm_config::LocationToAccountId::convert_location(&MultiLocation::new(
+                1,
+                X2(Parachain(ASSET_HUB_PARA_ID), sibling_investor),
+            ))
+            .unwrap()
+        });
+        let usdt_amount = 1_000_000_000;
+        create_usdt();
+        create_pool(USDT_ASSET_ID);
+        transfer_usdt_to(
+            pool_deposit_location(X2(Parachain(ASSET_HUB_PARA_ID), sibling_investor)),
+            sibling_investor_account,
+            usdt_amount,
+        );
+        SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            let position =
+                InvestorPositions::<crate::Runtime>::get(sibling_investor_account, USDT_ASSET_ID)
+                    .unwrap();
+            assert!(position.deposited > 0 && position.deposited <= usdt_amount);
+            let pool = CapitalPool::<crate::Runtime>::get(USDT_ASSET_ID);
+            assert_eq!(
+                free_balance(pool.share_asset_id.unwrap(), &sibling_investor_account),
+                position.deposited
+            );
+        });
+    }
+
+    #[test]
+    fn xcm_trader_registration_works() {
+        let trader = AccountId20::from(TRADER);
+        transfer_dot(trader, UNIT);
+        create_pool(DOT_ASSET_ID);
+
+        // the Phala contract dispatches `Transact` with `OriginKind::Xcm` from its parachain
+        let registrar = MultiLocation::new(
+            1,
+            X2(
+                Parachain(2035),
+                AccountId32 {
+                    network: None,
+                    id: [7u8; 32],
+                },
+            ),
+        );
+        let registrar_origin = || SpectreOrigin::from(pallet_xcm::Origin::Xcm(registrar));
+        SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            assert_ok!(crate::Spectre::apply_as_trader(
+                SpectreOrigin::signed(trader),
+                DOT_ASSET_ID,
+                UNIT / 2,
+            ));
+            let attestor = attestor();
+            // an unknown location is neither the registrar nor the attestor admin
+            assert_noop!(
+                crate::Spectre::add_attestor(registrar_origin(), attestor.public()),
+                DispatchError::BadOrigin
+            );
+            assert_ok!(crate::Spectre::set_xcm_trader_registrar(
+                SpectreOrigin::root(),
+                Some(registrar),
+            ));
+            // the registrar trusts the key of its worker, root keeps the other attestors out of its reach
+            assert_ok!(crate::Spectre::add_attestor(
+                registrar_origin(),
+                attestor.public()
+            ));
+            assert_eq!(
+                RegistrarAttestors::<crate::Runtime>::get().into_inner(),
+                vec![attestor.public()]
+            );
+            let (trading_accounts, attestation) = attested_trading_accounts(trader);
+            // no relayer is registered, the registrar registers the accounts on its own
+            assert_ok!(crate::Spectre::register_trader(
+                registrar_origin(),
+                trader,
+                trading_accounts,
+                attestation,
+            ));
+            assert_eq!(
+                TraderProfiles::<crate::Runtime>::get(trader)
+                    .unwrap()
+                    .status,
+                TraderStatus::AccountsConfirmed
+            );
+        });
+    }
+
+    // Signed HydraDX transaction of `call`, the signature is not checked by the decoder
+    fn hydradx_transaction(signer: [u8; 32], call: impl Encode) -> Vec<u8> {
+        let mut transaction = vec![0b1000_0100];
+        sp_runtime::MultiAddress::<sp_runtime::AccountId32, ()>::Id(signer.into())
+            .encode_to(&mut transaction);
+        sp_runtime::MultiSignature::Sr25519(sr25519::Signature::from_raw([0u8; 64]))
+            .encode_to(&mut transaction);
+        (
+            sp_runtime::generic::Era::Immortal,
+            Compact(0u32),
+            Compact(0 as Balance),
+        )
+            .encode_to(&mut transaction);
+        call.encode_to(&mut transaction);
+        transaction.encode()
+    }
+
+    #[test]
+    fn omnipool_trades_are_decoded() {
+        SpectreFinanceContainer::<RococoNet>::execute_with(|| {
+            let network = trading_network(ProofKind::SubstrateTrie);
+            assert_ok!(crate::Spectre::set_target_network(
+                SpectreOrigin::root(),
+                TRADING_NETWORK,
+                network.clone(),
+            ));
+            assert_ok!(crate::Spectre::set_dex_venue(
+                SpectreOrigin::root(),
+                1,
+                DexVenue {
+                    network: TRADING_NETWORK,
+                    dex: SupportedDexs::HydraDx,
+                    router: None,
+                    factory: None,
+                    pallet_index: Some(75),
+                    swap_calls: Some(SwapCalls { sell: 4, buy: 5 }),
+                    allowed_assets: Default::default(),
+                },
+            ));
+            let trading_account = RemoteAccount::AccountId32([1u8; 32]);
+            let decode = |transaction: Vec<u8>| {
+                HydraDxOmnipoolTrades::decode_trades(
+                    TRADING_NETWORK,
+                    &network,
+                    &trading_account,
+                    &transaction,
+                )
+            };
+
+            let sell = (75u8, 4u8, 10u32, 5u32, 100u128, 90u128);
+            let trades = decode(hydradx_transaction([1u8; 32], sell)).unwrap();
+            assert_eq!(trades.len(), 1);
+            assert_eq!(trades[0].venue, 1);
+            assert_eq!(trades[0].asset_in.to_vec(), 10u32.encode());
+            assert_eq!(trades[0].asset_out.to_vec(), 5u32.encode());
+            assert_eq!(trades[0].position_value, 100);
+
+            // buy 50 of asset 5 for at most 60 of asset 10
+            let buy = (75u8, 5u8, 5u32, 10u32, 50u128, 60u128);
+            let trades = decode(hydradx_transaction([1u8; 32], buy)).unwrap();
+            assert_eq!(trades[0].asset_out.to_vec(), 5u32.encode());
+            assert_eq!((trades[0].amount_in, trades[0].amount_out), (60, 50));
+
+            // calls of pallets that are not a registered venue are no trades
+            let transfer = (10u8, 0u8, [2u8; 32], 100u128);
+            assert!(decode(hydradx_transaction([1u8; 32], transfer))
+                .unwrap()
+                .is_empty());
+
+            // the trader is not accountable for transactions of other accounts
+            assert_noop!(
+                decode(hydradx_transaction([2u8; 32], sell)),
+                pallet_spectre::Error::<crate::Runtime>::InvalidTradeTransaction
+            );
+        });
+    }
 }
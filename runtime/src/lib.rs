@@ -70,6 +70,7 @@ use {
             ConstantMultiplier, Weight, WeightToFeeCoefficient, WeightToFeeCoefficients,
             WeightToFeePolynomial,
         },
+        BoundedVec, PalletId,
     },
     frame_system::{
         limits::{BlockLength, BlockWeights},
@@ -92,8 +93,8 @@ use {
     sp_runtime::{
         create_runtime_str, generic, impl_opaque_keys,
         traits::{
-            BlakeTwo256, Block as BlockT, DispatchInfoOf, Dispatchable, IdentifyAccount,
-            IdentityLookup, PostDispatchInfoOf, UniqueSaturatedInto, Verify,
+            AccountIdConversion, BlakeTwo256, Block as BlockT, DispatchInfoOf, Dispatchable,
+            IdentifyAccount, IdentityLookup, PostDispatchInfoOf, UniqueSaturatedInto, Verify,
         },
         transaction_validity::{
             InvalidTransaction, TransactionSource, TransactionValidity, TransactionValidityError,
@@ -109,14 +110,13 @@ pub use {
 };
 
 use {
-    cumulus_primitives_core::ParaId,
     frame_support::{
         pallet_prelude::EnsureOrigin,
         traits::{EnsureOriginWithArg, EqualPrivilegeOnly, Everything, Nothing},
     },
     frame_system::EnsureSignedBy,
     orml_traits::parameter_type_with_key,
-    sp_runtime::traits::Convert,
+    sp_runtime::traits::{Convert, MaybeEquivalence},
     staging_xcm::opaque::latest::{
         InteriorMultiLocation, Junction::*, Junctions::*, MultiAsset, MultiLocation, NetworkId,
     },
@@ -136,9 +136,13 @@ use {
 };
 // Orml
 use {
-    orml_asset_registry, orml_tokens,
-    orml_traits::{self, location::AbsoluteReserveProvider},
+    orml_asset_registry::{self, AssetRegistryTrader, FixedRateAssetRegistryTrader},
+    orml_tokens,
+    orml_traits::{
+        self, asset_registry::FixedConversionRateProvider, location::AbsoluteReserveProvider,
+    },
     orml_xtokens, pallet_spectre,
+    staging_xcm_executor::traits::TransactAsset,
 };
 
 // Polkadot imports
@@ -782,7 +786,7 @@ impl FindAuthor<H160> for FindAuthorAdapter {
 
 parameter_types! {
     pub BlockGasLimit: U256 = U256::from(BLOCK_GAS_LIMIT);
-    //pub PrecompilesValue: TemplatePrecompiles<Runtime> = TemplatePrecompiles::<_>::new();
+    pub PrecompilesValue: Precompiles = Precompiles::new();
     pub WeightPerGas: Weight = Weight::from_parts(weight_per_gas(BLOCK_GAS_LIMIT, NORMAL_DISPATCH_RATIO, WEIGHT_MILLISECS_PER_BLOCK), 0);
     pub SuicideQuickClearLimit: u32 = 0;
 }
@@ -798,8 +802,8 @@ impl pallet_evm::Config for Runtime {
     type AddressMapping = IdentityAddressMapping;
     type Currency = Balances;
     type RuntimeEvent = RuntimeEvent;
-    type PrecompilesType = ();
-    type PrecompilesValue = ();
+    type PrecompilesType = Precompiles;
+    type PrecompilesValue = PrecompilesValue;
     type ChainId = EVMChainId;
     type BlockGasLimit = BlockGasLimit;
     type Runner = pallet_evm::runner::stack::Runner<Self>;
@@ -918,7 +922,7 @@ impl orml_tokens::Config for Runtime {
     type RuntimeEvent = RuntimeEvent;
     type Balance = Balance;
     type Amount = i128;
-    type CurrencyHooks = (); // Hooks can be when user deposits, native tokens should be sent to their accounts
+    type CurrencyHooks = pallet_spectre::util::PoolShareHooks<Runtime>; // Moves spectre investor positions along with the pool share tokens
     type CurrencyId = AssetId;
     type DustRemovalWhitelist = Nothing;
     type ExistentialDeposits = ExistentialDeposits;
@@ -937,13 +941,8 @@ pub struct CurrencyIdConvert;
 impl Convert<AssetId, Option<MultiLocation>> for CurrencyIdConvert {
     fn convert(id: AssetId) -> Option<MultiLocation> {
         match id {
-            DOT_ASSET_ID => Some(MultiLocation::new(
-                1,
-                X2(
-                    Parachain(ParachainInfo::get().into()),
-                    GeneralIndex(id.into()),
-                ),
-            )),
+            // DOT is reserved on the relay chain
+            DOT_ASSET_ID => Some(MultiLocation::parent()),
             _ => AssetRegistry::multilocation(&id).unwrap_or_default(),
         }
     }
@@ -952,21 +951,20 @@ impl Convert<AssetId, Option<MultiLocation>> for CurrencyIdConvert {
 impl Convert<MultiLocation, Option<AssetId>> for CurrencyIdConvert {
     fn convert(location: MultiLocation) -> Option<AssetId> {
         match location {
+            // DOT reserve transferred from the relay chain
             MultiLocation {
-                parents,
-                interior: X2(Parachain(id), GeneralIndex(index)),
-            } if parents == 1
-                && ParaId::from(id) == ParachainInfo::get()
-                && (index as u32) == DOT_ASSET_ID =>
-            {
-                // Handling native asset for this parachain
-                Some(DOT_ASSET_ID)
-            }
+                parents: 1,
+                interior: Here,
+            } => Some(DOT_ASSET_ID),
             // handle reanchor canonical location: https://github.com/paritytech/polkadot/pull/4470
+            // reanchored location of the assets of this parachain, e.g. pool share tokens
             MultiLocation {
                 parents: 0,
                 interior: X1(GeneralIndex(index)),
-            } if (index as u32) == DOT_ASSET_ID => Some(DOT_ASSET_ID),
+            } => AssetRegistry::location_to_asset_id(MultiLocation::new(
+                1,
+                X2(Parachain(ParachainInfo::get().into()), GeneralIndex(index)),
+            )),
             // delegate to asset-registry
             _ => AssetRegistry::location_to_asset_id(location),
         }
@@ -987,6 +985,72 @@ impl Convert<MultiAsset, Option<AssetId>> for CurrencyIdConvert {
     }
 }
 
+/// Matches the `orml_tokens` assets of the XCM asset transactor through `CurrencyIdConvert`
+pub struct CurrencyIdEquivalence;
+
+impl MaybeEquivalence<MultiLocation, AssetId> for CurrencyIdEquivalence {
+    fn convert(location: &MultiLocation) -> Option<AssetId> {
+        <CurrencyIdConvert as Convert<MultiLocation, Option<AssetId>>>::convert(*location)
+    }
+
+    fn convert_back(asset_id: &AssetId) -> Option<MultiLocation> {
+        <CurrencyIdConvert as Convert<AssetId, Option<MultiLocation>>>::convert(*asset_id)
+    }
+}
+
+/// Name, symbol, decimals and existential deposit of a registered asset
+pub fn asset_info(asset_id: AssetId) -> Option<(Vec<u8>, Vec<u8>, u32, Balance)> {
+    match orml_asset_registry::Metadata::<Runtime>::get(asset_id) {
+        Some(metadata) => Some((
+            metadata.name.into_inner(),
+            metadata.symbol.into_inner(),
+            metadata.decimals,
+            metadata.existential_deposit,
+        )),
+        // DOT is not in the asset registry
+        None if asset_id == DOT_ASSET_ID => Some((
+            b"Polkadot".to_vec(),
+            b"DOT".to_vec(),
+            10,
+            ExistentialDeposit::get(),
+        )),
+        None => None,
+    }
+}
+
+/// Share tokens of the spectre capital pools use the upper half of the asset ids, above the
+/// XC20 range, their supply and balances are read through the vault of their pool
+pub const POOL_SHARE_ASSET_OFFSET: AssetId = 1 << 31;
+
+pub struct SpectrePoolShares;
+
+impl pallet_spectre::util::PoolShareAsset<Runtime> for SpectrePoolShares {
+    fn share_asset_id(asset_id: &AssetId) -> AssetId {
+        asset_id | POOL_SHARE_ASSET_OFFSET
+    }
+
+    fn share_metadata(
+        asset_id: &AssetId,
+    ) -> Option<pallet_spectre::util::AssetMetadataOf<Runtime>> {
+        let (name, symbol, decimals, existential_deposit) = asset_info(*asset_id)?;
+        let share_asset_id = Self::share_asset_id(asset_id);
+        Some(orml_traits::asset_registry::AssetMetadata {
+            decimals,
+            name: BoundedVec::truncate_from([b"Spectre Finance ".as_slice(), &name].concat()),
+            symbol: BoundedVec::truncate_from([b"sf".as_slice(), &symbol].concat()),
+            existential_deposit,
+            location: Some(staging_xcm::VersionedMultiLocation::V3(MultiLocation::new(
+                1,
+                X2(
+                    Parachain(ParachainInfo::get().into()),
+                    GeneralIndex(share_asset_id.into()),
+                ),
+            ))),
+            additional: Default::default(),
+        })
+    }
+}
+
 pub struct AccountIdToMultiLocation;
 impl Convert<AccountId, MultiLocation> for AccountIdToMultiLocation {
     fn convert(account: AccountId) -> MultiLocation {
@@ -1057,6 +1121,10 @@ parameter_types! {
     pub MaximumSchedulerWeight: Weight = Perbill::from_percent(80) *
         BlockWeights::default().max_block;
     pub WithdrawPeriod: BlockNumber = 400_000;
+    pub RiskViolationSlash: Permill = Permill::from_percent(10);
+    pub InsuranceFeeCut: Permill = Permill::from_percent(20);
+    pub InsuranceProfitCut: Permill = Permill::from_percent(2);
+    pub SpectreTreasuryAccount: AccountId = PalletId(*b"spc/trsy").into_account_truncating();
 }
 
 impl pallet_scheduler::Config for Runtime {
@@ -1097,15 +1165,62 @@ impl EnsureOriginWithArg<RuntimeOrigin, Option<u32>> for AssetAuthority {
         unimplemented!()
     }
 }
-#[derive(Eq, Debug, Clone, PartialEq, TypeInfo)]
+/// Runtime specific asset registry metadata
+/// `fee_per_second`: XCM execution fee charged in the asset per second of weight, 0 if the asset cannot pay for execution
+#[derive(Default, Copy, Clone, Eq, PartialEq, Encode, Decode, Debug, MaxEncodedLen, TypeInfo)]
 pub struct CustomMetadata {
     pub fee_per_second: u128,
 }
 
+parameter_types! {
+    // DOT is not in the asset registry, 0.01 DOT per second of weight
+    pub DotFeePerSecond: u128 = 100_000_000;
+}
+
+/// XCM execution fee of a registered asset per second of weight, `None` if it cannot pay for execution
+pub fn fee_per_second(asset_id: AssetId) -> Option<u128> {
+    let fee_per_second = match orml_asset_registry::Metadata::<Runtime>::get(asset_id) {
+        Some(metadata) => metadata.additional.fee_per_second,
+        None if asset_id == DOT_ASSET_ID => DotFeePerSecond::get(),
+        None => return None,
+    };
+    (fee_per_second > 0).then_some(fee_per_second)
+}
+
+pub struct AssetFeePerSecond;
+
+impl FixedConversionRateProvider for AssetFeePerSecond {
+    fn get_fee_per_second(location: &MultiLocation) -> Option<u128> {
+        let asset_id =
+            <CurrencyIdConvert as Convert<MultiLocation, Option<AssetId>>>::convert(*location)?;
+        fee_per_second(asset_id)
+    }
+}
+
+/// Sends the XCM execution fees to the spectre treasury
+pub struct ToSpectreTreasury;
+
+impl TakeRevenue for ToSpectreTreasury {
+    fn take_revenue(revenue: MultiAsset) {
+        let treasury = MultiLocation::new(
+            0,
+            X1(AccountKey20 {
+                network: None,
+                key: SpectreTreasuryAccount::get().into(),
+            }),
+        );
+        let _ = xcm_config::AssetTransactors::deposit_asset(&revenue, &treasury, None);
+    }
+}
+
+/// Buys XCM execution with DOT and the registered assets at their `fee_per_second`
+pub type AssetTrader =
+    AssetRegistryTrader<FixedRateAssetRegistryTrader<AssetFeePerSecond>, ToSpectreTreasury>;
+
 impl orml_asset_registry::Config for Runtime {
     type RuntimeEvent = RuntimeEvent;
 
-    type CustomMetadata = ();
+    type CustomMetadata = CustomMetadata;
 
     type AssetId = AssetId;
 
@@ -1123,11 +1238,42 @@ impl orml_asset_registry::Config for Runtime {
 impl pallet_spectre::Config for Runtime {
     type RuntimeEvent = RuntimeEvent;
     type NativeBalance = Balances;
-    type CapitalAllocator = ();
+    type CapitalAllocator = xcm_config::XcmCapitalAllocator;
     type TradeExecutionVerifier = TradeExecutionVerifyV1;
     type InvestorPoolOwnership = ConstU8<30>;
     type TraderPoolOwnership = ConstU8<60>;
     type WithdrawPeriod = WithdrawPeriod;
+    type PoolAdminOrigin = EnsureRoot<AccountId>;
+    type RelayerAdminOrigin = EnsureRoot<AccountId>;
+    type MaxRelayers = ConstU32<16>;
+    type AttestorAdminOrigin = EnsureRoot<AccountId>;
+    type TraderRegistrarOrigin = xcm_config::EnsureXcmTraderRegistrar;
+    type MaxRegistrarAttestors = ConstU32<4>;
+    type TraderAdminOrigin = EnsureRoot<AccountId>;
+    type AutoApproveTraders = ConstBool<false>;
+    type RegistryAdminOrigin = EnsureRoot<AccountId>;
+    type TradeDecoder = xcm_config::HydraDxOmnipoolTrades;
+    type MaxRiskViolations = ConstU8<3>;
+    type RiskViolationSlash = RiskViolationSlash;
+    type MaxReportAge = ConstU32<DAYS>;
+    type MaxDrawdownChecks = ConstU32<50>;
+    type BalanceQueryInterval = ConstU32<{ 6 * HOURS }>;
+    type MaxBalanceQueryChecks = ConstU32<50>;
+    type TreasuryAccount = SpectreTreasuryAccount;
+    type InsuranceFeeCut = InsuranceFeeCut;
+    type InsuranceProfitCut = InsuranceProfitCut;
+    type InsuranceClaimOrigin = EnsureRoot<AccountId>;
+    type EpochLength = ConstU32<{ 7 * DAYS }>;
+    type MaxEpochSteps = ConstU32<50>;
+    type MaxRedemptionRequests = ConstU32<256>;
+    type DepositFilter = ();
+    type MaxInvestorPools = ConstU32<4>;
+    type PoolShares = SpectrePoolShares;
+    type RemoteSwapExecutor = xcm_config::HydraDxOmnipoolSwaps;
+    type RemoteBalanceQuerier = xcm_config::XcmBalanceQueries;
+    type XcmResponseOrigin = pallet_xcm::EnsureResponse<Everything>;
+    type RemoteSwapTimeout = xcm_config::RemoteSwapTimeout;
+    type WeightInfo = ();
 }
 
 // TODO
@@ -1740,6 +1886,32 @@ impl_runtime_apis! {
             TransactionPayment::length_to_fee(length)
         }
     }
+
+    impl pallet_spectre_runtime_api::SpectreApi<Block, AccountId, AssetId, Balance> for Runtime {
+        fn insurance_fund_balance(asset_id: AssetId) -> Balance {
+            Spectre::insurance_fund_balance(asset_id)
+        }
+
+        fn insurance_coverage_ratio(asset_id: AssetId) -> Permill {
+            Spectre::insurance_coverage_ratio(asset_id)
+        }
+
+        fn claimable_profit(investor_id: AccountId, asset_id: AssetId) -> Balance {
+            Spectre::claimable_profit(&investor_id, asset_id)
+        }
+
+        fn claimable_performance_fees(trader_id: AccountId) -> Balance {
+            Spectre::claimable_performance_fees(&trader_id)
+        }
+
+        fn redemption_queue(asset_id: AssetId) -> Vec<(u32, AccountId, Balance)> {
+            Spectre::redemption_queue(asset_id)
+        }
+
+        fn investor_positions(investor_id: AccountId) -> Vec<(AssetId, Balance)> {
+            Spectre::investor_positions(&investor_id)
+        }
+    }
 }
 
 struct CheckInherents;
@@ -15,8 +15,14 @@
 // along with Tanssi.  If not, see <http://www.gnu.org/licenses/>.
 
 use {
-    crate::xcm_config::XcmConfig,
-    frame_support::parameter_types,
+    crate::{
+        asset_info,
+        xcm_config::{xc20_address, AssetId, XcmConfig},
+        AccountId, Balance, Runtime,
+    },
+    frame_support::{parameter_types, storage::with_storage_layer, traits::Get},
+    orml_traits::MultiCurrency,
+    pallet_evm::{AddressMapping, GasWeightMapping},
     pallet_evm_precompile_balances_erc20::{Erc20BalancesPrecompile, Erc20Metadata},
     pallet_evm_precompile_batch::BatchPrecompile,
     pallet_evm_precompile_call_permit::CallPermitPrecompile,
@@ -24,12 +30,22 @@ use {
     pallet_evm_precompile_sha3fips::Sha3FIPS256,
     pallet_evm_precompile_simple::{ECRecover, ECRecoverPublicKey, Identity, Ripemd160, Sha256},
     pallet_evm_precompile_xcm_utils::{AllExceptXcmExecute, XcmUtilsPrecompile},
-    pallet_evm_precompileset_assets_erc20::Erc20AssetsPrecompileSet,
-    precompile_utils::precompile_set::{
-        AcceptDelegateCall, AddressU64, CallableByContract, CallableByPrecompile, PrecompileAt,
-        PrecompileSetBuilder, PrecompileSetStartingWith, PrecompilesInRangeInclusive,
-        SubcallWithMaxNesting,
+    pallet_evm_precompileset_assets_erc20::AccountIdAssetIdConversion,
+    pallet_spectre::{util::InvestorCapitalPool, CapitalPool, Erc20Approvals},
+    precompile_utils::{
+        precompile_set::{
+            AcceptDelegateCall, AddressU64, CallableByContract, CallableByPrecompile, PrecompileAt,
+            PrecompileSetBuilder, PrecompileSetStartingWith, PrecompilesInRangeInclusive,
+            SubcallWithMaxNesting,
+        },
+        prelude::*,
+    },
+    sp_core::{MaxEncodedLen, H160, U256},
+    sp_runtime::{
+        traits::{SaturatedConversion, StaticLookup},
+        Rounding,
     },
+    sp_std::vec::Vec,
 };
 
 /// ERC20 metadata for the native token.
@@ -59,13 +75,680 @@ impl Erc20Metadata for NativeErc20Metadata {
 }
 
 /// The asset precompile address prefix. Addresses that match against this prefix will be routed
-/// to Erc20AssetsPrecompileSet being marked as foreign
+/// to OrmlErc20AssetsPrecompileSet being marked as foreign
 pub const FOREIGN_ASSET_PRECOMPILE_ADDRESS_PREFIX: &[u8] = &[255u8; 18];
 
 parameter_types! {
     pub ForeignAssetPrefix: &'static [u8] = FOREIGN_ASSET_PRECOMPILE_ADDRESS_PREFIX;
 }
 
+/// Solidity selector of the Transfer log, which is the Keccak of the Log signature.
+pub const SELECTOR_LOG_TRANSFER: [u8; 32] = keccak256!("Transfer(address,address,uint256)");
+
+/// Solidity selector of the Approval log, which is the Keccak of the Log signature.
+pub const SELECTOR_LOG_APPROVAL: [u8; 32] = keccak256!("Approval(address,address,uint256)");
+
+/// ERC-20 interface of the `orml_tokens` assets, named XC20s. The asset id is taken from the address
+/// through `AccountIdAssetIdConversion`, name, symbol and decimals come from `orml_asset_registry`
+pub struct OrmlErc20AssetsPrecompileSet;
+
+#[precompile_utils::precompile]
+#[precompile::precompile_set]
+impl OrmlErc20AssetsPrecompileSet {
+    /// PrecompileSet discriminant. Maps the address to a registered asset
+    #[precompile::discriminant]
+    fn discriminant(address: H160, gas: u64) -> DiscriminantResult<AssetId> {
+        let extra_cost = RuntimeHelper::<Runtime>::db_read_gas_cost();
+        if gas < extra_cost {
+            return DiscriminantResult::OutOfGas;
+        }
+
+        let account = <Runtime as pallet_evm::Config>::AddressMapping::into_account_id(address);
+        match Runtime::account_to_asset_id(account) {
+            Some((_, asset_id)) if asset_info(asset_id).is_some() => {
+                DiscriminantResult::Some(asset_id, extra_cost)
+            }
+            _ => DiscriminantResult::None(extra_cost),
+        }
+    }
+
+    #[precompile::public("totalSupply()")]
+    #[precompile::view]
+    fn total_supply(asset_id: AssetId, handle: &mut impl PrecompileHandle) -> EvmResult<U256> {
+        // Storage item: TotalIssuance:
+        // Twox64(8) + AssetId(4) + Balance(16)
+        handle.record_db_read::<Runtime>(28)?;
+        Ok(
+            <orml_tokens::Pallet<Runtime> as MultiCurrency<AccountId>>::total_issuance(asset_id)
+                .into(),
+        )
+    }
+
+    #[precompile::public("balanceOf(address)")]
+    #[precompile::view]
+    fn balance_of(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        who: Address,
+    ) -> EvmResult<U256> {
+        // Storage item: Accounts:
+        // Blake2_128(16) + AccountId(20) + Twox64(8) + AssetId(4) + AccountData(3 * Balance(16))
+        handle.record_db_read::<Runtime>(96)?;
+        let who = <Runtime as pallet_evm::Config>::AddressMapping::into_account_id(who.into());
+        Ok(
+            <orml_tokens::Pallet<Runtime> as MultiCurrency<AccountId>>::free_balance(
+                asset_id, &who,
+            )
+            .into(),
+        )
+    }
+
+    #[precompile::public("allowance(address,address)")]
+    #[precompile::view]
+    fn allowance(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        owner: Address,
+        spender: Address,
+    ) -> EvmResult<U256> {
+        // Storage item: Erc20Approvals:
+        // Twox64(8) + AssetId(4) + 2 * (Blake2_128(16) + AccountId(20)) + Balance(16)
+        handle.record_db_read::<Runtime>(100)?;
+        let owner = <Runtime as pallet_evm::Config>::AddressMapping::into_account_id(owner.into());
+        let spender =
+            <Runtime as pallet_evm::Config>::AddressMapping::into_account_id(spender.into());
+        Ok(Erc20Approvals::<Runtime>::get((asset_id, owner, spender)).into())
+    }
+
+    #[precompile::public("approve(address,uint256)")]
+    fn approve(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        spender: Address,
+        value: U256,
+    ) -> EvmResult<bool> {
+        handle.record_cost(RuntimeHelper::<Runtime>::db_write_gas_cost())?;
+        handle.record_log_costs_manual(3, 32)?;
+
+        let caller = handle.context().caller;
+        let spender: H160 = spender.into();
+        // Saturate the allowance, `type(uint256).max` is a common "infinite" approval
+        let amount = Self::u256_to_amount(value).unwrap_or(Balance::MAX);
+        {
+            let owner = <Runtime as pallet_evm::Config>::AddressMapping::into_account_id(caller);
+            let spender = <Runtime as pallet_evm::Config>::AddressMapping::into_account_id(spender);
+            Erc20Approvals::<Runtime>::insert((asset_id, owner, spender), amount);
+        }
+
+        log3(
+            handle.context().address,
+            SELECTOR_LOG_APPROVAL,
+            caller,
+            spender,
+            solidity::encode_event_data(value),
+        )
+        .record(handle)?;
+        Ok(true)
+    }
+
+    #[precompile::public("transfer(address,uint256)")]
+    fn transfer(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        to: Address,
+        value: U256,
+    ) -> EvmResult<bool> {
+        let caller = handle.context().caller;
+        Self::transfer_asset(asset_id, handle, caller, to.into(), value)?;
+        Ok(true)
+    }
+
+    #[precompile::public("transferFrom(address,address,uint256)")]
+    fn transfer_from(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        from: Address,
+        to: Address,
+        value: U256,
+    ) -> EvmResult<bool> {
+        let caller = handle.context().caller;
+        let from: H160 = from.into();
+        if caller != from {
+            // Storage item: Erc20Approvals, read and written
+            handle.record_db_read::<Runtime>(100)?;
+            handle.record_cost(RuntimeHelper::<Runtime>::db_write_gas_cost())?;
+            let amount = Self::u256_to_amount(value).in_field("value")?;
+            let owner = <Runtime as pallet_evm::Config>::AddressMapping::into_account_id(from);
+            let spender = <Runtime as pallet_evm::Config>::AddressMapping::into_account_id(caller);
+            Erc20Approvals::<Runtime>::try_mutate((asset_id, owner, spender), |allowance| {
+                *allowance = allowance
+                    .checked_sub(amount)
+                    .ok_or(revert("Trying to spend more than allowed"))?;
+                EvmResult::Ok(())
+            })?;
+        }
+        Self::transfer_asset(asset_id, handle, from, to.into(), value)?;
+        Ok(true)
+    }
+
+    #[precompile::public("name()")]
+    #[precompile::view]
+    fn name(asset_id: AssetId, handle: &mut impl PrecompileHandle) -> EvmResult<UnboundedBytes> {
+        let (name, _, _, _) = Self::asset_info(handle, asset_id)?;
+        Ok(name.as_slice().into())
+    }
+
+    #[precompile::public("symbol()")]
+    #[precompile::view]
+    fn symbol(asset_id: AssetId, handle: &mut impl PrecompileHandle) -> EvmResult<UnboundedBytes> {
+        let (_, symbol, _, _) = Self::asset_info(handle, asset_id)?;
+        Ok(symbol.as_slice().into())
+    }
+
+    #[precompile::public("decimals()")]
+    #[precompile::view]
+    fn decimals(asset_id: AssetId, handle: &mut impl PrecompileHandle) -> EvmResult<u8> {
+        let (_, _, decimals, _) = Self::asset_info(handle, asset_id)?;
+        Ok(decimals.saturated_into())
+    }
+
+    fn asset_info(
+        handle: &mut impl PrecompileHandle,
+        asset_id: AssetId,
+    ) -> EvmResult<(Vec<u8>, Vec<u8>, u32, Balance)> {
+        // Storage item: Metadata:
+        // Twox64(8) + AssetId(4) + AssetMetadata
+        handle.record_db_read::<Runtime>(
+            12 + pallet_spectre::util::AssetMetadataOf::<Runtime>::max_encoded_len(),
+        )?;
+        asset_info(asset_id).ok_or(revert("Asset not registered"))
+    }
+
+    fn transfer_asset(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        from: H160,
+        to: H160,
+        value: U256,
+    ) -> EvmResult {
+        handle.record_log_costs_manual(3, 32)?;
+        let amount = Self::u256_to_amount(value).in_field("value")?;
+        {
+            let origin = <Runtime as pallet_evm::Config>::AddressMapping::into_account_id(from);
+            let dest = <Runtime as pallet_evm::Config>::AddressMapping::into_account_id(to);
+            RuntimeHelper::<Runtime>::try_dispatch(
+                handle,
+                Some(origin).into(),
+                orml_tokens::Call::<Runtime>::transfer {
+                    dest: <Runtime as frame_system::Config>::Lookup::unlookup(dest),
+                    currency_id: asset_id,
+                    amount,
+                },
+                0,
+            )?;
+        }
+
+        log3(
+            handle.context().address,
+            SELECTOR_LOG_TRANSFER,
+            from,
+            to,
+            solidity::encode_event_data(value),
+        )
+        .record(handle)?;
+        Ok(())
+    }
+
+    fn u256_to_amount(value: U256) -> MayRevert<Balance> {
+        value
+            .try_into()
+            .map_err(|_| RevertReason::value_is_too_large("balance type").into())
+    }
+}
+
+/// The spectre vault precompile address prefix. Addresses made of this prefix and the pool asset id
+/// in the last 4 bytes are routed to the ERC-4626 vault of the pool
+pub const SPECTRE_VAULT_PRECOMPILE_ADDRESS_PREFIX: &[u8] = &[255u8, 255, 255, 254];
+
+parameter_types! {
+    pub SpectreVaultPrefix: &'static [u8] = SPECTRE_VAULT_PRECOMPILE_ADDRESS_PREFIX;
+}
+
+/// Solidity selector of the ERC-4626 Deposit log, which is the Keccak of the Log signature.
+pub const SELECTOR_LOG_DEPOSIT: [u8; 32] = keccak256!("Deposit(address,address,uint256,uint256)");
+
+/// Solidity selector of the ERC-4626 Withdraw log, which is the Keccak of the Log signature.
+pub const SELECTOR_LOG_WITHDRAW: [u8; 32] =
+    keccak256!("Withdraw(address,address,address,uint256,uint256)");
+
+/// Address of the ERC-4626 vault of the spectre pool of `asset_id`
+pub fn spectre_vault_address(asset_id: AssetId) -> H160 {
+    let mut data = [0u8; 20];
+    data[0..4].copy_from_slice(SPECTRE_VAULT_PRECOMPILE_ADDRESS_PREFIX);
+    data[16..20].copy_from_slice(&asset_id.to_be_bytes());
+    H160::from(data)
+}
+
+/// Exposes every spectre capital pool as an ERC-4626 vault of the pool asset.
+/// Vault shares are the pool share tokens, minted at the pool value like `register_investor` does and
+/// redeemed for their share of the pool net asset value
+pub struct SpectreVaultPrecompileSet;
+
+#[precompile_utils::precompile]
+#[precompile::precompile_set]
+impl SpectreVaultPrecompileSet {
+    /// PrecompileSet discriminant. Maps the address to the asset of an existing pool
+    #[precompile::discriminant]
+    fn discriminant(address: H160, gas: u64) -> DiscriminantResult<AssetId> {
+        let extra_cost = RuntimeHelper::<Runtime>::db_read_gas_cost();
+        if gas < extra_cost {
+            return DiscriminantResult::OutOfGas;
+        }
+
+        let mut data = [0u8; 4];
+        data.copy_from_slice(&address.as_bytes()[16..20]);
+        let asset_id = AssetId::from_be_bytes(data);
+        if address != spectre_vault_address(asset_id)
+            || !CapitalPool::<Runtime>::contains_key(asset_id)
+        {
+            return DiscriminantResult::None(extra_cost);
+        }
+        DiscriminantResult::Some(asset_id, extra_cost)
+    }
+
+    #[precompile::public("asset()")]
+    #[precompile::view]
+    fn asset(asset_id: AssetId, _handle: &mut impl PrecompileHandle) -> EvmResult<Address> {
+        let account = xc20_address(FOREIGN_ASSET_PRECOMPILE_ADDRESS_PREFIX, asset_id)
+            .ok_or(revert("Asset has no XC20 address"))?;
+        Ok(Address(account.into()))
+    }
+
+    #[precompile::public("totalAssets()")]
+    #[precompile::view]
+    fn total_assets(asset_id: AssetId, handle: &mut impl PrecompileHandle) -> EvmResult<U256> {
+        let pool = Self::pool(handle, asset_id)?;
+        Ok(pool.unrealized_balance.into())
+    }
+
+    #[precompile::public("totalSupply()")]
+    #[precompile::view]
+    fn total_supply(asset_id: AssetId, handle: &mut impl PrecompileHandle) -> EvmResult<U256> {
+        let pool = Self::pool(handle, asset_id)?;
+        Ok(pool.total_capital.into())
+    }
+
+    #[precompile::public("balanceOf(address)")]
+    #[precompile::view]
+    fn balance_of(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        owner: Address,
+    ) -> EvmResult<U256> {
+        let pool = Self::pool(handle, asset_id)?;
+        let Some(share_asset_id) = pool.share_asset_id else {
+            return Ok(U256::zero());
+        };
+        // Storage item: Accounts:
+        // Blake2_128(16) + AccountId(20) + Twox64(8) + AssetId(4) + AccountData(3 * Balance(16))
+        handle.record_db_read::<Runtime>(96)?;
+        let owner = <Runtime as pallet_evm::Config>::AddressMapping::into_account_id(owner.into());
+        Ok(
+            <orml_tokens::Pallet<Runtime> as MultiCurrency<AccountId>>::total_balance(
+                share_asset_id,
+                &owner,
+            )
+            .into(),
+        )
+    }
+
+    #[precompile::public("allowance(address,address)")]
+    #[precompile::view]
+    fn allowance(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        owner: Address,
+        spender: Address,
+    ) -> EvmResult<U256> {
+        let share_asset_id = Self::share_asset(handle, asset_id)?;
+        OrmlErc20AssetsPrecompileSet::allowance(share_asset_id, handle, owner, spender)
+    }
+
+    #[precompile::public("approve(address,uint256)")]
+    fn approve(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        spender: Address,
+        value: U256,
+    ) -> EvmResult<bool> {
+        let share_asset_id = Self::share_asset(handle, asset_id)?;
+        OrmlErc20AssetsPrecompileSet::approve(share_asset_id, handle, spender, value)
+    }
+
+    #[precompile::public("transfer(address,uint256)")]
+    fn transfer(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        to: Address,
+        value: U256,
+    ) -> EvmResult<bool> {
+        let share_asset_id = Self::share_asset(handle, asset_id)?;
+        OrmlErc20AssetsPrecompileSet::transfer(share_asset_id, handle, to, value)
+    }
+
+    #[precompile::public("transferFrom(address,address,uint256)")]
+    fn transfer_from(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        from: Address,
+        to: Address,
+        value: U256,
+    ) -> EvmResult<bool> {
+        let share_asset_id = Self::share_asset(handle, asset_id)?;
+        OrmlErc20AssetsPrecompileSet::transfer_from(share_asset_id, handle, from, to, value)
+    }
+
+    #[precompile::public("name()")]
+    #[precompile::view]
+    fn name(asset_id: AssetId, handle: &mut impl PrecompileHandle) -> EvmResult<UnboundedBytes> {
+        let (name, _, _, _) = OrmlErc20AssetsPrecompileSet::asset_info(handle, asset_id)?;
+        Ok([
+            b"Spectre ".as_slice(),
+            name.as_slice(),
+            b" Vault".as_slice(),
+        ]
+        .concat()
+        .as_slice()
+        .into())
+    }
+
+    #[precompile::public("symbol()")]
+    #[precompile::view]
+    fn symbol(asset_id: AssetId, handle: &mut impl PrecompileHandle) -> EvmResult<UnboundedBytes> {
+        let (_, symbol, _, _) = OrmlErc20AssetsPrecompileSet::asset_info(handle, asset_id)?;
+        Ok([b"sp".as_slice(), symbol.as_slice()]
+            .concat()
+            .as_slice()
+            .into())
+    }
+
+    /// Shares are minted one per unit of pool asset in a new pool, with the decimals of the pool asset
+    #[precompile::public("decimals()")]
+    #[precompile::view]
+    fn decimals(asset_id: AssetId, handle: &mut impl PrecompileHandle) -> EvmResult<u8> {
+        OrmlErc20AssetsPrecompileSet::decimals(asset_id, handle)
+    }
+
+    #[precompile::public("convertToShares(uint256)")]
+    #[precompile::view]
+    fn convert_to_shares(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        assets: U256,
+    ) -> EvmResult<U256> {
+        let assets = Self::u256_to_amount(assets).in_field("assets")?;
+        let pool = Self::pool(handle, asset_id)?;
+        Ok(pool.capital_for_value(assets, Rounding::Down).into())
+    }
+
+    #[precompile::public("convertToAssets(uint256)")]
+    #[precompile::view]
+    fn convert_to_assets(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        shares: U256,
+    ) -> EvmResult<U256> {
+        let shares = Self::u256_to_amount(shares).in_field("shares")?;
+        let pool = Self::pool(handle, asset_id)?;
+        Ok(pool.redemption_value(shares).into())
+    }
+
+    #[precompile::public("previewDeposit(uint256)")]
+    #[precompile::view]
+    fn preview_deposit(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        assets: U256,
+    ) -> EvmResult<U256> {
+        Self::convert_to_shares(asset_id, handle, assets)
+    }
+
+    #[precompile::public("previewMint(uint256)")]
+    #[precompile::view]
+    fn preview_mint(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        shares: U256,
+    ) -> EvmResult<U256> {
+        let shares = Self::u256_to_amount(shares).in_field("shares")?;
+        let pool = Self::pool(handle, asset_id)?;
+        Ok(pool.value_for_capital(shares, Rounding::Up).into())
+    }
+
+    #[precompile::public("previewWithdraw(uint256)")]
+    #[precompile::view]
+    fn preview_withdraw(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        assets: U256,
+    ) -> EvmResult<U256> {
+        let assets = Self::u256_to_amount(assets).in_field("assets")?;
+        let pool = Self::pool(handle, asset_id)?;
+        Ok(pool.capital_for_value(assets, Rounding::Up).into())
+    }
+
+    #[precompile::public("previewRedeem(uint256)")]
+    #[precompile::view]
+    fn preview_redeem(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        shares: U256,
+    ) -> EvmResult<U256> {
+        Self::convert_to_assets(asset_id, handle, shares)
+    }
+
+    #[precompile::public("deposit(uint256,address)")]
+    fn deposit(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        assets: U256,
+        receiver: Address,
+    ) -> EvmResult<U256> {
+        let assets = Self::u256_to_amount(assets).in_field("assets")?;
+        let shares = Self::deposit_assets(asset_id, handle, assets, receiver.into())?;
+        Ok(shares.into())
+    }
+
+    #[precompile::public("mint(uint256,address)")]
+    fn mint(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        shares: U256,
+        receiver: Address,
+    ) -> EvmResult<U256> {
+        let shares = Self::u256_to_amount(shares).in_field("shares")?;
+        let pool = Self::pool(handle, asset_id)?;
+        // rounded up, the deposit mints at least `shares`
+        let assets = pool.value_for_capital(shares, Rounding::Up);
+        let minted = Self::deposit_assets(asset_id, handle, assets, receiver.into())?;
+        if minted < shares {
+            return Err(revert("Minted fewer shares than requested"));
+        }
+        Ok(assets.into())
+    }
+
+    #[precompile::public("withdraw(uint256,address,address)")]
+    fn withdraw(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        assets: U256,
+        receiver: Address,
+        owner: Address,
+    ) -> EvmResult<U256> {
+        let assets = Self::u256_to_amount(assets).in_field("assets")?;
+        let pool = Self::pool(handle, asset_id)?;
+        let shares = pool.capital_for_value(assets, Rounding::Up);
+        Self::redeem_shares(
+            asset_id,
+            handle,
+            &pool,
+            shares,
+            receiver.into(),
+            owner.into(),
+        )?;
+        Ok(shares.into())
+    }
+
+    #[precompile::public("redeem(uint256,address,address)")]
+    fn redeem(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        shares: U256,
+        receiver: Address,
+        owner: Address,
+    ) -> EvmResult<U256> {
+        let shares = Self::u256_to_amount(shares).in_field("shares")?;
+        let pool = Self::pool(handle, asset_id)?;
+        let assets = Self::redeem_shares(
+            asset_id,
+            handle,
+            &pool,
+            shares,
+            receiver.into(),
+            owner.into(),
+        )?;
+        Ok(assets.into())
+    }
+
+    fn pool(
+        handle: &mut impl PrecompileHandle,
+        asset_id: AssetId,
+    ) -> EvmResult<InvestorCapitalPool<Runtime>> {
+        // Storage item: CapitalPool:
+        // Twox64(8) + AssetId(4) + InvestorCapitalPool
+        handle.record_db_read::<Runtime>(12 + InvestorCapitalPool::<Runtime>::max_encoded_len())?;
+        Ok(CapitalPool::<Runtime>::get(asset_id))
+    }
+
+    // Pool share token, the vault ERC-20. Share ids are outside the XC20 range, the vault is their only ERC-20 interface
+    fn share_asset(handle: &mut impl PrecompileHandle, asset_id: AssetId) -> EvmResult<AssetId> {
+        Self::pool(handle, asset_id)?
+            .share_asset_id
+            .ok_or(revert("Pool has no share token"))
+    }
+
+    // Deposit `assets` of the caller in the pool like `register_investor` and send the minted shares to `receiver`.
+    // Returns the shares minted by the pool, which processes the pending redemptions after the deposit
+    fn deposit_assets(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        assets: Balance,
+        receiver: H160,
+    ) -> EvmResult<Balance> {
+        handle.record_log_costs_manual(3, 64)?;
+        let pool = Self::pool(handle, asset_id)?;
+        let weight = <<Runtime as pallet_spectre::Config>::WeightInfo as pallet_spectre::WeightInfo>::register_investor(
+            <Runtime as pallet_spectre::Config>::MaxRedemptionRequests::get(),
+        );
+        handle.record_cost(
+            <Runtime as pallet_evm::Config>::GasWeightMapping::weight_to_gas(weight),
+        )?;
+        let caller = handle.context().caller;
+        let origin = <Runtime as pallet_evm::Config>::AddressMapping::into_account_id(caller);
+        let shares = with_storage_layer(|| {
+            pallet_spectre::Pallet::<Runtime>::deposit_capital(origin, asset_id, assets)
+        })
+        .map_err(|error| revert(<&'static str>::from(error)))?;
+
+        if receiver != caller {
+            if let Some(share_asset_id) = pool.share_asset_id {
+                Self::transfer_tokens(handle, share_asset_id, caller, receiver, shares)?;
+            }
+        }
+
+        log3(
+            handle.context().address,
+            SELECTOR_LOG_DEPOSIT,
+            caller,
+            receiver,
+            solidity::encode_event_data((U256::from(assets), U256::from(shares))),
+        )
+        .record(handle)?;
+        Ok(shares)
+    }
+
+    // Redeem `shares` of the caller for their value in pool asset, sent to `receiver`.
+    // Shares can only be redeemed by their owner
+    fn redeem_shares(
+        asset_id: AssetId,
+        handle: &mut impl PrecompileHandle,
+        pool: &InvestorCapitalPool<Runtime>,
+        shares: Balance,
+        receiver: H160,
+        owner: H160,
+    ) -> EvmResult<Balance> {
+        handle.record_log_costs_manual(4, 64)?;
+        let caller = handle.context().caller;
+        if owner != caller {
+            return Err(revert("Shares can only be redeemed by their owner"));
+        }
+
+        let assets = pool.redemption_value(shares);
+        let origin = <Runtime as pallet_evm::Config>::AddressMapping::into_account_id(caller);
+        RuntimeHelper::<Runtime>::try_dispatch(
+            handle,
+            Some(origin).into(),
+            pallet_spectre::Call::<Runtime>::withdraw_capital {
+                asset_id,
+                amount: shares,
+            },
+            0,
+        )?;
+        if receiver != caller {
+            Self::transfer_tokens(handle, asset_id, caller, receiver, assets)?;
+        }
+
+        log4(
+            handle.context().address,
+            SELECTOR_LOG_WITHDRAW,
+            caller,
+            receiver,
+            owner,
+            solidity::encode_event_data((U256::from(assets), U256::from(shares))),
+        )
+        .record(handle)?;
+        Ok(assets)
+    }
+
+    fn transfer_tokens(
+        handle: &mut impl PrecompileHandle,
+        currency_id: AssetId,
+        from: H160,
+        to: H160,
+        amount: Balance,
+    ) -> EvmResult {
+        let origin = <Runtime as pallet_evm::Config>::AddressMapping::into_account_id(from);
+        let to = <Runtime as pallet_evm::Config>::AddressMapping::into_account_id(to);
+        RuntimeHelper::<Runtime>::try_dispatch(
+            handle,
+            Some(origin).into(),
+            orml_tokens::Call::<Runtime>::transfer {
+                dest: <Runtime as frame_system::Config>::Lookup::unlookup(to),
+                currency_id,
+                amount,
+            },
+            0,
+        )?;
+        Ok(())
+    }
+
+    fn u256_to_amount(value: U256) -> MayRevert<Balance> {
+        value
+            .try_into()
+            .map_err(|_| RevertReason::value_is_too_large("balance type").into())
+    }
+}
+
 type EthereumPrecompilesChecks = (AcceptDelegateCall, CallableByContract, CallableByPrecompile);
 
 #[precompile_utils::precompile_name_from_address]
@@ -106,7 +789,13 @@ pub type TemplatePrecompiles<R> = PrecompileSetBuilder<
         // Prefixed precompile sets (XC20)
         PrecompileSetStartingWith<
             ForeignAssetPrefix,
-            Erc20AssetsPrecompileSet<R>,
+            OrmlErc20AssetsPrecompileSet,
+            (CallableByContract, CallableByPrecompile),
+        >,
+        // Spectre pools as ERC-4626 vaults
+        PrecompileSetStartingWith<
+            SpectreVaultPrefix,
+            SpectreVaultPrecompileSet,
             (CallableByContract, CallableByPrecompile),
         >,
     ),
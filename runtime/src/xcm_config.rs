@@ -14,14 +14,12 @@
 // You should have received a copy of the GNU General Public License
 // along with Tanssi.  If not, see <http://www.gnu.org/licenses/>
 
-use frame_support::Identity;
-
 use {
     super::{
         precompiles::FOREIGN_ASSET_PRECOMPILE_ADDRESS_PREFIX, AccountId, AllPalletsWithSystem,
-        Balance, Balances, MaintenanceMode, MessageQueue, ParachainInfo, ParachainSystem,
-        PolkadotXcm, Runtime, RuntimeBlockWeights, RuntimeCall, RuntimeEvent, RuntimeOrigin,
-        WeightToFee, XcmpQueue,
+        AssetTrader, Balance, Balances, MaintenanceMode, MessageQueue, ParachainInfo,
+        ParachainSystem, PolkadotXcm, Runtime, RuntimeBlockWeights, RuntimeCall, RuntimeEvent,
+        RuntimeOrigin, WeightToFee, XcmpQueue,
     },
     ccp_xcm::SignedToAccountKey20,
     cumulus_primitives_core::{AggregateMessageOrigin, ParaId},
@@ -165,7 +163,11 @@ pub type XcmOriginToTransactDispatchOrigin = (
 );
 
 /// Means for transacting assets on this chain.
-pub type AssetTransactors = (CurrencyTransactor, ForeignFungiblesTransactor);
+pub type AssetTransactors = (
+    SpectrePoolDeposits,
+    CurrencyTransactor,
+    ForeignFungiblesTransactor,
+);
 pub type XcmWeigher = FixedWeightBounds<UnitWeightCost, RuntimeCall, MaxInstructions>;
 
 /// The means for routing XCM messages which are not for local execution into the right message
@@ -188,7 +190,7 @@ impl staging_xcm_executor::Config for XcmConfig {
     type UniversalLocation = UniversalLocation;
     type Barrier = XcmBarrier;
     type Weigher = XcmWeigher;
-    type Trader = ();
+    type Trader = AssetTrader;
     type ResponseHandler = PolkadotXcm;
     type AssetTrap = PolkadotXcm;
     type AssetClaims = PolkadotXcm;
@@ -326,7 +328,7 @@ impl AccountIdAssetIdConversion<AccountId, AssetId> for Runtime {
         let mut data = [0u8; 4];
         let (prefix_part, id_part) = h160_account.as_fixed_bytes().split_at(18);
         if prefix_part == FOREIGN_ASSET_PRECOMPILE_ADDRESS_PREFIX {
-            data.copy_from_slice(id_part);
+            data[2..4].copy_from_slice(id_part);
             let asset_id: AssetId = u32::from_be_bytes(data);
             Some((prefix_part.to_vec(), asset_id))
         } else {
@@ -334,15 +336,25 @@ impl AccountIdAssetIdConversion<AccountId, AssetId> for Runtime {
         }
     }
 
-    // The opposite conversion
+    // The opposite conversion, asset ids above `MAX_XC20_ASSET_ID` have no XC20 address and map to
+    // the zero address rather than to the address of the asset sharing their lowest 2 bytes
     fn asset_id_to_account(prefix: &[u8], asset_id: AssetId) -> AccountId {
-        let mut data = [0u8; 20];
-        data[0..18].copy_from_slice(prefix);
-        data[18..20].copy_from_slice(&asset_id.to_be_bytes());
-        AccountId::from(data)
+        xc20_address(prefix, asset_id).unwrap_or_else(|| AccountId::from([0u8; 20]))
     }
 }
 
+/// Largest asset id reachable through the XC20 precompiles, their addresses only hold 2 bytes of asset id
+pub const MAX_XC20_ASSET_ID: AssetId = u16::MAX as AssetId;
+
+/// XC20 address of an asset, `None` for the asset ids above `MAX_XC20_ASSET_ID`
+pub fn xc20_address(prefix: &[u8], asset_id: AssetId) -> Option<AccountId> {
+    let id = u16::try_from(asset_id).ok()?;
+    let mut data = [0u8; 20];
+    data[0..18].copy_from_slice(prefix.get(0..18)?);
+    data[18..20].copy_from_slice(&id.to_be_bytes());
+    Some(AccountId::from(data))
+}
+
 pub type AssetId = u32;
 
 parameter_types! {
@@ -360,9 +372,25 @@ impl pallet_xcm_executor_utils::Config for Runtime {
 }
 
 use {
-    crate::Assets,
+    crate::{Assets, CurrencyIdEquivalence, Spectre, DAYS},
+    frame_support::weights::constants::WEIGHT_REF_TIME_PER_SECOND,
+    pallet_spectre::{
+        util::{
+            CapitalAllocator, DecodedTrade, DexVenue, IsXcmTraderRegistrar, NetworkId, NetworkInfo,
+            ProofKind, RemoteAccount, RemoteBalanceQuerier, RemoteSwap, RemoteSwapExecutor,
+            SupportedDexs, TradeAction, TradeDecoder,
+        },
+        CapitalPool, DexVenues, TargetNetworks,
+    },
+    parity_scale_codec::{Compact, Decode, Encode},
+    sp_runtime::{
+        generic::Era, traits::MaybeEquivalence, AccountId32, BoundedVec, DispatchError,
+        DispatchResult, MultiAddress, MultiSignature,
+    },
+    sp_std::boxed::Box,
+    staging_xcm::{VersionedMultiAssets, VersionedMultiLocation},
     staging_xcm_builder::{FungiblesAdapter, NoChecking},
-    staging_xcm_executor::traits::JustTry,
+    staging_xcm_executor::traits::{ConvertLocation, JustTry, TransactAsset},
 };
 
 /// Means for transacting foreign assets from different global consensus.
@@ -370,8 +398,7 @@ pub type ForeignFungiblesTransactor = FungiblesAdapter<
     // Use this fungibles implementation:
     Assets,
     // Use this currency when it is a fungible asset matching the given location or name:
-    // (ConvertedConcreteId<AssetId, Balance, Identity, JustTry>,),
-    (),
+    ConvertedConcreteId<AssetId, Balance, CurrencyIdEquivalence, JustTry>,
     // Convert an XCM MultiLocation into a local account id:
     LocationToAccountId,
     // Our chain's account ID type (we can't get away without mentioning it explicitly):
@@ -381,3 +408,509 @@ pub type ForeignFungiblesTransactor = FungiblesAdapter<
     // The account to use for tracking teleports.
     CheckingAccount,
 >;
+
+parameter_types! {
+    // Assets deposited under this location go to the spectre pool of the asset, the rest of the
+    // location names the investor, e.g. (0, X3(PalletInstance(spectre), Parachain(1000), AccountId32))
+    pub SpectrePoolDepositLocation: MultiLocation = MultiLocation {
+        parents: 0,
+        interior: X1(PalletInstance(<Spectre as PalletInfoAccess>::index() as u8)),
+    };
+}
+
+/// Deposits the assets sent to `SpectrePoolDepositLocation` into the spectre pool of the asset.
+/// The position is recorded for the `LocationToAccountId` account of the investor, the account
+/// a `Transact` of the investor dispatches from. The capital stays with the investor when the pool refuses it.
+/// Any sender can deposit for any investor location, see `Spectre::deposit_xcm_capital`.
+pub struct SpectrePoolDeposits;
+
+impl SpectrePoolDeposits {
+    // Location of the investor named by a pool deposit location,
+    // sibling chain investors start with their `Parachain` junction
+    fn investor_location(who: &MultiLocation) -> Option<MultiLocation> {
+        let deposit_location = SpectrePoolDepositLocation::get();
+        if who.parents != deposit_location.parents
+            || who.interior.first() != deposit_location.interior.first()
+        {
+            return None;
+        }
+        let (investor, _) = who.interior.split_first();
+        match investor.first()? {
+            Parachain(_) => Some(MultiLocation::new(1, investor)),
+            _ => Some(MultiLocation::new(0, investor)),
+        }
+    }
+}
+
+impl TransactAsset for SpectrePoolDeposits {
+    fn deposit_asset(
+        what: &MultiAsset,
+        who: &MultiLocation,
+        context: Option<&XcmContext>,
+    ) -> XcmResult {
+        let investor_location = Self::investor_location(who).ok_or(XcmError::AssetNotFound)?;
+        let (asset_id, amount) = match what {
+            MultiAsset {
+                id: Concrete(location),
+                fun: Fungible(amount),
+            } => (
+                CurrencyIdEquivalence::convert(location).ok_or(XcmError::AssetNotFound)?,
+                *amount,
+            ),
+            _ => return Err(XcmError::AssetNotFound),
+        };
+        let investor = LocationToAccountId::convert_location(&investor_location).ok_or(
+            XcmError::FailedToTransactAsset("Investor location to account conversion failed"),
+        )?;
+
+        ForeignFungiblesTransactor::deposit_asset(what, &investor_location, context)?;
+        Spectre::deposit_xcm_capital(investor, asset_id, amount);
+        Ok(())
+    }
+}
+
+parameter_types! {
+    // DOT worth of the pool asset withdrawn from the trading account on HydraDX to pay for the execution,
+    // the surplus is refunded
+    pub const HydraDxSwapFee: Balance = 1_000_000_000;
+    pub HydraDxSwapWeight: Weight = Weight::from_parts(2_000_000_000, 200_000);
+    // Weight of the `remote_swap_executed` notification
+    pub RemoteSwapResponseWeight: Weight = Weight::from_parts(1_000_000_000, 64 * 1024);
+    pub const RemoteSwapTimeout: crate::BlockNumber = DAYS;
+    // DOT worth of the pool asset withdrawn from the trading account to pay for a balance query, the surplus is refunded
+    pub const BalanceQueryFee: Balance = 1_000_000_000;
+    // Weight of the `balance_query_response` notification
+    pub BalanceQueryResponseWeight: Weight = Weight::from_parts(1_000_000_000, 64 * 1024);
+}
+
+// Location of the pool asset `asset_id` seen from the remote network at `destination`
+fn remote_asset(
+    asset_id: &AssetId,
+    destination: &MultiLocation,
+) -> Result<MultiLocation, DispatchError> {
+    CurrencyIdEquivalence::convert_back(asset_id)
+        .and_then(|location| {
+            location
+                .reanchored(destination, UniversalLocation::get())
+                .ok()
+        })
+        .ok_or(pallet_spectre::Error::<Runtime>::AssetPoolNotSupported.into())
+}
+
+// `dot_fee` converted to the pool asset `asset_id` at their registered fee rates. The trading accounts the pools
+// keep on remote networks are funded with the pool asset only, their executions are paid in it
+fn remote_fee_in(asset_id: AssetId, dot_fee: Balance) -> Result<Balance, DispatchError> {
+    let fee_per_second = crate::fee_per_second(asset_id)
+        .ok_or(pallet_spectre::Error::<Runtime>::AssetPoolNotSupported)?;
+    Ok(dot_fee.saturating_mul(fee_per_second) / crate::DotFeePerSecond::get())
+}
+
+// Fee charged here in `asset_id` for the execution of `instructions` XCM instructions, twice the weighed fee
+// so the execution is never short, the surplus is deposited with the assets
+fn local_execution_fee(asset_id: AssetId, instructions: u64) -> Result<Balance, DispatchError> {
+    let fee_per_second = crate::fee_per_second(asset_id)
+        .ok_or(pallet_spectre::Error::<Runtime>::AssetPoolNotSupported)?;
+    let ref_time = UnitWeightCost::get()
+        .ref_time()
+        .saturating_mul(instructions.saturating_mul(2));
+    Ok(fee_per_second.saturating_mul(ref_time.into()) / u128::from(WEIGHT_REF_TIME_PER_SECOND))
+}
+
+/// Origin the pool account descends for the remote trading of a trader, the account it converts to
+/// on the remote chain is the account the pool controls for the trader
+pub fn pool_trading_origin(pool_account: &AccountId, trader_id: &AccountId) -> Junctions {
+    X2(
+        AccountKey20 {
+            network: None,
+            key: (*pool_account).into(),
+        },
+        AccountKey20 {
+            network: None,
+            key: (*trader_id).into(),
+        },
+    )
+}
+
+// Location of a parachain target network
+fn network_location(network: &NetworkInfo) -> Result<MultiLocation, DispatchError> {
+    let para_id = u32::try_from(network.chain_id)
+        .map_err(|_| pallet_spectre::Error::<Runtime>::NetworkNotSupported)?;
+    Ok(MultiLocation::new(1, X1(Parachain(para_id))))
+}
+
+// Location of this chain seen from its sibling parachains
+fn sibling_location() -> MultiLocation {
+    MultiLocation::new(1, X1(Parachain(ParachainInfo::parachain_id().into())))
+}
+
+// Location of the account the pool keeps for the trader, seen from the target network
+fn pool_trading_account(
+    pool_account: &AccountId,
+    trader_id: &AccountId,
+) -> Result<MultiLocation, DispatchError> {
+    sibling_location()
+        .appended_with(pool_trading_origin(pool_account, trader_id))
+        .map_err(|_| pallet_spectre::Error::<Runtime>::NetworkNotSupported.into())
+}
+
+/// Allocates the capital of the `XcmQuery` networks to the account the pool keeps for the trader there, the
+/// account the remote swaps and the balance queries run from, with a `pallet_xcm` reserve transfer from the
+/// pool account. A recall withdraws the last verified balance from that account and reserve transfers it back
+/// to the pool account, the capital pays the execution on both chains. On the other networks the trader trades
+/// from its own trading account, funded off chain and returned by the relayers
+pub struct XcmCapitalAllocator;
+
+impl CapitalAllocator<Runtime> for XcmCapitalAllocator {
+    fn allocate_capital(
+        network: NetworkId,
+        asset_id: AssetId,
+        amount: Balance,
+        trader_id: AccountId,
+        _onchain_trading_account: RemoteAccount,
+    ) -> DispatchResult {
+        let network = TargetNetworks::<Runtime>::get(network)
+            .ok_or(pallet_spectre::Error::<Runtime>::NetworkNotSupported)?;
+        if network.proof_kind != ProofKind::XcmQuery {
+            return Ok(());
+        }
+        let destination = network_location(&network)?;
+        let asset = CurrencyIdEquivalence::convert_back(&asset_id)
+            .ok_or(pallet_spectre::Error::<Runtime>::AssetPoolNotSupported)?;
+        let pool_account = CapitalPool::<Runtime>::get(asset_id).account_id;
+        let beneficiary = pool_trading_account(&pool_account, &trader_id)?;
+
+        PolkadotXcm::limited_reserve_transfer_assets(
+            RuntimeOrigin::signed(pool_account),
+            Box::new(VersionedMultiLocation::V3(destination)),
+            Box::new(VersionedMultiLocation::V3(beneficiary)),
+            Box::new(VersionedMultiAssets::V3((asset, amount).into())),
+            0,
+            Unlimited,
+        )
+        .map_err(|_| pallet_spectre::Error::<Runtime>::FailedToAllocateFunds.into())
+    }
+
+    fn recall_capital(
+        network: NetworkId,
+        asset_id: AssetId,
+        amount: Balance,
+        trader_id: AccountId,
+        _onchain_trading_account: RemoteAccount,
+    ) -> DispatchResult {
+        let network = TargetNetworks::<Runtime>::get(network)
+            .ok_or(pallet_spectre::Error::<Runtime>::NetworkNotSupported)?;
+        // the capital is settled with `settle_allocation` once it is back in the pool account
+        if network.proof_kind != ProofKind::XcmQuery || amount == 0 {
+            return Ok(());
+        }
+        let destination = network_location(&network)?;
+        let local_asset = CurrencyIdEquivalence::convert_back(&asset_id)
+            .ok_or(pallet_spectre::Error::<Runtime>::AssetPoolNotSupported)?;
+        let asset = remote_asset(&asset_id, &destination)?;
+        let pool_account = CapitalPool::<Runtime>::get(asset_id).account_id;
+        let trading_origin = pool_trading_origin(&pool_account, &trader_id);
+        let trading_account = pool_trading_account(&pool_account, &trader_id)?;
+
+        let capital: MultiAsset = (asset, amount).into();
+        // withdraw, clear origin, buy execution and deposit here
+        let deposit_fee: MultiAsset = (local_asset, local_execution_fee(asset_id, 4)?).into();
+        let message = Xcm(vec![
+            DescendOrigin(trading_origin),
+            WithdrawAsset(capital.clone().into()),
+            BuyExecution {
+                fees: capital,
+                weight_limit: Unlimited,
+            },
+            // the capital stays in the trading account when it cannot be sent back
+            SetAppendix(Xcm(vec![
+                RefundSurplus,
+                DepositAsset {
+                    assets: Wild(AllCounted(1)),
+                    beneficiary: trading_account,
+                },
+            ])),
+            InitiateReserveWithdraw {
+                assets: Wild(AllCounted(1)),
+                reserve: sibling_location(),
+                xcm: Xcm(vec![
+                    BuyExecution {
+                        fees: deposit_fee,
+                        weight_limit: Unlimited,
+                    },
+                    DepositAsset {
+                        assets: Wild(AllCounted(1)),
+                        beneficiary: MultiLocation::new(
+                            0,
+                            X1(AccountKey20 {
+                                network: None,
+                                key: pool_account.into(),
+                            }),
+                        ),
+                    },
+                ]),
+            },
+        ]);
+        send_xcm::<XcmRouter>(destination, message)
+            .map_err(|_| pallet_spectre::Error::<Runtime>::FailedToRecallFunds)?;
+        Ok(())
+    }
+}
+
+/// Executes the spectre remote swaps on the HydraDX Omnipool. The pool account and the trader descend the origin
+/// of an XCM `Transact` of the Omnipool sell or buy call, so the swap runs on the HydraDX account the pool keeps for
+/// the trader, and `ReportTransactStatus` answers the `pallet_xcm` query notifying `remote_swap_executed`.
+/// The execution is paid in the pool asset the account is funded with, the result is only accepted from HydraDX.
+/// The Omnipool pallet and call indices come from the dex venue registry
+pub struct HydraDxOmnipoolSwaps;
+
+impl RemoteSwapExecutor<Runtime> for HydraDxOmnipoolSwaps {
+    fn execute_swap(
+        pool_account: &AccountId,
+        trader_id: &AccountId,
+        asset_id: &AssetId,
+        network: &NetworkInfo,
+        venue: &DexVenue,
+        swap: &RemoteSwap,
+    ) -> Result<(QueryId, MultiLocation), DispatchError> {
+        let (pallet_index, calls) = match (&venue.dex, venue.pallet_index, &venue.swap_calls) {
+            (SupportedDexs::HydraDx, Some(pallet_index), Some(calls)) => (pallet_index, calls),
+            _ => return Err(pallet_spectre::Error::<Runtime>::RemoteSwapsNotSupported.into()),
+        };
+        let hydradx = network_location(network)?;
+        let fee_asset = remote_asset(asset_id, &hydradx)?;
+        let fee_amount = remote_fee_in(*asset_id, HydraDxSwapFee::get())?;
+        let call = match swap.action {
+            TradeAction::Sell => (
+                pallet_index,
+                calls.sell,
+                swap.asset_in,
+                swap.asset_out,
+                swap.amount,
+                swap.limit,
+            )
+                .encode(),
+            TradeAction::Buy => (
+                pallet_index,
+                calls.buy,
+                swap.asset_out,
+                swap.asset_in,
+                swap.amount,
+                swap.limit,
+            )
+                .encode(),
+        };
+
+        let trading_origin = pool_trading_origin(pool_account, trader_id);
+        let here = sibling_location();
+        let trading_account = pool_trading_account(pool_account, trader_id)?;
+        // the status is reported by the trading origin, seen from here under this chain
+        let query_id = PolkadotXcm::new_notify_query(
+            hydradx,
+            pallet_spectre::Call::<Runtime>::remote_swap_executed {
+                query_id: 0,
+                response: Response::Null,
+            },
+            frame_system::Pallet::<Runtime>::block_number() + RemoteSwapTimeout::get(),
+            MultiLocation::new(0, trading_origin),
+        );
+
+        let fee: MultiAsset = (fee_asset, fee_amount).into();
+        let message = Xcm(vec![
+            DescendOrigin(trading_origin),
+            WithdrawAsset(fee.clone().into()),
+            BuyExecution {
+                fees: fee,
+                weight_limit: Unlimited,
+            },
+            SetAppendix(Xcm(vec![
+                RefundSurplus,
+                DepositAsset {
+                    assets: Wild(AllCounted(1)),
+                    beneficiary: trading_account,
+                },
+            ])),
+            Transact {
+                origin_kind: OriginKind::SovereignAccount,
+                require_weight_at_most: HydraDxSwapWeight::get(),
+                call: call.into(),
+            },
+            ReportTransactStatus(QueryResponseInfo {
+                destination: here,
+                query_id,
+                max_weight: RemoteSwapResponseWeight::get(),
+            }),
+        ]);
+        send_xcm::<XcmRouter>(hydradx, message)
+            .map_err(|_| pallet_spectre::Error::<Runtime>::FailedToSendRemoteSwap)?;
+
+        Ok((query_id, hydradx))
+    }
+}
+
+/// Signed extension data of HydraDX transactions, the era, nonce and tip. The other extensions encode nothing
+type HydraDxSignedExtra = (Era, Compact<u32>, Compact<Balance>);
+
+// Signer of a signed HydraDX transaction, leaves `input` at the encoded call
+fn hydradx_signer(input: &mut &[u8]) -> Option<[u8; 32]> {
+    // the transaction is encoded as a byte vector
+    Compact::<u32>::decode(input).ok()?;
+    // signed, transaction format version 4
+    if u8::decode(input).ok()? != 0b1000_0100 {
+        return None;
+    }
+    let MultiAddress::Id(signer) = MultiAddress::<AccountId32, ()>::decode(input).ok()? else {
+        return None;
+    };
+    MultiSignature::decode(input).ok()?;
+    HydraDxSignedExtra::decode(input).ok()?;
+    Some(signer.into())
+}
+
+/// Decodes the Omnipool sell and buy calls of the HydraDX transactions proven by trade proofs. The transaction
+/// must be signed by the trading account, calls of other pallets decode no trades and only count for the trade
+/// frequency. Trading accounts trade the allocated asset, the position is valued at the amount sold for it
+pub struct HydraDxOmnipoolTrades;
+
+impl TradeDecoder<Runtime> for HydraDxOmnipoolTrades {
+    fn decode_trades(
+        network: NetworkId,
+        _network_info: &NetworkInfo,
+        trading_account: &RemoteAccount,
+        transaction: &[u8],
+    ) -> Result<Vec<DecodedTrade<Balance>>, DispatchError> {
+        let invalid = pallet_spectre::Error::<Runtime>::InvalidTradeTransaction;
+        let mut input = transaction;
+        let signer = hydradx_signer(&mut input).ok_or(invalid)?;
+        if RemoteAccount::AccountId32(signer) != *trading_account {
+            return Err(invalid.into());
+        }
+
+        let (pallet_index, call_index) = <(u8, u8)>::decode(&mut input).map_err(|_| invalid)?;
+        let Some((venue, calls)) =
+            DexVenues::<Runtime>::iter().find_map(|(venue_id, venue)| match venue.swap_calls {
+                Some(calls)
+                    if venue.network == network
+                        && venue.dex == SupportedDexs::HydraDx
+                        && venue.pallet_index == Some(pallet_index) =>
+                {
+                    Some((venue_id, calls))
+                }
+                _ => None,
+            })
+        else {
+            return Ok(Vec::new());
+        };
+        let (asset_in, asset_out, amount_in, amount_out) = if call_index == calls.sell {
+            let (asset_in, asset_out, amount, min_buy_amount) =
+                <(u32, u32, u128, u128)>::decode(&mut input).map_err(|_| invalid)?;
+            (asset_in, asset_out, amount, min_buy_amount)
+        } else if call_index == calls.buy {
+            let (asset_out, asset_in, amount, max_sell_amount) =
+                <(u32, u32, u128, u128)>::decode(&mut input).map_err(|_| invalid)?;
+            (asset_in, asset_out, max_sell_amount, amount)
+        } else {
+            return Ok(Vec::new());
+        };
+
+        Ok(vec![DecodedTrade {
+            venue,
+            asset_in: BoundedVec::truncate_from(asset_in.encode()),
+            asset_out: BoundedVec::truncate_from(asset_out.encode()),
+            amount_in,
+            amount_out,
+            position_value: amount_in,
+        }])
+    }
+}
+
+/// Checks the balance of the trading accounts the pools control on sibling parachains, the accounts
+/// `XcmCapitalAllocator` funds and the remote swaps run from. The account withdraws the claimed balance of the
+/// pool asset into the holding, `ReportHolding` reports it capped at the claim to the `pallet_xcm` query
+/// notifying `balance_query_response`, and the assets are deposited back. The response is only accepted from the
+/// queried network
+pub struct XcmBalanceQueries;
+
+impl RemoteBalanceQuerier<Runtime> for XcmBalanceQueries {
+    fn query_balance(
+        pool_account: &AccountId,
+        trader_id: &AccountId,
+        network: &NetworkInfo,
+        asset_id: &AssetId,
+        balance: Balance,
+    ) -> Result<(QueryId, MultiLocation), DispatchError> {
+        let destination = network_location(network)?;
+        let asset = remote_asset(asset_id, &destination)?;
+        let fee_amount = remote_fee_in(*asset_id, BalanceQueryFee::get())?;
+        // the unspent fee stays in the holding, the claim paid in the same asset must exceed it
+        if balance <= fee_amount {
+            return Err(pallet_spectre::Error::<Runtime>::FailedToSendBalanceQuery.into());
+        }
+
+        let trading_origin = pool_trading_origin(pool_account, trader_id);
+        let here = sibling_location();
+        let trading_account = pool_trading_account(pool_account, trader_id)?;
+        // the holding is reported by the trading origin, seen from here under this chain
+        let query_id = PolkadotXcm::new_notify_query(
+            destination,
+            pallet_spectre::Call::<Runtime>::balance_query_response {
+                query_id: 0,
+                response: Response::Null,
+            },
+            frame_system::Pallet::<Runtime>::block_number() + RemoteSwapTimeout::get(),
+            MultiLocation::new(0, trading_origin),
+        );
+
+        let fee: MultiAsset = (asset, fee_amount).into();
+        let claim: MultiAsset = (asset, balance).into();
+        let message = Xcm(vec![
+            DescendOrigin(trading_origin),
+            WithdrawAsset(fee.clone().into()),
+            BuyExecution {
+                fees: fee,
+                weight_limit: Unlimited,
+            },
+            SetAppendix(Xcm(vec![
+                ReportHolding {
+                    response_info: QueryResponseInfo {
+                        destination: here,
+                        query_id,
+                        max_weight: BalanceQueryResponseWeight::get(),
+                    },
+                    assets: Definite(claim.clone().into()),
+                },
+                RefundSurplus,
+                DepositAsset {
+                    assets: Wild(AllCounted(2)),
+                    beneficiary: trading_account,
+                },
+            ])),
+            // fails and leaves the claim out of the holding when the account does not hold it
+            WithdrawAsset(claim.into()),
+        ]);
+        send_xcm::<XcmRouter>(destination, message)
+            .map_err(|_| pallet_spectre::Error::<Runtime>::FailedToSendBalanceQuery)?;
+
+        Ok((query_id, destination))
+    }
+
+    fn reported_balance(asset_id: &AssetId, response: &Response) -> Balance {
+        let Response::Assets(assets) = response else {
+            return 0;
+        };
+        assets
+            .inner()
+            .iter()
+            .filter_map(|asset| match asset {
+                MultiAsset {
+                    id: Concrete(location),
+                    fun: Fungible(amount),
+                } if CurrencyIdEquivalence::convert(location) == Some(*asset_id) => Some(*amount),
+                _ => None,
+            })
+            .fold(0, |total: Balance, amount| total.saturating_add(amount))
+    }
+}
+
+/// Origin of the trader registrar contract, e.g. the Phala contract on its parachain, dispatching
+/// `Transact` with `OriginKind::Xcm` so `XcmPassthrough` keeps its location as the origin
+pub type EnsureXcmTraderRegistrar = pallet_xcm::EnsureXcm<IsXcmTraderRegistrar<Runtime>>;